@@ -5,3 +5,8 @@ import { AuthService } from '@app/services/auth.service';
 export const authGuard: CanActivateFn = (_route, _state) => {
   return inject(AuthService).canActivateAuthenticated();
 };
+
+/** Admin screens are only shown to users with the role `admin`. The server checks the role again. */
+export const adminGuard: CanActivateFn = (_route, _state) => {
+  return inject(AuthService).canActivateAdmin();
+};
@@ -2,4 +2,13 @@ export interface User {
     id: number,
     email: string,
     name: string,
+    timezone: string,
+    email_verified: boolean,
+    roles: Role[],
+}
+
+export type Role = 'user' | 'admin';
+
+export function isAdmin(user: User | null): boolean {
+    return !!user && user.roles.includes('admin');
 }
\ No newline at end of file
@@ -19,9 +19,10 @@ export class RegisterTotpComponent {
   constructor(private http: HttpClient) {
   }
 
-  debugTotp() {
-    this.http.get('/api/totp/debug-user-data').subscribe(data => {
-      console.log(data);
+  checkStatus() {
+    this.http.get<{ enabled: boolean }>('/api/totp/status').subscribe(status => {
+      this.totpMessage = status.enabled ? '2FA is enabled' : '2FA is not enabled';
+      this.isErrorMessage = false;
     })
   }
 
@@ -2,7 +2,7 @@ import { Injectable } from '@angular/core';
 import { BehaviorSubject, catchError, map, Observable, of, take } from 'rxjs';
 import { HttpClient } from '@angular/common/http';
 import { Router } from '@angular/router';
-import { User } from '../models/user.model';
+import { isAdmin, User } from '../models/user.model';
 
 @Injectable({
   providedIn: 'root'
@@ -67,6 +67,24 @@ export class AuthService {
     );
   }
 
+  canActivateAdmin(): Observable<boolean> {
+    return this.http.get<User>("/api/current-user")
+    .pipe(
+      catchError(() => {
+        return of(null)
+      }),
+      take(1),
+      map(user => {
+        if (!isAdmin(user)) {
+          this.router.navigate([user ? '/account' : '/login'])
+          return false;
+        }
+
+        return true;
+      })
+    );
+  }
+
   logout() {
     this.http.post('/api/logout', null).subscribe(() => {
       this._userSubject.next(null);
@@ -1,26 +1,40 @@
 use std::sync::Arc;
 
 use actix_files::Files;
-use authfix::{actix_session::{config::{PersistentSession, SessionLifecycle}, storage::CookieSessionStore, SessionMiddleware}, mfa::MfaConfig, multifactor::authenticator::AuthenticatorFactor};
-use actix_web::{body::MessageBody, cookie::Key, dev::{ServiceFactory, ServiceRequest, ServiceResponse}, get, web::{self, Data}, App, Error, HttpResponse, Responder};
+use authfix::{actix_session::{config::{PersistentSession, SessionLifecycle}, SessionMiddleware}, mfa::MfaConfig, multifactor::{authenticator::AuthenticatorFactor, Factor}};
+use actix_web::{body::MessageBody, cookie::{time::Duration, Cookie, Key, SameSite}, dev::{ServiceFactory, ServiceRequest, ServiceResponse}, get, middleware::from_fn, web::{self, Data}, App, Error, HttpResponse, Responder};
 use authfix::{config::Routes, session::app_builder::SessionLoginAppBuilder};
 use serde::Serialize;
 
-use crate::{config::db::DbConfig, controller::{activity_controller, mfa_controller, root_controller}, domain::{user_api::UserApi}, service::{auth_service::{AuthenticationService, HandleMfaRequestImpl}, user_service::UserService}};
+use crate::{config::{config::Config, cookie_key::CookieKeys, db::DbConfig}, controller::{account_controller, admin_controller, api_token_controller, session_controller, activity_controller, calendar_controller, category_controller, goal_controller, import_controller, mfa_controller, password_controller, registration_controller, root_controller, stats_controller, tag_controller, timer_controller}, domain::{activity_api::ActivityApi, api_token_api::ApiTokenApi, audit_event_api::AuditEventApi, email_otp_api::EmailOtpApi, login_attempt_api::LoginAttemptApi, auth_api::AuthenticationApi, activity_session_api::ActivitySessionApi, calendar_feed_api::CalendarFeedApi, category_api::CategoryApi, goal_api::GoalApi, mailer_api::Mailer, recovery_code_api::RecoveryCodeApi, session_api::SessionApi, stats_api::StatsApi, tag_api::TagApi, user_api::UserApi, user_token_api::UserTokenApi}, middleware::{accept_previous_cookie_key, audit_logins, authenticate_bearer_tokens, reject_stale_sessions, throttle_logins, track_sessions}, service::{activity_service::ActivityService, api_token_service::ApiTokenService, audit_event_service::AuditEventService, audited_factor::AuditedFactor, activity_session_service::ActivitySessionService, auth_service::{AuthenticationService, HandleMfaRequestImpl}, calendar_feed_service::CalendarFeedService, category_service::CategoryService, email_otp_factor::EmailOtpFactor, email_otp_service::EmailOtpService, file_mailer::FileMailer, goal_service::GoalService, login_attempt_service::LoginAttemptService, recovery_code_factor::RecoveryCodeFactor, recovery_code_service::RecoveryCodeService, secret_cipher::SecretCipher, session_service::SessionService, smtp_mailer::SmtpMailer, stats_service::StatsService, tag_service::TagService, throttled_factor::ThrottledFactor, user_service::UserService, user_token_service::UserTokenService}};
 
 
-pub fn create_test_session_middleware (key: Key) -> SessionMiddleware<CookieSessionStore> {
-    let persistent_session = PersistentSession::default();
+pub const SESSION_COOKIE_NAME: &str = "sessionId";
+const SESSION_TTL_DAYS: i64 = 1;
+
+/// The cookie only holds the session key, the session state is kept by `SessionService`
+pub fn create_session_middleware(store: SessionService, key: Key) -> SessionMiddleware<SessionService> {
+    let persistent_session = PersistentSession::default().session_ttl(Duration::days(SESSION_TTL_DAYS));
     let lc = SessionLifecycle::PersistentSession(persistent_session);
-    SessionMiddleware::builder(CookieSessionStore::default(), key)
-                .cookie_name("sessionId".to_string())
+    SessionMiddleware::builder(store, key)
+                .cookie_name(SESSION_COOKIE_NAME.to_string())
                 .cookie_http_only(true)
-                .cookie_same_site(actix_web::cookie::SameSite::Lax)
+                .cookie_same_site(SameSite::Lax)
                 .cookie_secure(false)
                 .session_lifecycle(lc)
                 .build()
 }
 
+/// Sets the attributes of `create_session_middleware` for a session cookie that is created outside the session middleware
+pub fn session_cookie(mut cookie: Cookie<'static>) -> Cookie<'static> {
+    cookie.set_path("/");
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_secure(false);
+    cookie.set_max_age(Duration::days(SESSION_TTL_DAYS));
+    cookie
+}
+
 #[derive(Serialize)]
 struct TestResponse {
     pub test: i32,
@@ -32,7 +46,7 @@ async fn test_endpoint() -> impl Responder {
     HttpResponse::Ok().json(TestResponse { test: 42, title: "MyActivities".to_owned() })
 }
 
-pub fn create_app(cookie_key: Key, db_config: DbConfig) -> App<
+pub fn create_app(cookie_keys: CookieKeys, secret_cipher: Arc<SecretCipher>, db_config: DbConfig, config: Config) -> App<
 impl ServiceFactory<
     ServiceRequest,
     Response = ServiceResponse<impl MessageBody>,
@@ -41,27 +55,207 @@ impl ServiceFactory<
     Error = Error,
 >> {
     
-    let user_service= Arc::new(UserService::new(Arc::new(db_config)));
+    let db_config = Arc::new(db_config);
+
+    let mailer: Arc<dyn Mailer> = match &config.smtp {
+        Some(smtp) => Arc::new(SmtpMailer::new(smtp, &config.mail_from).expect("Invalid SMTP configuration")),
+        None => Arc::new(FileMailer::new(&config.mail_dir)),
+    };
+    let mailer_data = Data::from(Arc::clone(&mailer));
+    let login_attempt_api: Arc<dyn LoginAttemptApi> = Arc::new(LoginAttemptService::new(Arc::clone(&db_config), config.login_throttle.clone()));
+    let login_attempt_api_data = Data::from(Arc::clone(&login_attempt_api));
+    let config_data = Data::new(config);
+    let user_service= Arc::new(UserService::new(Arc::clone(&db_config), secret_cipher));
     let user_api: Arc<dyn UserApi> = Arc::clone(&user_service) as Arc<dyn UserApi>;
     let user_api_data = Data::from(user_api);
 
+    let activity_api: Arc<dyn ActivityApi> = Arc::new(ActivityService::new(Arc::clone(&db_config)));
+    let activity_api_data = Data::from(activity_api);
+
+    let activity_session_api: Arc<dyn ActivitySessionApi> = Arc::new(ActivitySessionService::new(Arc::clone(&db_config)));
+    let activity_session_api_data = Data::from(activity_session_api);
+
+    let category_api: Arc<dyn CategoryApi> = Arc::new(CategoryService::new(Arc::clone(&db_config)));
+    let category_api_data = Data::from(category_api);
+
+    let tag_api: Arc<dyn TagApi> = Arc::new(TagService::new(Arc::clone(&db_config)));
+    let tag_api_data = Data::from(tag_api);
+
+    let goal_api: Arc<dyn GoalApi> = Arc::new(GoalService::new(Arc::clone(&db_config)));
+    let goal_api_data = Data::from(goal_api);
+
+    let stats_api: Arc<dyn StatsApi> = Arc::new(StatsService::new(Arc::clone(&db_config)));
+    let stats_api_data = Data::from(stats_api);
+
+    let calendar_feed_api: Arc<dyn CalendarFeedApi> = Arc::new(CalendarFeedService::new(Arc::clone(&db_config)));
+    let calendar_feed_api_data = Data::from(calendar_feed_api);
+
+    let user_token_api: Arc<dyn UserTokenApi> = Arc::new(UserTokenService::new(Arc::clone(&db_config)));
+    let user_token_api_data = Data::from(user_token_api);
+
+    let auth_api: Arc<dyn AuthenticationApi> = Arc::new(AuthenticationService::new(Arc::clone(&user_service)));
+    let auth_api_data = Data::from(auth_api);
+
+    let recovery_code_api: Arc<dyn RecoveryCodeApi> = Arc::new(RecoveryCodeService::new(Arc::clone(&db_config)));
+    let recovery_code_api_data = Data::from(Arc::clone(&recovery_code_api));
+
+    let session_api: Arc<dyn SessionApi> = Arc::new(SessionService::new(Arc::clone(&db_config)));
+    let session_api_data = Data::from(session_api);
+
+    let api_token_api: Arc<dyn ApiTokenApi> = Arc::new(ApiTokenService::new(Arc::clone(&db_config)));
+    let api_token_api_data = Data::from(api_token_api);
+
+    let audit_event_api: Arc<dyn AuditEventApi> = Arc::new(AuditEventService::new(Arc::clone(&db_config)));
+    let audit_event_api_data = Data::from(Arc::clone(&audit_event_api));
+
     let routes = Routes::new("/api", "/login", "/login/mfa", "/logout");
+    let mut public_paths = vec!["/api/test", "/web/index.html"];
+    public_paths.extend_from_slice(calendar_controller::PUBLIC_PATHS);
+    public_paths.extend_from_slice(registration_controller::PUBLIC_PATHS);
+    public_paths.extend_from_slice(password_controller::PUBLIC_PATHS);
     let login_handler = AuthenticationService::new(Arc::clone(&user_service));
     let handle_mfa = HandleMfaRequestImpl::new(Arc::clone(&user_service));
 
-    let mfa_config = MfaConfig::new(vec![Box::new(AuthenticatorFactor::new(Arc::clone(&user_service)))], handle_mfa);
+    let email_otp_api: Arc<dyn EmailOtpApi> = Arc::new(EmailOtpService::new(Arc::clone(&db_config)));
+    let email_otp_factor = EmailOtpFactor::new(Arc::clone(&user_service) as Arc<dyn UserApi>, email_otp_api, mailer);
+
+    // recovery codes are accepted by every factor, so they are throttled and audited like its codes
+    let recover = |factor: Box<dyn Factor>| -> Box<dyn Factor> { Box::new(RecoveryCodeFactor::new(factor, Arc::clone(&recovery_code_api))) };
+    let throttle = |factor: Box<dyn Factor>| -> Box<dyn Factor> { Box::new(ThrottledFactor::new(factor, Arc::clone(&user_service) as Arc<dyn UserApi>, Arc::clone(&login_attempt_api))) };
+    // attempts rejected by the lockout are recorded as well
+    let audit = |factor: Box<dyn Factor>| -> Box<dyn Factor> { Box::new(AuditedFactor::new(factor, Arc::clone(&audit_event_api))) };
+    let mfa_config = MfaConfig::new(vec![
+        audit(throttle(recover(Box::new(AuthenticatorFactor::new(Arc::clone(&user_service)))))),
+        audit(throttle(recover(Box::new(email_otp_factor)))),
+    ], handle_mfa);
     
-    SessionLoginAppBuilder::create_with_session_middleware(login_handler, create_test_session_middleware(cookie_key))
-        .set_login_routes_and_public_paths(routes, vec!["/api/test", "/web/index.html"])
+    SessionLoginAppBuilder::create_with_session_middleware(login_handler, create_session_middleware(SessionService::new(Arc::clone(&db_config)), cookie_keys.current.clone()))
+        .set_login_routes_and_public_paths(routes, public_paths)
         .set_mfa(mfa_config)
         .build()
     .service(
         web::scope("/api")
+            .wrap(from_fn(track_sessions))
+            .wrap(from_fn(reject_stale_sessions))
             .service(test_endpoint)
             .configure(activity_controller::config)
+            .configure(timer_controller::config)
+            .configure(category_controller::config)
+            .configure(tag_controller::config)
+            .configure(goal_controller::config)
+            .configure(stats_controller::config)
+            .configure(calendar_controller::config)
+            .configure(import_controller::config)
+            .configure(registration_controller::config)
+            .configure(password_controller::config)
+            .configure(account_controller::config)
+            .configure(session_controller::config)
+            .configure(api_token_controller::config)
+            .configure(admin_controller::config)
             .configure(root_controller::config)
             .configure(mfa_controller::config)
     )
     .service(Files::new("/web", "./static"))
+    .app_data(config_data.clone())
     .app_data(user_api_data.clone())
-}
\ No newline at end of file
+    .app_data(auth_api_data.clone())
+    .app_data(activity_api_data.clone())
+    .app_data(activity_session_api_data.clone())
+    .app_data(category_api_data.clone())
+    .app_data(tag_api_data.clone())
+    .app_data(goal_api_data.clone())
+    .app_data(stats_api_data.clone())
+    .app_data(calendar_feed_api_data.clone())
+    .app_data(user_token_api_data.clone())
+    .app_data(mailer_data.clone())
+    .app_data(recovery_code_api_data.clone())
+    .app_data(login_attempt_api_data.clone())
+    .app_data(session_api_data.clone())
+    .app_data(api_token_api_data.clone())
+    .app_data(audit_event_api_data.clone())
+    .app_data(Data::new(cookie_keys))
+    .wrap(from_fn(throttle_logins))
+    .wrap(from_fn(audit_logins))
+    .wrap(from_fn(accept_previous_cookie_key))
+    .wrap(from_fn(authenticate_bearer_tokens))
+}
+
+#[cfg(test)]
+mod app_factory_tests {
+    use std::sync::Arc;
+
+    use actix_web::{cookie::Key, http::StatusCode, test};
+    use rusqlite::Connection;
+    use serde_json::json;
+
+    use crate::{config::{config::Config, cookie_key::CookieKeys, db::DbConfig}, create_db, domain::{user::User, user_api::UserApi}, service::{secret_cipher::SecretCipher, secure_token, user_service::UserService}};
+
+    use super::{create_app, SESSION_COOKIE_NAME};
+
+    struct TestSetup {
+        _db: Connection,
+        cookie_keys: CookieKeys,
+        secret_cipher: Arc<SecretCipher>,
+        config: Config,
+        user_service: UserService,
+        user: User,
+    }
+
+    /// Creates the verified user `linda@example.org` without admin role
+    async fn setup(database: &str) -> TestSetup {
+        let db_config = DbConfig::new(database);
+        let db = create_db(&db_config);
+        let secret_cipher = Arc::new(SecretCipher::new(&SecretCipher::generate_key()).unwrap());
+        let user_service = UserService::new(Arc::new(db_config), Arc::clone(&secret_cipher));
+        let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        let user = user_service.save_user_with_credentials(user, "linda123").await.unwrap();
+        user_service.mark_email_verified(user.id).await.unwrap();
+
+        let mut config = Config::from_env();
+        config.cookie_key.key = Some(secure_token::to_hex(Key::generate().master()));
+        let cookie_keys = CookieKeys::load(&config.cookie_key).unwrap();
+
+        TestSetup { _db: db, cookie_keys, secret_cipher, config, user_service, user }
+    }
+
+    fn login_request() -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(json!({ "email": "linda@example.org", "password": "linda123" }))
+    }
+
+    #[actix_web::test]
+    async fn should_reject_non_admin_at_admin_endpoints() {
+        let database = "file:app_factory_admin_test?mode=memory&cache=shared";
+        let setup = setup(database).await;
+        let app = test::init_service(create_app(setup.cookie_keys, setup.secret_cipher, DbConfig::new(database), setup.config)).await;
+
+        let res = test::call_service(&app, login_request().to_request()).await;
+        assert!(res.status().is_success(), "Login should succeed");
+        let cookie = res.response().cookies().find(|cookie| cookie.name() == SESSION_COOKIE_NAME).unwrap().into_owned();
+
+        let req = test::TestRequest::get().uri("/api/admin/users").cookie(cookie).to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn should_end_session_of_disabled_user() {
+        let database = "file:app_factory_disabled_test?mode=memory&cache=shared";
+        let setup = setup(database).await;
+        let app = test::init_service(create_app(setup.cookie_keys, setup.secret_cipher, DbConfig::new(database), setup.config)).await;
+
+        let res = test::call_service(&app, login_request().to_request()).await;
+        assert!(res.status().is_success(), "Login should succeed");
+        let cookie = res.response().cookies().find(|cookie| cookie.name() == SESSION_COOKIE_NAME).unwrap().into_owned();
+
+        let req = test::TestRequest::get().uri("/api/current-user").cookie(cookie.clone()).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        setup.user_service.set_disabled(setup.user.id, true).await.unwrap();
+
+        let req = test::TestRequest::get().uri("/api/current-user").cookie(cookie).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
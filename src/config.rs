@@ -1,2 +1,4 @@
 pub mod config;
-pub mod db;
\ No newline at end of file
+pub mod db;
+pub mod cookie_key;
+pub mod key_file;
\ No newline at end of file
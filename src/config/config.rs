@@ -1,9 +1,80 @@
+use chrono::{DateTime, Utc};
+
 const DEFAULT_HOST: &str = "127.0.0.1";
 const DEFAULT_PORT: u16 = 5665;
+const DEFAULT_SMTP_PORT: u16 = 587;
+const DEFAULT_MAIL_FROM: &str = "MyActivities <noreply@localhost>";
+const DEFAULT_MAIL_DIR: &str = "mails";
+const DEFAULT_COOKIE_KEY_FILE: &str = "cookie_key";
+const DEFAULT_MFA_SECRET_KEY_FILE: &str = "mfa_secret_key";
+const DEFAULT_LOGIN_MAX_FAILURES: u32 = 5;
+const DEFAULT_LOGIN_IP_MAX_FAILURES: u32 = 20;
+const DEFAULT_LOGIN_LOCKOUT_SECONDS: i64 = 30;
+const DEFAULT_LOGIN_MAX_LOCKOUT_SECONDS: i64 = 3600;
+
+#[derive(Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+}
+
+/// Keys are hex encoded and at least 64 bytes long. If `key` is not set, it is read from `key_file`,
+/// which is created on the first start. To rotate the key, the old one is set as `previous_key`
+/// and still accepted until `previous_key_valid_until`.
+#[derive(Clone)]
+pub struct CookieKeyConfig {
+    pub key: Option<String>,
+    pub key_file: String,
+    pub previous_key: Option<String>,
+    pub previous_key_valid_until: Option<DateTime<Utc>>,
+}
+
+/// After `max_failures` failed logins the subject is locked for `lockout_seconds`.
+/// Every further failure doubles the lockout up to `max_lockout_seconds`.
+#[derive(Clone)]
+pub struct LoginThrottleConfig {
+    /// Failures per account (email address)
+    pub max_failures: u32,
+    /// Failures per client ip, forgotten after a quiet period. The ip is the peer address of the connection,
+    /// see `middleware::throttle_logins` for running behind a proxy.
+    pub ip_max_failures: u32,
+    pub lockout_seconds: i64,
+    pub max_lockout_seconds: i64,
+}
 
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: DEFAULT_LOGIN_MAX_FAILURES,
+            ip_max_failures: DEFAULT_LOGIN_IP_MAX_FAILURES,
+            lockout_seconds: DEFAULT_LOGIN_LOCKOUT_SECONDS,
+            max_lockout_seconds: DEFAULT_LOGIN_MAX_LOCKOUT_SECONDS,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub host: String,
     pub port: u16,
+    /// Allows everyone to create an account via `POST /api/register`
+    pub open_registration: bool,
+    /// Public url of the server, used for links in mails
+    pub base_url: String,
+    pub mail_from: String,
+    /// Mails are sent via SMTP if `MA_SMTP_HOST` is set, otherwise they are written to `mail_dir`
+    pub smtp: Option<SmtpConfig>,
+    pub mail_dir: String,
+    pub login_throttle: LoginThrottleConfig,
+    pub cookie_key: CookieKeyConfig,
+    /// Hex encoded 32 byte key to encrypt TOTP secrets. If not set, it is read from `mfa_secret_key_file`,
+    /// which is created on the first start.
+    pub mfa_secret_key: Option<String>,
+    pub mfa_secret_key_file: String,
+    /// The user with this email address gets the admin role on start
+    pub admin_email: Option<String>,
 }
 
 impl Config {
@@ -18,9 +89,72 @@ impl Config {
             Err(_) => DEFAULT_PORT
         };
 
+        let open_registration = match std::env::var("MA_OPEN_REGISTRATION") {
+            Ok(r) => r.parse().expect("MA_OPEN_REGISTRATION must be `true` or `false`"),
+            Err(_) => false
+        };
+
+        let base_url = match std::env::var("MA_BASE_URL") {
+            Ok(u) => u.trim_end_matches('/').to_owned(),
+            Err(_) => format!("http://{}:{}", host, port)
+        };
+
+        let smtp = match std::env::var("MA_SMTP_HOST") {
+            Ok(smtp_host) => Some(SmtpConfig {
+                host: smtp_host,
+                port: match std::env::var("MA_SMTP_PORT") {
+                    Ok(p) => p.parse().expect("MA_SMTP_PORT must be of type u16"),
+                    Err(_) => DEFAULT_SMTP_PORT
+                },
+                username: std::env::var("MA_SMTP_USER").expect("MA_SMTP_USER is required if MA_SMTP_HOST is set"),
+                password: std::env::var("MA_SMTP_PASSWORD").expect("MA_SMTP_PASSWORD is required if MA_SMTP_HOST is set"),
+            }),
+            Err(_) => None
+        };
+
+        let login_throttle = LoginThrottleConfig {
+            max_failures: match std::env::var("MA_LOGIN_MAX_FAILURES") {
+                Ok(f) => f.parse().expect("MA_LOGIN_MAX_FAILURES must be of type u32"),
+                Err(_) => DEFAULT_LOGIN_MAX_FAILURES
+            },
+            ip_max_failures: match std::env::var("MA_LOGIN_IP_MAX_FAILURES") {
+                Ok(f) => f.parse().expect("MA_LOGIN_IP_MAX_FAILURES must be of type u32"),
+                Err(_) => DEFAULT_LOGIN_IP_MAX_FAILURES
+            },
+            lockout_seconds: match std::env::var("MA_LOGIN_LOCKOUT_SECONDS") {
+                Ok(s) => s.parse().expect("MA_LOGIN_LOCKOUT_SECONDS must be of type i64"),
+                Err(_) => DEFAULT_LOGIN_LOCKOUT_SECONDS
+            },
+            max_lockout_seconds: match std::env::var("MA_LOGIN_MAX_LOCKOUT_SECONDS") {
+                Ok(s) => s.parse().expect("MA_LOGIN_MAX_LOCKOUT_SECONDS must be of type i64"),
+                Err(_) => DEFAULT_LOGIN_MAX_LOCKOUT_SECONDS
+            },
+        };
+
+        let previous_key = std::env::var("MA_COOKIE_PREVIOUS_KEY").ok();
+        let cookie_key = CookieKeyConfig {
+            key: std::env::var("MA_COOKIE_KEY").ok(),
+            key_file: std::env::var("MA_COOKIE_KEY_FILE").unwrap_or_else(|_| DEFAULT_COOKIE_KEY_FILE.to_owned()),
+            previous_key_valid_until: previous_key.as_ref().map(|_| {
+                let valid_until = std::env::var("MA_COOKIE_PREVIOUS_KEY_UNTIL").expect("MA_COOKIE_PREVIOUS_KEY_UNTIL is required if MA_COOKIE_PREVIOUS_KEY is set");
+                DateTime::parse_from_rfc3339(&valid_until).expect("MA_COOKIE_PREVIOUS_KEY_UNTIL must be a RFC 3339 date").to_utc()
+            }),
+            previous_key,
+        };
+
         Config {
             host,
             port,
+            open_registration,
+            base_url,
+            mail_from: std::env::var("MA_MAIL_FROM").unwrap_or_else(|_| DEFAULT_MAIL_FROM.to_owned()),
+            smtp,
+            mail_dir: std::env::var("MA_MAIL_DIR").unwrap_or_else(|_| DEFAULT_MAIL_DIR.to_owned()),
+            login_throttle,
+            cookie_key,
+            mfa_secret_key: std::env::var("MA_MFA_SECRET_KEY").ok(),
+            mfa_secret_key_file: std::env::var("MA_MFA_SECRET_KEY_FILE").unwrap_or_else(|_| DEFAULT_MFA_SECRET_KEY_FILE.to_owned()),
+            admin_email: std::env::var("MA_ADMIN_EMAIL").ok(),
         }
     }
 }
@@ -35,6 +169,12 @@ mod tests {
 
         assert_eq!(c.host, "127.0.0.1".to_string());
         assert_eq!(c.port, 5665);
+        assert!(!c.open_registration);
+        assert_eq!(c.base_url, "http://127.0.0.1:5665");
+        assert!(c.smtp.is_none());
+        assert_eq!(c.login_throttle.max_failures, 5);
+        assert_eq!(c.cookie_key.key_file, "cookie_key");
+        assert!(c.cookie_key.previous_key.is_none());
     }
 
 }
@@ -0,0 +1,122 @@
+use std::io;
+
+use actix_web::cookie::{Cookie, CookieJar, Key};
+use chrono::{DateTime, Utc};
+
+use crate::{config::{config::CookieKeyConfig, key_file}, service::secure_token};
+
+/// Key for the session cookie and the previous key during a rotation
+#[derive(Clone)]
+pub struct CookieKeys {
+    pub current: Key,
+    previous: Option<(Key, DateTime<Utc>)>,
+}
+
+fn to_key(bytes: &[u8]) -> io::Result<Key> {
+    Key::try_from(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Cookie key must be at least 64 bytes long"))
+}
+
+fn parse_key(hex: &str) -> io::Result<Key> {
+    let bytes = secure_token::from_hex(hex.trim())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Cookie key is not hex encoded"))?;
+
+    to_key(&bytes)
+}
+
+impl CookieKeys {
+    pub fn load(config: &CookieKeyConfig) -> io::Result<Self> {
+        let current = match &config.key {
+            Some(hex) => parse_key(hex)?,
+            None => to_key(&key_file::load_or_create(&config.key_file, || Key::generate().master().to_vec())?)?,
+        };
+
+        let previous = match (&config.previous_key, config.previous_key_valid_until) {
+            (Some(hex), Some(valid_until)) => Some((parse_key(hex)?, valid_until)),
+            _ => None,
+        };
+
+        Ok(Self { current, previous })
+    }
+
+    fn previous_key(&self) -> Option<&Key> {
+        self.previous.as_ref()
+            .filter(|(_, valid_until)| *valid_until > Utc::now())
+            .map(|(key, _)| key)
+    }
+
+    /// Encrypts the cookie value with the current key like the session middleware does
+    pub fn encrypt(&self, name: &str, value: String) -> Option<String> {
+        let mut jar = CookieJar::new();
+        jar.private_mut(&self.current).add(Cookie::new(name.to_owned(), value));
+        jar.get(name).map(|cookie| cookie.value().to_owned())
+    }
+
+    /// If the cookie `name` in the `Cookie` header was encrypted with the previous key, it is encrypted again with the
+    /// current key. Returns the new header value and the renewed cookie. `None` if nothing needs to be renewed.
+    pub fn renew_cookie(&self, cookie_header: &str, name: &str) -> Option<(String, Cookie<'static>)> {
+        let previous_key = self.previous_key()?;
+        let cookies: Vec<Cookie> = cookie_header.split(';')
+            .filter_map(|cookie| Cookie::parse(cookie.trim()).ok())
+            .collect();
+
+        let mut jar = CookieJar::new();
+        jar.add_original(cookies.iter().find(|cookie| cookie.name() == name)?.clone().into_owned());
+        if jar.private(&self.current).get(name).is_some() {
+            return None;
+        }
+
+        let decrypted = jar.private(previous_key).get(name)?;
+        let mut renewed_jar = CookieJar::new();
+        renewed_jar.private_mut(&self.current).add(decrypted);
+        let renewed = renewed_jar.get(name)?.clone();
+
+        let header = cookies.iter()
+            .map(|cookie| {
+                let value = if cookie.name() == name { renewed.value() } else { cookie.value() };
+                format!("{}={}", cookie.name(), value)
+            })
+            .collect::<Vec<String>>()
+            .join("; ");
+
+        Some((header, renewed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::cookie::{Cookie, CookieJar, Key};
+    use chrono::{Duration, Utc};
+
+    use super::CookieKeys;
+
+    fn encrypt(key: &Key, value: &str) -> String {
+        let mut jar = CookieJar::new();
+        jar.private_mut(key).add(Cookie::new("sessionId", value.to_owned()));
+        jar.get("sessionId").unwrap().value().to_owned()
+    }
+
+    #[test]
+    fn should_renew_cookie_of_previous_key_during_grace_period() {
+        let previous = Key::generate();
+        let keys = CookieKeys { current: Key::generate(), previous: Some((previous.clone(), Utc::now() + Duration::days(1))) };
+        let header = format!("lang=de; sessionId={}", encrypt(&previous, "abc"));
+
+        let (renewed_header, cookie) = keys.renew_cookie(&header, "sessionId").expect("Cookie should be renewed");
+
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+        assert_eq!(jar.private(&keys.current).get("sessionId").unwrap().value(), "abc");
+        assert!(renewed_header.starts_with("lang=de; sessionId="));
+        assert!(keys.renew_cookie(&renewed_header, "sessionId").is_none(), "A cookie of the current key is kept");
+    }
+
+    #[test]
+    fn should_reject_previous_key_after_grace_period() {
+        let previous = Key::generate();
+        let keys = CookieKeys { current: Key::generate(), previous: Some((previous.clone(), Utc::now() - Duration::days(1))) };
+        let header = format!("sessionId={}", encrypt(&previous, "abc"));
+
+        assert!(keys.renew_cookie(&header, "sessionId").is_none());
+    }
+}
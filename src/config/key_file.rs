@@ -0,0 +1,26 @@
+use std::{fs::{self, OpenOptions}, io::{self, Write}};
+
+use crate::service::secure_token;
+
+/// Reads a hex encoded key from the file or creates the file with the generated key, so the key survives a restart
+pub fn load_or_create(path: &str, generate: impl FnOnce() -> Vec<u8>) -> io::Result<Vec<u8>> {
+    match fs::read_to_string(path) {
+        Ok(hex) => secure_token::from_hex(hex.trim())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Key in {} is not hex encoded", path))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let key = generate();
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            // only the owner may read the key, also for the moment between creating and writing the file
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            options.open(path)?.write_all(secure_token::to_hex(&key).as_bytes())?;
+            log::info!("Created new key in {}", path);
+            Ok(key)
+        },
+        Err(err) => Err(err),
+    }
+}
@@ -1,3 +1,16 @@
 pub mod activity_controller;
 pub mod root_controller;
-pub mod mfa_controller;
\ No newline at end of file
+pub mod mfa_controller;
+pub mod timer_controller;
+pub mod category_controller;
+pub mod tag_controller;
+pub mod goal_controller;
+pub mod stats_controller;
+pub mod calendar_controller;
+pub mod import_controller;
+pub mod registration_controller;
+pub mod password_controller;
+pub mod account_controller;
+pub mod session_controller;
+pub mod api_token_controller;
+pub mod admin_controller;
@@ -0,0 +1,109 @@
+use actix_web::{error, get, post, web::{Data, Json, Query, ServiceConfig}, HttpRequest, HttpResponse, Responder, Result};
+use authfix::{actix_session::Session, multifactor::authenticator::Authenticator, AuthToken};
+use serde::{Deserialize, Serialize};
+
+use std::future::Future;
+
+use crate::{domain::{audit_event::{AuditEvent, AuditEventFilter, AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi, auth_api::AuthenticationApi, login_attempt::LoginSubject, login_attempt_api::LoginAttemptApi, user::{self, User}, user_api::UserApi}, service::audit_log};
+
+const SECURITY_LOG_PAGE_SIZE: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequestBody {
+    current_password: String,
+    new_password: String,
+    /// TOTP code, required if an authenticator is configured
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SecurityLogQuery {
+    /// Starts with 1
+    page: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SecurityLogResponse {
+    events: Vec<AuditEvent>,
+    total: i64,
+    page: i64,
+    page_size: i64,
+}
+
+/// Checks a password or code the current user entered to confirm a change. Wrong values count as failed logins
+/// of the account like at login, so a session cannot be used to guess them. Correct values do not reset the count,
+/// otherwise the password would unlock the guessing of the code in the same request.
+pub async fn verify_credential(user: &User, check: impl Future<Output = bool>, login_attempt_api: &Data<dyn LoginAttemptApi>) -> Result<bool> {
+    let account = LoginSubject::Account(user.email.clone());
+    match login_attempt_api.locked_until(&account).await {
+        Ok(Some(_)) => return Err(error::ErrorTooManyRequests("Too many failed attempts, try again later")),
+        Ok(None) => {},
+        Err(err) => log::error!("Cannot check login lockout: {}", err),
+    }
+
+    let correct = check.await;
+    if !correct {
+        if let Err(err) = login_attempt_api.record_failure(&account).await {
+            log::error!("Cannot record failed attempt: {}", err);
+        }
+    }
+
+    Ok(correct)
+}
+
+/// Changes the password of the current user. All sessions end, including the current one.
+#[post("/account/password")]
+pub async fn change_password(req: HttpRequest, body: Json<ChangePasswordRequestBody>, token: AuthToken<User>, session: Session, user_api: Data<dyn UserApi>, auth_api: Data<dyn AuthenticationApi>, audit_event_api: Data<dyn AuditEventApi>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let user = token.get_authenticated_user();
+    let body = body.into_inner();
+
+    if !verify_credential(user, auth_api.is_password_correct(user, &body.current_password), &login_attempt_api).await? {
+        return Err(error::ErrorForbidden("The current password is wrong"));
+    }
+
+    user::validate_password(&body.new_password).map_err(error::ErrorBadRequest)?;
+
+    let creds = user_api.find_credentials_by_user_id(user.id).await
+        .map_err(|err| {
+            log::error!("Cannot load credentials: {}", err);
+            error::ErrorInternalServerError("Cannot change password")
+        })?;
+
+    if let Some(secret) = creds.mfa_config.and_then(|mfa_config| mfa_config.secret) {
+        let code = body.code.ok_or_else(|| error::ErrorBadRequest("The TOTP code is required"))?;
+        if !verify_credential(user, async { Authenticator::verify(&secret, &code, 0) }, &login_attempt_api).await? {
+            return Err(error::ErrorForbidden("The TOTP was wrong"));
+        }
+    }
+
+    user_api.update_password(user.id, &body.new_password).await
+        .map_err(|err| {
+            log::error!("Cannot change password: {}", err);
+            error::ErrorInternalServerError("Cannot change password")
+        })?;
+
+    audit_log::record_event(audit_event_api.get_ref(), &req, NewAuditEvent::for_user(user.id, AuditEventType::PasswordChanged)).await;
+    session.purge();
+
+    Ok(HttpResponse::NoContent())
+}
+
+/// Logins, credential changes and revoked sessions of the current user, newest first
+#[get("/account/security-log")]
+pub async fn get_security_log(query: Query<SecurityLogQuery>, token: AuthToken<User>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let page = query.page.unwrap_or(1).max(1);
+    let filter = AuditEventFilter { user_id: Some(token.get_authenticated_user().id), ..AuditEventFilter::default() };
+
+    let (events, total) = audit_event_api.find(&filter, (page - 1) * SECURITY_LOG_PAGE_SIZE, SECURITY_LOG_PAGE_SIZE).await
+        .map_err(|err| {
+            log::error!("Cannot load security log: {}", err);
+            error::ErrorInternalServerError("Cannot load security log")
+        })?;
+
+    Ok(HttpResponse::Ok().json(SecurityLogResponse { events, total, page, page_size: SECURITY_LOG_PAGE_SIZE }))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(change_password)
+        .service(get_security_log);
+}
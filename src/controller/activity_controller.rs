@@ -1,13 +1,165 @@
-use actix_web::{get, web::ServiceConfig, HttpResponse, Responder};
+use actix_web::{delete, error, get, post, put, web::{Data, Json, Path, Query, ServiceConfig}, HttpResponse, Responder, Result};
 use authfix::AuthToken;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
 
-use crate::domain::user::User;
+use crate::{domain::{activity::{Activity, ActivityFilter}, activity_api::ActivityApi, recurrence::RecurrenceRule, user::User}, error::errors::{ActivityUpdateError, QueryActivityError}};
 
+#[derive(Deserialize)]
+pub struct ActivityRequestBody {
+    title: String,
+    description: Option<String>,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    category_id: Option<i32>,
+    #[serde(default)]
+    tag_ids: Vec<i32>,
+    recurrence: Option<RecurrenceRule>,
+}
+
+#[derive(Deserialize)]
+pub struct OccurrenceQuery {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+/// Maximum range of an occurrence query
+const MAX_OCCURRENCE_RANGE_DAYS: i64 = 366;
+
+impl ActivityRequestBody {
+    fn into_activity(self, activity_id: i32, user_id: i32) -> Result<Activity> {
+        if self.title.trim().is_empty() {
+            return Err(error::ErrorBadRequest("Title must not be empty"));
+        }
+
+        if let Some(end) = self.end {
+            if end < self.start {
+                return Err(error::ErrorBadRequest("End must not be before start"));
+            }
+        }
+
+        if let Some(recurrence) = &self.recurrence {
+            recurrence.validate().map_err(error::ErrorBadRequest)?;
+        }
+
+        Ok(Activity {
+            id: activity_id,
+            title: self.title.trim().to_owned(),
+            description: self.description,
+            start: self.start,
+            end: self.end,
+            category_id: self.category_id,
+            tag_ids: self.tag_ids,
+            recurrence: self.recurrence,
+            ical_uid: None,
+            metrics: None,
+            user_id,
+        })
+    }
+}
+
+fn query_error(err: QueryActivityError) -> error::Error {
+    match err {
+        QueryActivityError::NotFound => error::ErrorNotFound("Activity not found"),
+        err => {
+            log::error!("Cannot load activities: {}", err);
+            error::ErrorInternalServerError("Cannot load activities")
+        }
+    }
+}
+
+fn update_error(err: ActivityUpdateError) -> error::Error {
+    match err {
+        ActivityUpdateError::NotFound => error::ErrorNotFound("Activity not found"),
+        ActivityUpdateError::InvalidReference(msg) => error::ErrorBadRequest(msg),
+        err => {
+            log::error!("Cannot save activity: {}", err);
+            error::ErrorInternalServerError("Cannot save activity")
+        }
+    }
+}
+
+/// Lists the activities of the current user, optionally filtered by `?category={id}&tag={id}`
 #[get("/activities")]
-pub async fn activities(token: AuthToken<User>) -> impl Responder {
-    HttpResponse::Ok().body(format!("my activities. Request from user: {}", token.get_authenticated_user().email))
+pub async fn activities(token: AuthToken<User>, filter: Query<ActivityFilter>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let activities = activity_api.find_all_by_user_id(user_id, &filter).await.map_err(query_error)?;
+
+    Ok(HttpResponse::Ok().json(activities))
+}
+
+/// Expands all activities of the current user to occurrences between `from` and `to`
+#[get("/activities/occurrences")]
+pub async fn occurrences(query: Query<OccurrenceQuery>, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    if query.to <= query.from {
+        return Err(error::ErrorBadRequest("`to` must be after `from`"));
+    }
+
+    if query.to - query.from > Duration::days(MAX_OCCURRENCE_RANGE_DAYS) {
+        return Err(error::ErrorBadRequest(format!("The range must not exceed {} days", MAX_OCCURRENCE_RANGE_DAYS)));
+    }
+
+    let user_id = token.get_authenticated_user().id;
+    let occurrences = activity_api.find_occurrences(user_id, query.from, query.to).await.map_err(query_error)?;
+
+    Ok(HttpResponse::Ok().json(occurrences))
+}
+
+#[get("/activities/{id}")]
+pub async fn get_activity(id: Path<i32>, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let activity = activity_api.find_by_id(id.into_inner(), user_id).await.map_err(query_error)?;
+
+    Ok(HttpResponse::Ok().json(activity))
+}
+
+/// Simplified GPS track of an imported recording
+#[get("/activities/{id}/track")]
+pub async fn get_track(id: Path<i32>, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let track = activity_api.find_track(id.into_inner(), user_id).await.map_err(query_error)?;
+
+    Ok(HttpResponse::Ok().json(track))
+}
+
+#[post("/activities")]
+pub async fn create_activity(body: Json<ActivityRequestBody>, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let activity = body.into_inner().into_activity(0, user_id)?;
+    let activity = activity_api.save_activity(activity).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Created().json(activity))
+}
+
+#[put("/activities/{id}")]
+pub async fn update_activity(id: Path<i32>, body: Json<ActivityRequestBody>, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let id = id.into_inner();
+    if id <= 0 {
+        return Err(error::ErrorNotFound("Activity not found"));
+    }
+
+    let activity = body.into_inner().into_activity(id, user_id)?;
+    let activity = activity_api.save_activity(activity).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Ok().json(activity))
+}
+
+#[delete("/activities/{id}")]
+pub async fn delete_activity(id: Path<i32>, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    activity_api.delete_activity(id.into_inner(), user_id).await.map_err(update_error)?;
+
+    Ok(HttpResponse::NoContent())
 }
 
 pub fn config(cfg: &mut ServiceConfig) {
-    cfg.service(activities);
-}
\ No newline at end of file
+    // must be registered before `/activities/{id}`
+    cfg.service(activities)
+        .service(occurrences)
+        .service(get_activity)
+        .service(get_track)
+        .service(create_activity)
+        .service(update_activity)
+        .service(delete_activity);
+}
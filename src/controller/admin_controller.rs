@@ -0,0 +1,212 @@
+use actix_web::{delete, error, get, post, web::{Data, Json, Path, Query, ServiceConfig}, HttpRequest, HttpResponse, Responder, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{controller::password_controller::send_password_reset_mail, domain::{audit_event::{AuditEvent, AuditEventFilter, AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi, mailer_api::Mailer, recovery_code_api::RecoveryCodeApi, user::{self, Role, User}, user_api::UserApi, user_token_api::UserTokenApi}, error::errors::UserUpdateError, guard::AdminUser, service::{audit_log, secure_token}};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct UserSearchQuery {
+    search: Option<String>,
+    /// Starts with 1
+    page: Option<i64>,
+    page_size: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditEventQuery {
+    user_id: Option<i32>,
+    event_type: Option<AuditEventType>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// Starts with 1
+    page: Option<i64>,
+    page_size: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequestBody {
+    email: String,
+    name: String,
+    password: String,
+    #[serde(default)]
+    roles: Vec<Role>,
+}
+
+#[derive(Serialize)]
+struct UserPageResponse {
+    users: Vec<User>,
+    total: i64,
+    page: i64,
+    page_size: i64,
+}
+
+#[derive(Serialize)]
+struct AuditEventPageResponse {
+    events: Vec<AuditEvent>,
+    total: i64,
+    page: i64,
+    page_size: i64,
+}
+
+/// Page number and size of a request, within the allowed range
+fn page_and_size(page: Option<i64>, page_size: Option<i64>) -> (i64, i64) {
+    (page.unwrap_or(1).max(1), page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE))
+}
+
+/// Records an action of the admin on the account of the user
+async fn record_admin_event(user_id: i32, event_type: AuditEventType, admin: &AdminUser, req: &HttpRequest, audit_event_api: &Data<dyn AuditEventApi>) {
+    let event = NewAuditEvent::for_user(user_id, event_type)
+        .with_details(&format!("by admin {}", admin.get_authenticated_user().id));
+    audit_log::record_event(audit_event_api.get_ref(), req, event).await;
+}
+
+async fn load_user(user_id: i32, user_api: &Data<dyn UserApi>) -> Result<User> {
+    user_api.find_by_id(user_id).await
+        .map_err(|_| error::ErrorNotFound("User not found"))
+}
+
+#[get("/admin/users")]
+async fn get_users(query: Query<UserSearchQuery>, _admin: AdminUser, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    let (page, page_size) = page_and_size(query.page, query.page_size);
+    let search = query.search.as_deref().map(str::trim).filter(|search| !search.is_empty());
+
+    let (users, total) = user_api.find_users(search, (page - 1) * page_size, page_size).await
+        .map_err(|err| {
+            log::error!("Cannot load users: {}", err);
+            error::ErrorInternalServerError("Cannot load users")
+        })?;
+
+    Ok(HttpResponse::Ok().json(UserPageResponse { users, total, page, page_size }))
+}
+
+/// Creates a user who can log in without verifying the email address
+#[post("/admin/users")]
+async fn create_user(body: Json<CreateUserRequestBody>, _admin: AdminUser, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    let body = body.into_inner();
+    let email = user::normalize_email(&body.email).map_err(error::ErrorBadRequest)?;
+    user::validate_name(&body.name).map_err(error::ErrorBadRequest)?;
+    user::validate_password(&body.password).map_err(error::ErrorBadRequest)?;
+
+    let mut user = User::new(0, email, body.name.trim().to_owned());
+    user.roles = body.roles;
+    let user = user_api.create_verified_user(user, &body.password).await
+        .map_err(|err| match err {
+            UserUpdateError::DuplicateEmail => error::ErrorConflict("Email address is already registered"),
+            err => {
+                log::error!("Cannot create user: {}", err);
+                error::ErrorInternalServerError("Cannot create user")
+            }
+        })?;
+
+    Ok(HttpResponse::Created().json(user))
+}
+
+async fn set_disabled(user_id: i32, disabled: bool, admin: &AdminUser, req: &HttpRequest, user_api: &Data<dyn UserApi>, audit_event_api: &Data<dyn AuditEventApi>) -> Result<HttpResponse> {
+    if admin.get_authenticated_user().id == user_id {
+        return Err(error::ErrorBadRequest("Admins cannot disable themselves"));
+    }
+
+    load_user(user_id, user_api).await?;
+    user_api.set_disabled(user_id, disabled).await
+        .map_err(|err| {
+            log::error!("Cannot update user {}: {}", user_id, err);
+            error::ErrorInternalServerError("Cannot update user")
+        })?;
+
+    let event_type = if disabled { AuditEventType::AccountDisabled } else { AuditEventType::AccountEnabled };
+    record_admin_event(user_id, event_type, admin, req, audit_event_api).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The user cannot log in anymore and all sessions end
+#[post("/admin/users/{id}/disable")]
+async fn disable_user(req: HttpRequest, id: Path<i32>, admin: AdminUser, user_api: Data<dyn UserApi>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    set_disabled(id.into_inner(), true, &admin, &req, &user_api, &audit_event_api).await
+}
+
+#[post("/admin/users/{id}/enable")]
+async fn enable_user(req: HttpRequest, id: Path<i32>, admin: AdminUser, user_api: Data<dyn UserApi>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    set_disabled(id.into_inner(), false, &admin, &req, &user_api, &audit_event_api).await
+}
+
+/// Replaces the password with a random one, ends all sessions and mails a reset code to the user
+#[post("/admin/users/{id}/password-reset")]
+async fn force_password_reset(req: HttpRequest, id: Path<i32>, admin: AdminUser, user_api: Data<dyn UserApi>, token_api: Data<dyn UserTokenApi>, mailer: Data<dyn Mailer>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let user = load_user(id.into_inner(), &user_api).await?;
+
+    user_api.update_password(user.id, &secure_token::generate_token()).await
+        .map_err(|err| {
+            log::error!("Cannot reset password of user {}: {}", user.id, err);
+            error::ErrorInternalServerError("Cannot reset password")
+        })?;
+
+    record_admin_event(user.id, AuditEventType::PasswordReset, &admin, &req, &audit_event_api).await;
+    send_password_reset_mail(&user, &token_api, &mailer).await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+/// Removes all second factors and recovery codes, e.g. if the user lost the authenticator
+#[delete("/admin/users/{id}/mfa")]
+async fn reset_mfa(req: HttpRequest, id: Path<i32>, admin: AdminUser, user_api: Data<dyn UserApi>, recovery_code_api: Data<dyn RecoveryCodeApi>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let user = load_user(id.into_inner(), &user_api).await?;
+
+    let mut creds = user_api.find_credentials_by_user_id(user.id).await
+        .map_err(|err| {
+            log::error!("Cannot load credentials: {}", err);
+            error::ErrorInternalServerError("Cannot reset MFA")
+        })?;
+
+    creds.mfa_config = None;
+    creds.email_otp_enabled = false;
+    user_api.save_credentials(creds).await
+        .map_err(|err| {
+            log::error!("Cannot save credentials: {}", err);
+            error::ErrorInternalServerError("Cannot reset MFA")
+        })?;
+
+    recovery_code_api.delete_codes(user.id).await
+        .map_err(|err| {
+            log::error!("Cannot delete recovery codes: {}", err);
+            error::ErrorInternalServerError("Cannot reset MFA")
+        })?;
+
+    record_admin_event(user.id, AuditEventType::MfaDisabled, &admin, &req, &audit_event_api).await;
+
+    Ok(HttpResponse::NoContent())
+}
+
+/// Audit events of all users, newest first
+#[get("/admin/audit-events")]
+async fn get_audit_events(query: Query<AuditEventQuery>, _admin: AdminUser, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let query = query.into_inner();
+    let (page, page_size) = page_and_size(query.page, query.page_size);
+    let filter = AuditEventFilter {
+        user_id: query.user_id,
+        event_type: query.event_type,
+        from: query.from,
+        to: query.to,
+    };
+
+    let (events, total) = audit_event_api.find(&filter, (page - 1) * page_size, page_size).await
+        .map_err(|err| {
+            log::error!("Cannot load audit events: {}", err);
+            error::ErrorInternalServerError("Cannot load audit events")
+        })?;
+
+    Ok(HttpResponse::Ok().json(AuditEventPageResponse { events, total, page, page_size }))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_users)
+        .service(create_user)
+        .service(disable_user)
+        .service(enable_user)
+        .service(force_password_reset)
+        .service(reset_mfa)
+        .service(get_audit_events);
+}
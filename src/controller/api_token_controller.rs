@@ -0,0 +1,83 @@
+use actix_web::{delete, error, get, post, web::{Data, Json, Path, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::{actix_session::Session, AuthToken};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::{api_token::{ApiToken, TokenScope}, api_token_api::ApiTokenApi, user::User}, error::errors::ApiTokenError};
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_VALIDITY_DAYS: i64 = 365;
+
+#[derive(Deserialize)]
+pub struct CreateApiTokenRequestBody {
+    name: String,
+    scopes: Vec<TokenScope>,
+    expires_in_days: i64,
+}
+
+/// The token is only shown once
+#[derive(Serialize)]
+struct CreateApiTokenResponse {
+    api_token: ApiToken,
+    token: String,
+}
+
+/// Creates a token that is sent as `Authorization: Bearer <token>`. The token acts in a copy of the current session.
+#[post("/tokens")]
+async fn create_api_token(body: Json<CreateApiTokenRequestBody>, token: AuthToken<User>, session: Session, api_token_api: Data<dyn ApiTokenApi>) -> Result<impl Responder> {
+    let body = body.into_inner();
+    let name = body.name.trim();
+
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(error::ErrorBadRequest(format!("The name must have 1 to {} characters", MAX_NAME_LENGTH)));
+    }
+
+    if body.scopes.is_empty() {
+        return Err(error::ErrorBadRequest("At least one scope is required"));
+    }
+
+    if !(1..=MAX_VALIDITY_DAYS).contains(&body.expires_in_days) {
+        return Err(error::ErrorBadRequest(format!("A token is valid for 1 to {} days", MAX_VALIDITY_DAYS)));
+    }
+
+    let expires_at = Utc::now() + Duration::days(body.expires_in_days);
+    let session_state = session.entries().clone();
+    let (api_token, token) = api_token_api.create_token(token.get_authenticated_user().id, name, &body.scopes, expires_at, session_state).await
+        .map_err(|err| {
+            log::error!("Cannot create api token: {}", err);
+            error::ErrorInternalServerError("Cannot create token")
+        })?;
+
+    Ok(HttpResponse::Created().json(CreateApiTokenResponse { api_token, token }))
+}
+
+#[get("/tokens")]
+async fn get_api_tokens(token: AuthToken<User>, api_token_api: Data<dyn ApiTokenApi>) -> Result<impl Responder> {
+    let api_tokens = api_token_api.find_by_user(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot load api tokens: {}", err);
+            error::ErrorInternalServerError("Cannot load tokens")
+        })?;
+
+    Ok(HttpResponse::Ok().json(api_tokens))
+}
+
+#[delete("/tokens/{id}")]
+async fn revoke_api_token(id: Path<i32>, token: AuthToken<User>, api_token_api: Data<dyn ApiTokenApi>) -> Result<impl Responder> {
+    api_token_api.revoke(token.get_authenticated_user().id, id.into_inner()).await
+        .map_err(|err| match err {
+            ApiTokenError::NotFound => error::ErrorNotFound("Token not found"),
+            _ => {
+                log::error!("Cannot revoke api token: {}", err);
+                error::ErrorInternalServerError("Cannot revoke token")
+            },
+        })?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(create_api_token)
+        .service(get_api_tokens)
+        .service(revoke_api_token);
+}
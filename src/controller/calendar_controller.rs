@@ -0,0 +1,108 @@
+use actix_web::{delete, error, get, http::header::{ContentDisposition, DispositionParam, DispositionType}, post, web::{Data, Path, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{domain::{activity::{Activity, ActivityFilter}, activity_api::ActivityApi, calendar_feed_api::CalendarFeedApi, user::User}, service::ical};
+
+/// The feed is polled by calendar apps without a session. Access is granted by the token in the path only,
+/// so nothing else must be registered below this prefix.
+pub const PUBLIC_PATHS: &[&str] = &["/api/calendar/*"];
+
+const CONTENT_TYPE_CALENDAR: &str = "text/calendar; charset=utf-8";
+
+#[derive(Serialize)]
+struct CalendarFeedResponse {
+    enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+fn feed_url(token: &str) -> String {
+    format!("/api/calendar/{}.ics", token)
+}
+
+async fn load_activities(user_id: i32, activity_api: &Data<dyn ActivityApi>) -> Result<Vec<Activity>> {
+    activity_api.find_all_by_user_id(user_id, &ActivityFilter::default()).await
+        .map_err(|err| {
+            log::error!("Cannot load activities for calendar: {}", err);
+            error::ErrorInternalServerError("Cannot create calendar")
+        })
+}
+
+#[get("/calendar/{feed_token}.ics")]
+pub async fn calendar_feed(feed_token: Path<String>, feed_api: Data<dyn CalendarFeedApi>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let feed_token = feed_token.into_inner();
+    if feed_token.len() != 64 || !feed_token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error::ErrorNotFound("Not found"));
+    }
+
+    let user_id = feed_api.find_user_id_by_token(&feed_token).await
+        .map_err(|err| {
+            log::error!("Cannot resolve calendar feed token: {}", err);
+            error::ErrorInternalServerError("Cannot create calendar")
+        })?
+        .ok_or_else(|| error::ErrorNotFound("Not found"))?;
+
+    let activities = load_activities(user_id, &activity_api).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(CONTENT_TYPE_CALENDAR)
+        .body(ical::write_calendar(&activities, Utc::now())))
+}
+
+#[get("/export/activities.ics")]
+pub async fn export_calendar(token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let activities = load_activities(token.get_authenticated_user().id, &activity_api).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(CONTENT_TYPE_CALENDAR)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("activities.ics".to_owned())],
+        })
+        .body(ical::write_calendar(&activities, Utc::now())))
+}
+
+#[get("/calendar-feed")]
+pub async fn get_calendar_feed(token: AuthToken<User>, feed_api: Data<dyn CalendarFeedApi>) -> Result<impl Responder> {
+    let enabled = feed_api.has_feed_token(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot load calendar feed: {}", err);
+            error::ErrorInternalServerError("Cannot load calendar feed")
+        })?;
+
+    // the url cannot be shown again, because only the hash of the token is stored
+    Ok(HttpResponse::Ok().json(CalendarFeedResponse { enabled, url: None }))
+}
+
+/// Creates a new feed url. An existing url stops working.
+#[post("/calendar-feed")]
+pub async fn create_calendar_feed(token: AuthToken<User>, feed_api: Data<dyn CalendarFeedApi>) -> Result<impl Responder> {
+    let feed_token = feed_api.create_feed_token(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot create calendar feed: {}", err);
+            error::ErrorInternalServerError("Cannot create calendar feed")
+        })?;
+
+    Ok(HttpResponse::Created().json(CalendarFeedResponse { enabled: true, url: Some(feed_url(&feed_token)) }))
+}
+
+#[delete("/calendar-feed")]
+pub async fn revoke_calendar_feed(token: AuthToken<User>, feed_api: Data<dyn CalendarFeedApi>) -> Result<impl Responder> {
+    feed_api.revoke_feed_token(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot revoke calendar feed: {}", err);
+            error::ErrorInternalServerError("Cannot revoke calendar feed")
+        })?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(calendar_feed)
+        .service(export_calendar)
+        .service(get_calendar_feed)
+        .service(create_calendar_feed)
+        .service(revoke_calendar_feed);
+}
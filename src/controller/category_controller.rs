@@ -0,0 +1,105 @@
+use actix_web::{delete, error, get, post, put, web::{Data, Json, Path, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+use serde::Deserialize;
+
+use crate::{domain::{category::Category, category_api::CategoryApi, user::User}, error::errors::{CategoryUpdateError, QueryCategoryError}};
+
+#[derive(Deserialize)]
+pub struct CategoryRequestBody {
+    name: String,
+    color: Option<String>,
+    icon: Option<String>,
+}
+
+impl CategoryRequestBody {
+    fn into_category(self, category_id: i32, user_id: i32) -> Result<Category> {
+        if self.name.trim().is_empty() {
+            return Err(error::ErrorBadRequest("Name must not be empty"));
+        }
+
+        if let Some(color) = &self.color {
+            let is_hex_color = color.len() == 7
+                && color.starts_with('#')
+                && color.chars().skip(1).all(|c| c.is_ascii_hexdigit());
+
+            if !is_hex_color {
+                return Err(error::ErrorBadRequest("Color must have the format #rrggbb"));
+            }
+        }
+
+        Ok(Category {
+            id: category_id,
+            name: self.name.trim().to_owned(),
+            color: self.color,
+            icon: self.icon,
+            user_id,
+        })
+    }
+}
+
+fn query_error(err: QueryCategoryError) -> error::Error {
+    match err {
+        QueryCategoryError::NotFound => error::ErrorNotFound("Category not found"),
+        err => {
+            log::error!("Cannot load categories: {}", err);
+            error::ErrorInternalServerError("Cannot load categories")
+        }
+    }
+}
+
+fn update_error(err: CategoryUpdateError) -> error::Error {
+    match err {
+        CategoryUpdateError::NotFound => error::ErrorNotFound("Category not found"),
+        CategoryUpdateError::Duplicate => error::ErrorConflict("A category with this name already exists"),
+        err => {
+            log::error!("Cannot save category: {}", err);
+            error::ErrorInternalServerError("Cannot save category")
+        }
+    }
+}
+
+#[get("/categories")]
+pub async fn categories(token: AuthToken<User>, category_api: Data<dyn CategoryApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let categories = category_api.find_all_by_user_id(user_id).await.map_err(query_error)?;
+
+    Ok(HttpResponse::Ok().json(categories))
+}
+
+#[post("/categories")]
+pub async fn create_category(body: Json<CategoryRequestBody>, token: AuthToken<User>, category_api: Data<dyn CategoryApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let category = body.into_inner().into_category(0, user_id)?;
+    let category = category_api.save_category(category).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Created().json(category))
+}
+
+#[put("/categories/{id}")]
+pub async fn update_category(id: Path<i32>, body: Json<CategoryRequestBody>, token: AuthToken<User>, category_api: Data<dyn CategoryApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let id = id.into_inner();
+    if id <= 0 {
+        return Err(error::ErrorNotFound("Category not found"));
+    }
+
+    let category = body.into_inner().into_category(id, user_id)?;
+    let category = category_api.save_category(category).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Ok().json(category))
+}
+
+#[delete("/categories/{id}")]
+pub async fn delete_category(id: Path<i32>, token: AuthToken<User>, category_api: Data<dyn CategoryApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    category_api.delete_category(id.into_inner(), user_id).await.map_err(update_error)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(categories)
+        .service(create_category)
+        .service(update_category)
+        .service(delete_category);
+}
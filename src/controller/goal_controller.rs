@@ -0,0 +1,103 @@
+use actix_web::{delete, error, get, post, put, web::{Data, Json, Path, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{domain::{goal::{Goal, GoalMetric, GoalPeriod}, goal_api::GoalApi, user::User, user_api::UserApi}, error::errors::GoalUpdateError};
+
+#[derive(Deserialize)]
+pub struct GoalRequestBody {
+    title: String,
+    category_id: i32,
+    metric: GoalMetric,
+    target: i64,
+    period: GoalPeriod,
+}
+
+impl GoalRequestBody {
+    fn into_goal(self, goal_id: i32, user_id: i32) -> Result<Goal> {
+        let goal = Goal {
+            id: goal_id,
+            title: self.title.trim().to_owned(),
+            category_id: self.category_id,
+            metric: self.metric,
+            target: self.target,
+            period: self.period,
+            user_id,
+        };
+
+        goal.validate().map_err(error::ErrorBadRequest)?;
+
+        Ok(goal)
+    }
+}
+
+fn update_error(err: GoalUpdateError) -> error::Error {
+    match err {
+        GoalUpdateError::NotFound => error::ErrorNotFound("Goal not found"),
+        GoalUpdateError::InvalidReference(msg) => error::ErrorBadRequest(msg),
+        err => {
+            log::error!("Cannot save goal: {}", err);
+            error::ErrorInternalServerError("Cannot save goal")
+        }
+    }
+}
+
+/// Lists the goals of the current user with the progress of the current period in the time zone of the user
+#[get("/goals")]
+pub async fn goals(token: AuthToken<User>, user_api: Data<dyn UserApi>, goal_api: Data<dyn GoalApi>) -> Result<impl Responder> {
+    // The user in the session may be outdated, the time zone could have been changed in the meantime
+    let user = user_api.find_by_id(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot load user: {}", err);
+            error::ErrorInternalServerError("Cannot load goals")
+        })?;
+
+    let timezone = user.time_zone();
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+    let goals = goal_api.find_progress_by_user_id(user.id, today, timezone).await
+        .map_err(|err| {
+            log::error!("Cannot load goals: {}", err);
+            error::ErrorInternalServerError("Cannot load goals")
+        })?;
+
+    Ok(HttpResponse::Ok().json(goals))
+}
+
+#[post("/goals")]
+pub async fn create_goal(body: Json<GoalRequestBody>, token: AuthToken<User>, goal_api: Data<dyn GoalApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let goal = body.into_inner().into_goal(0, user_id)?;
+    let goal = goal_api.save_goal(goal).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Created().json(goal))
+}
+
+#[put("/goals/{id}")]
+pub async fn update_goal(id: Path<i32>, body: Json<GoalRequestBody>, token: AuthToken<User>, goal_api: Data<dyn GoalApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let id = id.into_inner();
+    if id <= 0 {
+        return Err(error::ErrorNotFound("Goal not found"));
+    }
+
+    let goal = body.into_inner().into_goal(id, user_id)?;
+    let goal = goal_api.save_goal(goal).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+#[delete("/goals/{id}")]
+pub async fn delete_goal(id: Path<i32>, token: AuthToken<User>, goal_api: Data<dyn GoalApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    goal_api.delete_goal(id.into_inner(), user_id).await.map_err(update_error)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(goals)
+        .service(create_goal)
+        .service(update_goal)
+        .service(delete_goal);
+}
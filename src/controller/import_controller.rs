@@ -0,0 +1,161 @@
+use actix_web::{error, get, http::header::{ContentDisposition, DispositionParam, DispositionType}, post, web::{self, Bytes, Data, PayloadConfig, Query, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::{activity::{Activity, ActivityFilter, ImportRowError}, activity_api::ActivityApi, category::Category, category_api::CategoryApi, tag::Tag, tag_api::TagApi, user::User, user_api::UserApi}, service::{activity_csv, ical, track_import}};
+
+const CONTENT_TYPE_CSV: &str = "text/csv; charset=utf-8";
+/// Recordings of long activities easily exceed the default limit of 256 kB
+const MAX_UPLOAD_BYTES: usize = 20 * 1024 * 1024;
+const DEFAULT_RECORDING_TITLE: &str = "Recorded activity";
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct ImportErrorResponse {
+    errors: Vec<ImportRowError>,
+}
+
+/// Time zone for floating times of the imported file
+async fn load_timezone(user_id: i32, user_api: &Data<dyn UserApi>) -> Result<Tz> {
+    let user = user_api.find_by_id(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot load user: {}", err);
+            error::ErrorInternalServerError("Cannot import activities")
+        })?;
+
+    Ok(user.timezone.parse().unwrap_or(Tz::UTC))
+}
+
+/// Imports the VEVENTs of the uploaded `.ics` file (request body) as activities.
+/// Events that were imported before (same UID) are skipped. With `?dry_run=true` nothing is saved.
+#[post("/ical")]
+pub async fn import_ical(body: String, query: Query<ImportQuery>, token: AuthToken<User>, user_api: Data<dyn UserApi>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let timezone = load_timezone(user_id, &user_api).await?;
+
+    let events = ical::parse_calendar(&body, user_id, timezone).map_err(error::ErrorBadRequest)?;
+
+    let mut activities: Vec<Activity> = Vec::new();
+    let mut skipped = Vec::new();
+    for event in events {
+        match event {
+            Ok(activity) => activities.push(activity),
+            Err(skip) => skipped.push(skip),
+        }
+    }
+
+    let mut result = activity_api.import_activities(user_id, activities, query.dry_run).await
+        .map_err(|err| {
+            log::error!("Cannot import activities: {}", err);
+            error::ErrorInternalServerError("Cannot import activities")
+        })?;
+    result.skipped.append(&mut skipped);
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Categories and tags of the user, to map between names and ids
+async fn load_categories_and_tags(user_id: i32, category_api: &Data<dyn CategoryApi>, tag_api: &Data<dyn TagApi>) -> Result<(Vec<Category>, Vec<Tag>)> {
+    let categories = category_api.find_all_by_user_id(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot load categories: {}", err);
+            error::ErrorInternalServerError("Cannot load categories")
+        })?;
+
+    let tags = tag_api.find_all_by_user_id(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot load tags: {}", err);
+            error::ErrorInternalServerError("Cannot load tags")
+        })?;
+
+    Ok((categories, tags))
+}
+
+#[get("/export/activities.csv")]
+pub async fn export_csv(token: AuthToken<User>, activity_api: Data<dyn ActivityApi>, category_api: Data<dyn CategoryApi>, tag_api: Data<dyn TagApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+
+    let activities = activity_api.find_all_by_user_id(user_id, &ActivityFilter::default()).await
+        .map_err(|err| {
+            log::error!("Cannot load activities: {}", err);
+            error::ErrorInternalServerError("Cannot load activities")
+        })?;
+    let (categories, tags) = load_categories_and_tags(user_id, &category_api, &tag_api).await?;
+
+    let csv = activity_csv::write_activities(&activities, &categories, &tags)
+        .map_err(|err| {
+            log::error!("Cannot write csv: {}", err);
+            error::ErrorInternalServerError("Cannot export activities")
+        })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(CONTENT_TYPE_CSV)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("activities.csv".to_owned())],
+        })
+        .body(csv))
+}
+
+/// Imports the rows of the uploaded CSV file (request body, see [`activity_csv::CSV_HEADER`]).
+/// All rows are validated first. If a row is invalid, nothing is imported and the errors of all rows are returned.
+#[post("/activities.csv")]
+pub async fn import_csv(body: Bytes, query: Query<ImportQuery>, token: AuthToken<User>, user_api: Data<dyn UserApi>, activity_api: Data<dyn ActivityApi>, category_api: Data<dyn CategoryApi>, tag_api: Data<dyn TagApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let timezone = load_timezone(user_id, &user_api).await?;
+    let (categories, tags) = load_categories_and_tags(user_id, &category_api, &tag_api).await?;
+
+    let activities = match activity_csv::parse_activities(&body, user_id, timezone, &categories, &tags) {
+        Ok(activities) => activities,
+        Err(errors) => return Ok(HttpResponse::BadRequest().json(ImportErrorResponse { errors })),
+    };
+
+    let result = activity_api.import_activities(user_id, activities, query.dry_run).await
+        .map_err(|err| {
+            log::error!("Cannot import activities: {}", err);
+            error::ErrorInternalServerError("Cannot import activities")
+        })?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Imports a GPX or TCX recording (request body) as activity with sport metrics and a simplified track
+#[post("/track")]
+pub async fn import_track(body: Bytes, token: AuthToken<User>, activity_api: Data<dyn ActivityApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+
+    let data = std::str::from_utf8(&body).map_err(|_| error::ErrorBadRequest("The file must be UTF-8 encoded"))?;
+    let recording = track_import::parse_recording(data).map_err(error::ErrorBadRequest)?;
+
+    let title = recording.name.clone()
+        .or_else(|| recording.metrics.sport.clone())
+        .unwrap_or_else(|| DEFAULT_RECORDING_TITLE.to_owned());
+    let mut activity = Activity::new(0, title, recording.start, user_id);
+    activity.end = Some(recording.end);
+    activity.metrics = Some(recording.metrics);
+
+    let activity = activity_api.import_recording(activity, recording.track).await
+        .map_err(|err| {
+            log::error!("Cannot import recording: {}", err);
+            error::ErrorInternalServerError("Cannot import recording")
+        })?;
+
+    Ok(HttpResponse::Created().json(activity))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    // the larger body limit only applies to uploads, not to other requests like the login
+    cfg.service(
+        web::scope("/import")
+            .app_data(PayloadConfig::new(MAX_UPLOAD_BYTES))
+            .service(import_ical)
+            .service(import_track)
+            .service(import_csv))
+        .service(export_csv);
+}
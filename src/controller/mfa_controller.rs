@@ -1,23 +1,108 @@
 use authfix::{actix_session::Session, multifactor::authenticator::Authenticator, session::handlers::MfaRequestBody};
-use actix_web::{error, get, http::header::ContentType, post, web::{Data, Json, ServiceConfig}, HttpResponse, Responder, Result};
+use actix_web::{delete, error, get, http::header::ContentType, post, put, web::{Data, Json, ServiceConfig}, HttpRequest, HttpResponse, Responder, Result};
 use authfix::{multifactor::authenticator::{TotpSecretGenerator, MFA_ID_AUTHENTICATOR_TOTP}, AuthToken};
+use serde::{Deserialize, Serialize};
 
-use crate::domain::{user::{MfaConfig, User}, user_api::UserApi};
+use crate::{controller::account_controller::verify_credential, domain::{audit_event::{AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi, auth_api::AuthenticationApi, login_attempt_api::LoginAttemptApi, recovery_code_api::RecoveryCodeApi, user::{Credentials, MfaConfig, User}, user_api::UserApi}, service::{audit_log, email_otp_factor::MFA_ID_EMAIL_OTP}};
 
 const SESSION_KEY_TOTP_SECRET: &str = "totp_secret";
 
-#[get("/totp/debug-user-data")]
-async fn get_user_data(token: AuthToken<User>, user_api: Data<dyn UserApi>) -> impl Responder {
-    let creds = user_api.find_credentials_by_user_id(token.get_authenticated_user().id).await.unwrap();
+#[derive(Deserialize)]
+pub struct DisableTotpRequestBody {
+    password: String,
+    code: String,
+}
+
+/// Used by all changes that require the password only
+#[derive(Deserialize)]
+pub struct PasswordRequestBody {
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct PreferredFactorRequestBody {
+    mfa_id: String,
+}
+
+#[derive(Serialize)]
+struct TotpStatusResponse {
+    enabled: bool,
+}
+
+#[derive(Serialize)]
+struct MfaStatusResponse {
+    /// Factor used at login, `None` if mfa is off
+    preferred: Option<String>,
+    authenticator: bool,
+    email: bool,
+}
+
+/// The codes are only shown once
+#[derive(Serialize)]
+struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RecoveryCodeStatusResponse {
+    remaining: usize,
+}
+
+async fn create_recovery_codes(user_id: i32, recovery_code_api: &Data<dyn RecoveryCodeApi>) -> Result<RecoveryCodesResponse> {
+    let recovery_codes = recovery_code_api.replace_codes(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot create recovery codes: {}", err);
+            error::ErrorInternalServerError("Cannot create recovery codes")
+        })?;
+
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+async fn load_credentials(user_id: i32, user_api: &Data<dyn UserApi>) -> Result<Credentials> {
+    user_api.find_credentials_by_user_id(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot load credentials: {}", err);
+            error::ErrorInternalServerError("Cannot load credentials")
+        })
+}
+
+async fn save_credentials(creds: Credentials, user_api: &Data<dyn UserApi>) -> Result<()> {
+    user_api.save_credentials(creds).await
+        .map_err(|err| {
+            log::error!("Cannot save credentials: {}", err);
+            error::ErrorInternalServerError("Cannot save credentials")
+        })?;
+
+    Ok(())
+}
+
+async fn check_password(user: &User, password: &str, auth_api: &Data<dyn AuthenticationApi>, login_attempt_api: &Data<dyn LoginAttemptApi>) -> Result<()> {
+    if verify_credential(user, auth_api.is_password_correct(user, password), login_attempt_api).await? {
+        Ok(())
+    } else {
+        Err(error::ErrorForbidden("The password is wrong"))
+    }
+}
+
+/// Removes the recovery codes, if no factor is left
+async fn delete_recovery_codes_if_mfa_off(creds: &Credentials, recovery_code_api: &Data<dyn RecoveryCodeApi>) -> Result<()> {
+    if creds.mfa_config.is_none() {
+        recovery_code_api.delete_codes(creds.user_id).await
+            .map_err(|err| {
+                log::error!("Cannot delete recovery codes: {}", err);
+                error::ErrorInternalServerError("Cannot delete recovery codes")
+            })?;
+    }
+
+    Ok(())
+}
 
-    let mfa = creds.mfa_config.unwrap();
-    let r = format!(r#"{{
-        "user": "{}",
-        "mfa_id": "{}",
-        "secret": "{}"
-    }}"#, token.get_authenticated_user().name, mfa.mfa_id, mfa.secret.unwrap());
+/// Reports only whether a second factor is required at login, the secret never leaves the server
+#[get("/totp/status")]
+async fn get_totp_status(token: AuthToken<User>, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    let creds = load_credentials(token.get_authenticated_user().id, &user_api).await?;
 
-    HttpResponse::Ok().json(r)
+    Ok(HttpResponse::Ok().json(TotpStatusResponse { enabled: creds.mfa_config.is_some() }))
 }
 
 
@@ -38,7 +123,7 @@ async fn get_qrcode(token: AuthToken<User>, session: Session) -> Result<impl Res
 }
 
 #[post("/totp/set-secret")]
-async fn set_totp_secret(code: Json<MfaRequestBody>, token: AuthToken<User>, session: Session, user_api: Data<dyn UserApi>) 
+async fn set_totp_secret(req: HttpRequest, code: Json<MfaRequestBody>, token: AuthToken<User>, session: Session, user_api: Data<dyn UserApi>, recovery_code_api: Data<dyn RecoveryCodeApi>, audit_event_api: Data<dyn AuditEventApi>) 
     -> Result<impl Responder> 
 {
     let user_id = token.get_authenticated_user().id;
@@ -48,6 +133,11 @@ async fn set_totp_secret(code: Json<MfaRequestBody>, token: AuthToken<User>, ses
             error::ErrorBadRequest("Cannot save secret")
         })?;
 
+    // an existing authenticator must be disabled first, which requires the password and a valid code
+    if creds.mfa_config.as_ref().is_some_and(|mfa_config| mfa_config.secret.is_some()) {
+        return Err(error::ErrorConflict("TOTP is already configured"));
+    }
+
     let secret = session.get::<String>(SESSION_KEY_TOTP_SECRET)?;
 
     if let Some(secret) = secret {
@@ -66,7 +156,12 @@ async fn set_totp_secret(code: Json<MfaRequestBody>, token: AuthToken<User>, ses
 
         // clean up session
         session.remove(SESSION_KEY_TOTP_SECRET);
-        Ok(HttpResponse::Ok())   
+
+        let event = NewAuditEvent::for_user(user_id, AuditEventType::MfaEnabled).with_details(MFA_ID_AUTHENTICATOR_TOTP);
+        audit_log::record_event(audit_event_api.get_ref(), &req, event).await;
+
+        let recovery_codes = create_recovery_codes(user_id, &recovery_code_api).await?;
+        Ok(HttpResponse::Ok().json(recovery_codes))
     } else {
         log::error!("Session does not contain the secret.");
 
@@ -76,9 +171,167 @@ async fn set_totp_secret(code: Json<MfaRequestBody>, token: AuthToken<User>, ses
     }
 }
 
+/// Turns off the authenticator. Requires the password and a valid code.
+#[delete("/totp")]
+async fn disable_totp(req: HttpRequest, body: Json<DisableTotpRequestBody>, token: AuthToken<User>, user_api: Data<dyn UserApi>, auth_api: Data<dyn AuthenticationApi>, recovery_code_api: Data<dyn RecoveryCodeApi>, audit_event_api: Data<dyn AuditEventApi>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let user = token.get_authenticated_user();
+    check_password(user, &body.password, &auth_api, &login_attempt_api).await?;
+
+    let mut creds = user_api.find_credentials_by_user_id(user.id).await
+        .map_err(|err| {
+            log::error!("Cannot load credentials: {}", err);
+            error::ErrorInternalServerError("Cannot disable TOTP")
+        })?;
+
+    let secret = creds.mfa_config.as_ref()
+        .and_then(|mfa_config| mfa_config.secret.clone())
+        .ok_or_else(|| error::ErrorNotFound("TOTP is not configured"))?;
+
+    if !verify_credential(user, async { Authenticator::verify(&secret, &body.code, 0) }, &login_attempt_api).await? {
+        return Err(error::ErrorForbidden("The TOTP was wrong"));
+    }
+
+    // codes by mail stay active, if enabled
+    creds.mfa_config = creds.email_otp_enabled.then(|| MfaConfig::new(MFA_ID_EMAIL_OTP));
+    delete_recovery_codes_if_mfa_off(&creds, &recovery_code_api).await?;
+    save_credentials(creds, &user_api).await?;
+
+    let event = NewAuditEvent::for_user(user.id, AuditEventType::MfaDisabled).with_details(MFA_ID_AUTHENTICATOR_TOTP);
+    audit_log::record_event(audit_event_api.get_ref(), &req, event).await;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/mfa")]
+async fn get_mfa_status(token: AuthToken<User>, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    let creds = load_credentials(token.get_authenticated_user().id, &user_api).await?;
+
+    Ok(HttpResponse::Ok().json(MfaStatusResponse {
+        preferred: creds.mfa_config.as_ref().map(|mfa_config| mfa_config.mfa_id.clone()),
+        authenticator: creds.mfa_config.as_ref().is_some_and(|mfa_config| mfa_config.secret.is_some()),
+        email: creds.email_otp_enabled,
+    }))
+}
+
+/// Enables login codes by mail. If it is the first factor of the user, it becomes the preferred one
+/// and the recovery codes are returned.
+#[post("/mfa/email")]
+async fn enable_email_otp(req: HttpRequest, body: Json<PasswordRequestBody>, token: AuthToken<User>, user_api: Data<dyn UserApi>, auth_api: Data<dyn AuthenticationApi>, recovery_code_api: Data<dyn RecoveryCodeApi>, audit_event_api: Data<dyn AuditEventApi>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let user = token.get_authenticated_user();
+    check_password(user, &body.password, &auth_api, &login_attempt_api).await?;
+
+    let mut creds = load_credentials(user.id, &user_api).await?;
+    if creds.email_otp_enabled {
+        return Err(error::ErrorConflict("Login codes by mail are already enabled"));
+    }
+
+    let is_first_factor = creds.mfa_config.is_none();
+    creds.email_otp_enabled = true;
+    if is_first_factor {
+        creds.set_mfa(MfaConfig::new(MFA_ID_EMAIL_OTP));
+    }
+    save_credentials(creds, &user_api).await?;
+
+    let event = NewAuditEvent::for_user(user.id, AuditEventType::MfaEnabled).with_details(MFA_ID_EMAIL_OTP);
+    audit_log::record_event(audit_event_api.get_ref(), &req, event).await;
+
+    if is_first_factor {
+        let recovery_codes = create_recovery_codes(user.id, &recovery_code_api).await?;
+        Ok(HttpResponse::Ok().json(recovery_codes))
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+#[delete("/mfa/email")]
+async fn disable_email_otp(req: HttpRequest, body: Json<PasswordRequestBody>, token: AuthToken<User>, user_api: Data<dyn UserApi>, auth_api: Data<dyn AuthenticationApi>, recovery_code_api: Data<dyn RecoveryCodeApi>, audit_event_api: Data<dyn AuditEventApi>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let user = token.get_authenticated_user();
+    check_password(user, &body.password, &auth_api, &login_attempt_api).await?;
+
+    let mut creds = load_credentials(user.id, &user_api).await?;
+    if !creds.email_otp_enabled {
+        return Err(error::ErrorNotFound("Login codes by mail are not enabled"));
+    }
+
+    // an authenticator stays active, if configured
+    creds.email_otp_enabled = false;
+    creds.mfa_config = creds.mfa_config.take()
+        .and_then(|mfa_config| mfa_config.secret)
+        .map(|secret| MfaConfig::with_secret(MFA_ID_AUTHENTICATOR_TOTP, &secret));
+    delete_recovery_codes_if_mfa_off(&creds, &recovery_code_api).await?;
+    save_credentials(creds, &user_api).await?;
+
+    let event = NewAuditEvent::for_user(user.id, AuditEventType::MfaDisabled).with_details(MFA_ID_EMAIL_OTP);
+    audit_log::record_event(audit_event_api.get_ref(), &req, event).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Selects the factor that is requested at login
+#[put("/mfa/preferred")]
+async fn set_preferred_factor(body: Json<PreferredFactorRequestBody>, token: AuthToken<User>, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    let mut creds = load_credentials(token.get_authenticated_user().id, &user_api).await?;
+    let mfa_config = creds.mfa_config.take()
+        .ok_or_else(|| error::ErrorNotFound("MFA is not configured"))?;
+
+    let available = match body.mfa_id.as_str() {
+        MFA_ID_AUTHENTICATOR_TOTP => mfa_config.secret.is_some(),
+        MFA_ID_EMAIL_OTP => creds.email_otp_enabled,
+        _ => false,
+    };
+
+    if !available {
+        return Err(error::ErrorBadRequest("The factor is not configured"));
+    }
+
+    creds.set_mfa(MfaConfig { mfa_id: body.into_inner().mfa_id, secret: mfa_config.secret });
+    save_credentials(creds, &user_api).await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/totp/recovery-codes")]
+async fn get_recovery_code_status(token: AuthToken<User>, recovery_code_api: Data<dyn RecoveryCodeApi>) -> Result<impl Responder> {
+    let remaining = recovery_code_api.count_remaining(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot count recovery codes: {}", err);
+            error::ErrorInternalServerError("Cannot load recovery codes")
+        })?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodeStatusResponse { remaining }))
+}
+
+/// Replaces all recovery codes. Requires the password and a configured authenticator.
+#[post("/totp/recovery-codes")]
+async fn regenerate_recovery_codes(body: Json<PasswordRequestBody>, token: AuthToken<User>, user_api: Data<dyn UserApi>, auth_api: Data<dyn AuthenticationApi>, recovery_code_api: Data<dyn RecoveryCodeApi>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let user = token.get_authenticated_user();
+    check_password(user, &body.password, &auth_api, &login_attempt_api).await?;
+
+    let creds = user_api.find_credentials_by_user_id(user.id).await
+        .map_err(|err| {
+            log::error!("Cannot load credentials: {}", err);
+            error::ErrorInternalServerError("Cannot create recovery codes")
+        })?;
+
+    if creds.mfa_config.is_none() {
+        return Err(error::ErrorNotFound("MFA is not configured"));
+    }
+
+    let recovery_codes = create_recovery_codes(user.id, &recovery_code_api).await?;
+
+    Ok(HttpResponse::Ok().json(recovery_codes))
+}
+
 
 pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(get_qrcode)
     .service(set_totp_secret)
-    .service(get_user_data);
+    .service(disable_totp)
+    .service(get_mfa_status)
+    .service(enable_email_otp)
+    .service(disable_email_otp)
+    .service(set_preferred_factor)
+    .service(get_recovery_code_status)
+    .service(regenerate_recovery_codes)
+    .service(get_totp_status);
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use actix_web::{error, post, web::{Data, Json, ServiceConfig}, HttpRequest, HttpResponse, Responder, Result};
+use serde::Deserialize;
+
+use crate::{controller::registration_controller::throttle_mail_request, domain::{audit_event::{AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi, login_attempt_api::LoginAttemptApi, mail::Mail, mailer_api::Mailer, user::{self, User}, user_api::UserApi, user_token::TokenPurpose, user_token_api::UserTokenApi}, service::audit_log};
+
+pub const PUBLIC_PATHS: &[&str] = &["/api/password/forgot", "/api/password/reset"];
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequestBody {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequestBody {
+    token: String,
+    password: String,
+}
+
+/// Creates a reset code and mails it to the user
+pub async fn send_password_reset_mail(user: &User, token_api: &Data<dyn UserTokenApi>, mailer: &Data<dyn Mailer>) -> Result<()> {
+    let token = token_api.create_token(user.id, TokenPurpose::PasswordReset).await
+        .map_err(|err| {
+            log::error!("Cannot create password reset token: {}", err);
+            error::ErrorInternalServerError("Cannot reset password")
+        })?;
+
+    let body = format!(
+        "Hello {},\n\nsomeone requested to reset the password of your account. Use the following code to set a new password:\n\n{}\n\nThe code is valid for {} minutes. If you did not request a reset, you can ignore this mail.",
+        user.name, token, TokenPurpose::PasswordReset.validity().num_minutes());
+
+    if let Err(err) = mailer.send(Mail::new(&user.email, "Reset your password", body)).await {
+        log::error!("Cannot send password reset mail to user {}: {}", user.id, err);
+    }
+
+    Ok(())
+}
+
+/// Mails a reset code to the user. Always accepted, so that registered addresses cannot be probed.
+/// The code is created and mailed after the response, so neither status nor response time reveal an account.
+/// Requests are limited per address, since every new code replaces the pending one.
+#[post("/password/forgot")]
+pub async fn forgot_password(body: Json<ForgotPasswordRequestBody>, user_api: Data<dyn UserApi>, token_api: Data<dyn UserTokenApi>, mailer: Data<dyn Mailer>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let Ok(email) = user::normalize_email(&body.email) else {
+        return Ok(HttpResponse::Accepted());
+    };
+
+    throttle_mail_request(&email, &login_attempt_api).await?;
+
+    actix_web::rt::spawn(async move {
+        if let Ok(user) = user_api.find_by_email(&email).await {
+            // errors are logged by `send_password_reset_mail`
+            let _ = send_password_reset_mail(&user, &token_api, &mailer).await;
+        }
+    });
+
+    Ok(HttpResponse::Accepted())
+}
+
+/// Sets a new password with a reset code. All sessions of the user end.
+#[post("/password/reset")]
+pub async fn reset_password(req: HttpRequest, body: Json<ResetPasswordRequestBody>, user_api: Data<dyn UserApi>, token_api: Data<dyn UserTokenApi>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let body = body.into_inner();
+    user::validate_password(&body.password).map_err(error::ErrorBadRequest)?;
+
+    let user_id = token_api.consume_token(&body.token, TokenPurpose::PasswordReset).await
+        .map_err(|err| {
+            log::error!("Cannot consume password reset token: {}", err);
+            error::ErrorInternalServerError("Cannot reset password")
+        })?
+        .ok_or_else(|| error::ErrorBadRequest("The code is invalid or expired"))?;
+
+    user_api.update_password(user_id, &body.password).await
+        .map_err(|err| {
+            log::error!("Cannot reset password: {}", err);
+            error::ErrorInternalServerError("Cannot reset password")
+        })?;
+
+    audit_log::record_event(audit_event_api.get_ref(), &req, NewAuditEvent::for_user(user_id, AuditEventType::PasswordReset)).await;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(forgot_password)
+        .service(reset_password);
+}
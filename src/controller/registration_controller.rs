@@ -0,0 +1,132 @@
+use actix_web::{error, get, post, web::{Data, Json, Query, ServiceConfig}, HttpResponse, Responder, Result};
+use serde::Deserialize;
+
+use crate::{config::config::Config, domain::{login_attempt::LoginSubject, login_attempt_api::LoginAttemptApi, mail::Mail, mailer_api::Mailer, user::{self, User}, user_api::UserApi, user_token::TokenPurpose, user_token_api::UserTokenApi}, error::errors::UserUpdateError};
+
+pub const PUBLIC_PATHS: &[&str] = &["/api/register", "/api/verify-email", "/api/verify-email/resend"];
+
+#[derive(Deserialize)]
+pub struct RegistrationRequestBody {
+    email: String,
+    name: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailQuery {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationRequestBody {
+    email: String,
+}
+
+/// Creates a new verification token and mails the link to the user
+async fn send_verification_mail(user: &User, config: &Config, token_api: &Data<dyn UserTokenApi>, mailer: &Data<dyn Mailer>) -> Result<(), String> {
+    let token = token_api.create_token(user.id, TokenPurpose::EmailVerification).await
+        .map_err(|e| e.to_string())?;
+
+    let link = format!("{}/api/verify-email?token={}", config.base_url, token);
+    let body = format!(
+        "Hello {},\n\nplease confirm your email address by opening the following link:\n\n{}\n\nThe link is valid for {} hours.",
+        user.name, link, TokenPurpose::EmailVerification.validity().num_hours());
+
+    mailer.send(Mail::new(&user.email, "Confirm your email address", body)).await
+        .map_err(|e| e.to_string())
+}
+
+/// Counts a requested mail for the address and rejects it with `429 Too Many Requests` while the address is locked.
+/// Unknown addresses are counted the same way, so the lockout does not reveal an account.
+pub async fn throttle_mail_request(email: &str, login_attempt_api: &Data<dyn LoginAttemptApi>) -> Result<()> {
+    let subject = LoginSubject::Mail(email.to_owned());
+    match login_attempt_api.locked_until(&subject).await {
+        Ok(Some(_)) => return Err(error::ErrorTooManyRequests("Too many requests for this address, try again later")),
+        Ok(None) => {},
+        Err(err) => log::error!("Cannot check mail request lockout: {}", err),
+    }
+
+    if let Err(err) = login_attempt_api.record_failure(&subject).await {
+        log::error!("Cannot record mail request: {}", err);
+    }
+
+    Ok(())
+}
+
+/// Creates a new account, if open registration is enabled (`MA_OPEN_REGISTRATION=true`).
+/// Login is possible after the email address was verified.
+#[post("/register")]
+pub async fn register(body: Json<RegistrationRequestBody>, config: Data<Config>, user_api: Data<dyn UserApi>, token_api: Data<dyn UserTokenApi>, mailer: Data<dyn Mailer>) -> Result<impl Responder> {
+    if !config.open_registration {
+        return Err(error::ErrorForbidden("Registration is disabled"));
+    }
+
+    let body = body.into_inner();
+    let email = user::normalize_email(&body.email).map_err(error::ErrorBadRequest)?;
+    user::validate_name(&body.name).map_err(error::ErrorBadRequest)?;
+    user::validate_password(&body.password).map_err(error::ErrorBadRequest)?;
+
+    let user = User::new(0, email, body.name.trim().to_owned());
+    let user = user_api.save_user_with_credentials(user, &body.password).await
+        .map_err(|err| match err {
+            UserUpdateError::DuplicateEmail => error::ErrorConflict("Email address is already registered"),
+            err => {
+                log::error!("Cannot register user: {}", err);
+                error::ErrorInternalServerError("Cannot register user")
+            }
+        })?;
+
+    // the user can request a new mail, so the registration itself does not fail
+    if let Err(err) = send_verification_mail(&user, &config, &token_api, &mailer).await {
+        log::error!("Cannot send verification mail to user {}: {}", user.id, err);
+    }
+
+    Ok(HttpResponse::Created().json(user))
+}
+
+#[get("/verify-email")]
+pub async fn verify_email(query: Query<VerifyEmailQuery>, user_api: Data<dyn UserApi>, token_api: Data<dyn UserTokenApi>) -> Result<impl Responder> {
+    let user_id = token_api.consume_token(&query.token, TokenPurpose::EmailVerification).await
+        .map_err(|err| {
+            log::error!("Cannot verify email: {}", err);
+            error::ErrorInternalServerError("Cannot verify email address")
+        })?
+        .ok_or_else(|| error::ErrorBadRequest("The link is invalid or expired"))?;
+
+    user_api.mark_email_verified(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot verify email: {}", err);
+            error::ErrorInternalServerError("Cannot verify email address")
+        })?;
+
+    Ok(HttpResponse::Ok().body("Your email address is verified. You can log in now."))
+}
+
+/// Sends a new verification link. Always accepted, so that registered addresses cannot be probed.
+/// The mail is sent after the response, so the response time does not reveal an account either.
+#[post("/verify-email/resend")]
+pub async fn resend_verification(body: Json<ResendVerificationRequestBody>, config: Data<Config>, user_api: Data<dyn UserApi>, token_api: Data<dyn UserTokenApi>, mailer: Data<dyn Mailer>, login_attempt_api: Data<dyn LoginAttemptApi>) -> Result<impl Responder> {
+    let Ok(email) = user::normalize_email(&body.email) else {
+        return Ok(HttpResponse::Accepted());
+    };
+
+    throttle_mail_request(&email, &login_attempt_api).await?;
+
+    actix_web::rt::spawn(async move {
+        if let Ok(user) = user_api.find_by_email(&email).await {
+            if !user.email_verified {
+                if let Err(err) = send_verification_mail(&user, &config, &token_api, &mailer).await {
+                    log::error!("Cannot send verification mail to user {}: {}", user.id, err);
+                }
+            }
+        }
+    });
+
+    Ok(HttpResponse::Accepted())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(register)
+        .service(verify_email)
+        .service(resend_verification);
+}
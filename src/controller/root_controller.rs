@@ -1,14 +1,51 @@
-use actix_web::{get, web::ServiceConfig, HttpResponse, Responder};
+use actix_web::{error, get, put, web::{Data, Json, ServiceConfig}, HttpResponse, Responder, Result};
 use authfix::AuthToken;
+use chrono_tz::Tz;
+use serde::Deserialize;
 
-use crate::domain::user::User;
+use crate::domain::{user::User, user_api::UserApi};
 
+#[derive(Deserialize)]
+pub struct UserSettingsRequestBody {
+    timezone: String,
+}
+
+/// Loads the user again, so that changed roles are visible without a new login
 #[get("/current-user")]
-pub async fn get_authenticated_user(auth_token: AuthToken<User>) -> impl Responder {
-    HttpResponse::Ok().json(auth_token.get_authenticated_user().clone())
+pub async fn get_authenticated_user(auth_token: AuthToken<User>, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    let user = user_api.find_by_id(auth_token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot load user: {}", err);
+            error::ErrorInternalServerError("Cannot load user")
+        })?;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[put("/current-user/settings")]
+pub async fn update_settings(body: Json<UserSettingsRequestBody>, auth_token: AuthToken<User>, user_api: Data<dyn UserApi>) -> Result<impl Responder> {
+    if body.timezone.parse::<Tz>().is_err() {
+        return Err(error::ErrorBadRequest("Unknown time zone"));
+    }
+
+    let mut user = user_api.find_by_id(auth_token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot load user: {}", err);
+            error::ErrorInternalServerError("Cannot save settings")
+        })?;
+
+    user.timezone = body.into_inner().timezone;
+    let user = user_api.save_user(user).await
+        .map_err(|err| {
+            log::error!("Cannot save user settings: {}", err);
+            error::ErrorInternalServerError("Cannot save settings")
+        })?;
+
+    Ok(HttpResponse::Ok().json(user))
 }
 
 
 pub fn config(config: &mut ServiceConfig) {
-    config.service(get_authenticated_user);
-}
\ No newline at end of file
+    config.service(get_authenticated_user)
+        .service(update_settings);
+}
@@ -0,0 +1,68 @@
+use actix_web::{delete, error, get, web::{Data, Path, ServiceConfig}, HttpRequest, HttpResponse, Responder, Result};
+use authfix::{actix_session::Session, AuthToken};
+
+use crate::{domain::{audit_event::{AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi, session::SESSION_KEY_PUBLIC_ID, session_api::SessionApi, user::User}, error::errors::SessionError, service::audit_log};
+
+fn current_session_id(session: &Session) -> Option<String> {
+    session.get::<String>(SESSION_KEY_PUBLIC_ID).ok().flatten()
+}
+
+#[get("/sessions")]
+async fn get_sessions(token: AuthToken<User>, session: Session, session_api: Data<dyn SessionApi>) -> Result<impl Responder> {
+    let current_id = current_session_id(&session);
+    let sessions = session_api.find_by_user(token.get_authenticated_user().id, current_id.as_deref()).await
+        .map_err(|err| {
+            log::error!("Cannot load sessions: {}", err);
+            error::ErrorInternalServerError("Cannot load sessions")
+        })?;
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+/// Ends a session of the current user, which may be the current one
+#[delete("/sessions/{id}")]
+async fn delete_session(req: HttpRequest, id: Path<String>, token: AuthToken<User>, session: Session, session_api: Data<dyn SessionApi>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let id = id.into_inner();
+    let user_id = token.get_authenticated_user().id;
+    session_api.delete(user_id, &id).await
+        .map_err(|err| match err {
+            SessionError::NotFound => error::ErrorNotFound("Session not found"),
+            _ => {
+                log::error!("Cannot delete session: {}", err);
+                error::ErrorInternalServerError("Cannot delete session")
+            },
+        })?;
+
+    let event = NewAuditEvent::for_user(user_id, AuditEventType::SessionRevoked).with_details(&format!("session {}", id));
+    audit_log::record_event(audit_event_api.get_ref(), &req, event).await;
+
+    if current_session_id(&session).as_deref() == Some(id.as_str()) {
+        session.purge();
+    }
+
+    Ok(HttpResponse::NoContent())
+}
+
+/// Logs the user out everywhere
+#[delete("/sessions")]
+async fn delete_sessions(req: HttpRequest, token: AuthToken<User>, session: Session, session_api: Data<dyn SessionApi>, audit_event_api: Data<dyn AuditEventApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    session_api.delete_all(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot delete sessions: {}", err);
+            error::ErrorInternalServerError("Cannot delete sessions")
+        })?;
+
+    let event = NewAuditEvent::for_user(user_id, AuditEventType::SessionRevoked).with_details("all sessions");
+    audit_log::record_event(audit_event_api.get_ref(), &req, event).await;
+
+    session.purge();
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_sessions)
+        .service(delete_session)
+        .service(delete_sessions);
+}
@@ -0,0 +1,30 @@
+use actix_web::{error, get, web::{Data, Query, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+
+use crate::domain::{stats::StatsQuery, stats_api::StatsApi, user::User, user_api::UserApi};
+
+/// Aggregates the tracked time of the current user,
+/// e.g. `/stats?from=2025-04-01&to=2025-04-30&group_by=week&by=category`
+#[get("/stats")]
+pub async fn stats(query: Query<StatsQuery>, token: AuthToken<User>, user_api: Data<dyn UserApi>, stats_api: Data<dyn StatsApi>) -> Result<impl Responder> {
+    query.validate().map_err(error::ErrorBadRequest)?;
+
+    // The user in the session may be outdated, the time zone could have been changed in the meantime
+    let user = user_api.find_by_id(token.get_authenticated_user().id).await
+        .map_err(|err| {
+            log::error!("Cannot load user: {}", err);
+            error::ErrorInternalServerError("Cannot load stats")
+        })?;
+
+    let entries = stats_api.aggregate(user.id, &query, user.time_zone()).await
+        .map_err(|err| {
+            log::error!("Cannot aggregate stats: {}", err);
+            error::ErrorInternalServerError("Cannot load stats")
+        })?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(stats);
+}
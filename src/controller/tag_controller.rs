@@ -0,0 +1,81 @@
+use actix_web::{delete, error, get, post, put, web::{Data, Json, Path, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+use serde::Deserialize;
+
+use crate::{domain::{tag::Tag, tag_api::TagApi, user::User}, error::errors::TagUpdateError};
+
+#[derive(Deserialize)]
+pub struct TagRequestBody {
+    name: String,
+}
+
+impl TagRequestBody {
+    fn into_tag(self, tag_id: i32, user_id: i32) -> Result<Tag> {
+        if self.name.trim().is_empty() {
+            return Err(error::ErrorBadRequest("Name must not be empty"));
+        }
+
+        Ok(Tag::new(tag_id, self.name.trim().to_owned(), user_id))
+    }
+}
+
+fn update_error(err: TagUpdateError) -> error::Error {
+    match err {
+        TagUpdateError::NotFound => error::ErrorNotFound("Tag not found"),
+        TagUpdateError::Duplicate => error::ErrorConflict("A tag with this name already exists"),
+        err => {
+            log::error!("Cannot save tag: {}", err);
+            error::ErrorInternalServerError("Cannot save tag")
+        }
+    }
+}
+
+#[get("/tags")]
+pub async fn tags(token: AuthToken<User>, tag_api: Data<dyn TagApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let tags = tag_api.find_all_by_user_id(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot load tags: {}", err);
+            error::ErrorInternalServerError("Cannot load tags")
+        })?;
+
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+#[post("/tags")]
+pub async fn create_tag(body: Json<TagRequestBody>, token: AuthToken<User>, tag_api: Data<dyn TagApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let tag = body.into_inner().into_tag(0, user_id)?;
+    let tag = tag_api.save_tag(tag).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Created().json(tag))
+}
+
+#[put("/tags/{id}")]
+pub async fn update_tag(id: Path<i32>, body: Json<TagRequestBody>, token: AuthToken<User>, tag_api: Data<dyn TagApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let id = id.into_inner();
+    if id <= 0 {
+        return Err(error::ErrorNotFound("Tag not found"));
+    }
+
+    let tag = body.into_inner().into_tag(id, user_id)?;
+    let tag = tag_api.save_tag(tag).await.map_err(update_error)?;
+
+    Ok(HttpResponse::Ok().json(tag))
+}
+
+#[delete("/tags/{id}")]
+pub async fn delete_tag(id: Path<i32>, token: AuthToken<User>, tag_api: Data<dyn TagApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    tag_api.delete_tag(id.into_inner(), user_id).await.map_err(update_error)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(tags)
+        .service(create_tag)
+        .service(update_tag)
+        .service(delete_tag);
+}
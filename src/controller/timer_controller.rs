@@ -0,0 +1,51 @@
+use actix_web::{error, get, post, web::{Data, Path, ServiceConfig}, HttpResponse, Responder, Result};
+use authfix::AuthToken;
+
+use crate::{domain::{activity_session_api::ActivitySessionApi, user::User}, error::errors::TimerError};
+
+fn timer_error(err: TimerError) -> error::Error {
+    match err {
+        TimerError::ActivityNotFound => error::ErrorNotFound("Activity not found"),
+        TimerError::AlreadyRunning => error::ErrorConflict("Another timer is already running"),
+        TimerError::NotRunning => error::ErrorConflict("No timer is running for this activity"),
+        err => {
+            log::error!("Cannot update timer: {}", err);
+            error::ErrorInternalServerError("Cannot update timer")
+        }
+    }
+}
+
+#[post("/activities/{id}/timer/start")]
+pub async fn start_timer(id: Path<i32>, token: AuthToken<User>, session_api: Data<dyn ActivitySessionApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let session = session_api.start_timer(id.into_inner(), user_id).await.map_err(timer_error)?;
+
+    Ok(HttpResponse::Created().json(session))
+}
+
+#[post("/activities/{id}/timer/stop")]
+pub async fn stop_timer(id: Path<i32>, token: AuthToken<User>, session_api: Data<dyn ActivitySessionApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let session = session_api.stop_timer(id.into_inner(), user_id).await.map_err(timer_error)?;
+
+    Ok(HttpResponse::Ok().json(session))
+}
+
+/// Returns the running timer of the current user or `null` if no timer is running
+#[get("/current-user/timer")]
+pub async fn get_running_timer(token: AuthToken<User>, session_api: Data<dyn ActivitySessionApi>) -> Result<impl Responder> {
+    let user_id = token.get_authenticated_user().id;
+    let session = session_api.find_running_by_user_id(user_id).await
+        .map_err(|err| {
+            log::error!("Cannot load running timer: {}", err);
+            error::ErrorInternalServerError("Cannot load running timer")
+        })?;
+
+    Ok(HttpResponse::Ok().json(session))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(start_timer)
+        .service(stop_timer)
+        .service(get_running_timer);
+}
@@ -1,4 +1,32 @@
 #[allow(dead_code)]
 pub mod user;
 pub mod user_api;
-pub mod auth_api;
\ No newline at end of file
+pub mod auth_api;
+pub mod activity;
+pub mod activity_api;
+pub mod activity_session;
+pub mod activity_session_api;
+pub mod category;
+pub mod category_api;
+pub mod tag;
+pub mod tag_api;
+pub mod recurrence;
+pub mod goal;
+pub mod goal_api;
+pub mod stats;
+pub mod stats_api;
+pub mod calendar_feed_api;
+pub mod mail;
+pub mod mailer_api;
+pub mod user_token;
+pub mod user_token_api;
+pub mod recovery_code_api;
+pub mod email_otp_api;
+pub mod login_attempt;
+pub mod login_attempt_api;
+pub mod session;
+pub mod session_api;
+pub mod api_token;
+pub mod api_token_api;
+pub mod audit_event;
+pub mod audit_event_api;
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::recurrence::RecurrenceRule;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Activity {
+    pub id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub category_id: Option<i32>,
+    pub tag_ids: Vec<i32>,
+    /// If set, `start` and `end` describe the first occurrence of the series
+    pub recurrence: Option<RecurrenceRule>,
+    /// UID of the calendar event the activity was imported from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ical_uid: Option<String>,
+    /// Set for activities imported from a GPS recording
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<SportMetrics>,
+    pub user_id: i32,
+}
+
+/// Metrics of a recorded sport activity
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SportMetrics {
+    /// Sport type of the recording, e.g. `running` or `Biking`
+    pub sport: Option<String>,
+    pub duration_seconds: i64,
+    pub distance_meters: f64,
+    pub elevation_gain_meters: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrackPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: Option<f64>,
+}
+
+/// Optional criteria for listing activities
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ActivityFilter {
+    pub category: Option<i32>,
+    pub tag: Option<i32>,
+}
+
+impl Activity {
+    pub fn new(id: i32, title: String, start: DateTime<Utc>, user_id: i32) -> Self {
+        Self {
+            id,
+            title,
+            description: None,
+            start,
+            end: None,
+            category_id: None,
+            tag_ids: Vec::new(),
+            recurrence: None,
+            ical_uid: None,
+            metrics: None,
+            user_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SkippedImport {
+    pub uid: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportResult {
+    pub dry_run: bool,
+    pub created: Vec<Activity>,
+    pub skipped: Vec<SkippedImport>,
+}
+
+/// Validation error of a single row of an import file
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportRowError {
+    pub row: u64,
+    pub reason: String,
+}
@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::errors::{ActivityUpdateError, QueryActivityError};
+
+use super::{activity::{Activity, ActivityFilter, ImportResult, TrackPoint}, recurrence::Occurrence};
+
+/// All methods are scoped to the owning user. An activity of another user is treated as not found.
+#[async_trait]
+pub trait ActivityApi: Send + Sync {
+    async fn find_all_by_user_id(&self, user_id: i32, filter: &ActivityFilter) -> Result<Vec<Activity>, QueryActivityError>;
+    async fn find_by_id(&self, activity_id: i32, user_id: i32) -> Result<Activity, QueryActivityError>;
+    /// Fails with `InvalidReference` if the category or one of the tags does not belong to the user
+    async fn save_activity(&self, activity: Activity) -> Result<Activity, ActivityUpdateError>;
+    /// Expands all activities (recurring or not) to occurrences starting within `[from, to)`
+    async fn find_occurrences(&self, user_id: i32, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Occurrence>, QueryActivityError>;
+    /// Inserts all activities in a single transaction. Activities with an `ical_uid` that was already imported are skipped.
+    /// In a dry run nothing is written.
+    async fn import_activities(&self, user_id: i32, activities: Vec<Activity>, dry_run: bool) -> Result<ImportResult, ActivityUpdateError>;
+    /// Inserts an activity recorded by a GPS device together with its track. The activity must have metrics.
+    async fn import_recording(&self, activity: Activity, track: Vec<TrackPoint>) -> Result<Activity, ActivityUpdateError>;
+    /// Simplified track of a recorded activity. Fails with `NotFound` if the activity has no track.
+    async fn find_track(&self, activity_id: i32, user_id: i32) -> Result<Vec<TrackPoint>, QueryActivityError>;
+    async fn delete_activity(&self, activity_id: i32, user_id: i32) -> Result<(), ActivityUpdateError>;
+}
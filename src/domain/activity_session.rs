@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A tracked period of time spent on an activity. A session without `end` is a running timer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActivitySession {
+    pub id: i32,
+    pub activity_id: i32,
+    pub user_id: i32,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl ActivitySession {
+    pub fn is_running(&self) -> bool {
+        self.end.is_none()
+    }
+}
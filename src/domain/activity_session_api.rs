@@ -0,0 +1,15 @@
+use async_trait::async_trait;
+
+use crate::error::errors::{QueryActivityError, TimerError};
+
+use super::activity_session::ActivitySession;
+
+#[async_trait]
+pub trait ActivitySessionApi: Send + Sync {
+    /// Starts a timer for the activity. Fails if the user already has a running timer.
+    async fn start_timer(&self, activity_id: i32, user_id: i32) -> Result<ActivitySession, TimerError>;
+    /// Stops the running timer of the activity.
+    async fn stop_timer(&self, activity_id: i32, user_id: i32) -> Result<ActivitySession, TimerError>;
+    async fn find_running_by_user_id(&self, user_id: i32) -> Result<Option<ActivitySession>, QueryActivityError>;
+    async fn find_all_by_activity_id(&self, activity_id: i32, user_id: i32) -> Result<Vec<ActivitySession>, QueryActivityError>;
+}
@@ -0,0 +1,18 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{domain::api_token::{ApiToken, TokenScope}, error::errors::ApiTokenError};
+
+/// Tokens are only stored as hash. Every token has its own server side session, which starts with `session_state`.
+#[async_trait]
+pub trait ApiTokenApi: Send + Sync {
+    /// Returns the token and its plain text value, which is only shown once
+    async fn create_token(&self, user_id: i32, name: &str, scopes: &[TokenScope], expires_at: DateTime<Utc>, session_state: HashMap<String, String>) -> Result<(ApiToken, String), ApiTokenError>;
+    async fn find_by_user(&self, user_id: i32) -> Result<Vec<ApiToken>, ApiTokenError>;
+    /// Deletes the token and its session
+    async fn revoke(&self, user_id: i32, id: i32) -> Result<(), ApiTokenError>;
+    /// The token, if it exists and is not expired. Updates `last_used_at`.
+    async fn authenticate(&self, token: &str) -> Result<Option<ApiToken>, ApiTokenError>;
+}
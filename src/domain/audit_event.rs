@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A security relevant event of an account. Events are only appended, never changed.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub user_id: Option<i32>,
+    /// The address of a login attempt, also if it does not belong to a user
+    pub email: Option<String>,
+    pub event_type: AuditEventType,
+    pub details: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    LoginSucceeded,
+    LoginFailed,
+    /// The password was correct and the login waits for the second factor
+    MfaRequired,
+    MfaFailed,
+    PasswordChanged,
+    PasswordReset,
+    MfaEnabled,
+    MfaDisabled,
+    SessionRevoked,
+    AccountDisabled,
+    AccountEnabled,
+}
+
+impl AuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::LoginSucceeded => "login_succeeded",
+            AuditEventType::LoginFailed => "login_failed",
+            AuditEventType::MfaRequired => "mfa_required",
+            AuditEventType::MfaFailed => "mfa_failed",
+            AuditEventType::PasswordChanged => "password_changed",
+            AuditEventType::PasswordReset => "password_reset",
+            AuditEventType::MfaEnabled => "mfa_enabled",
+            AuditEventType::MfaDisabled => "mfa_disabled",
+            AuditEventType::SessionRevoked => "session_revoked",
+            AuditEventType::AccountDisabled => "account_disabled",
+            AuditEventType::AccountEnabled => "account_enabled",
+        }
+    }
+
+    pub fn parse(event_type: &str) -> Option<Self> {
+        match event_type {
+            "login_succeeded" => Some(AuditEventType::LoginSucceeded),
+            "login_failed" => Some(AuditEventType::LoginFailed),
+            "mfa_required" => Some(AuditEventType::MfaRequired),
+            "mfa_failed" => Some(AuditEventType::MfaFailed),
+            "password_changed" => Some(AuditEventType::PasswordChanged),
+            "password_reset" => Some(AuditEventType::PasswordReset),
+            "mfa_enabled" => Some(AuditEventType::MfaEnabled),
+            "mfa_disabled" => Some(AuditEventType::MfaDisabled),
+            "session_revoked" => Some(AuditEventType::SessionRevoked),
+            "account_disabled" => Some(AuditEventType::AccountDisabled),
+            "account_enabled" => Some(AuditEventType::AccountEnabled),
+            _ => None,
+        }
+    }
+}
+
+/// An event to record. Ip and user agent are taken from the request by `audit_log::record_event`.
+pub struct NewAuditEvent {
+    pub user_id: Option<i32>,
+    pub email: Option<String>,
+    pub event_type: AuditEventType,
+    pub details: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl NewAuditEvent {
+    pub fn new(user_id: Option<i32>, event_type: AuditEventType) -> Self {
+        Self {
+            user_id,
+            email: None,
+            event_type,
+            details: None,
+            ip: None,
+            user_agent: None,
+        }
+    }
+
+    pub fn for_user(user_id: i32, event_type: AuditEventType) -> Self {
+        Self::new(Some(user_id), event_type)
+    }
+
+    pub fn with_details(mut self, details: &str) -> Self {
+        self.details = Some(details.to_owned());
+        self
+    }
+}
+
+/// All conditions are optional, `from` is inclusive and `to` exclusive
+#[derive(Clone, Debug, Default)]
+pub struct AuditEventFilter {
+    pub user_id: Option<i32>,
+    pub event_type: Option<AuditEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
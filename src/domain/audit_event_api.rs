@@ -0,0 +1,11 @@
+use async_trait::async_trait;
+
+use crate::{domain::audit_event::{AuditEvent, AuditEventFilter, NewAuditEvent}, error::errors::AuditEventError};
+
+/// Append-only log of security relevant account events
+#[async_trait]
+pub trait AuditEventApi: Send + Sync {
+    async fn record(&self, event: NewAuditEvent) -> Result<(), AuditEventError>;
+    /// Matching events, newest first, and the number of all matching events
+    async fn find(&self, filter: &AuditEventFilter, offset: i64, limit: i64) -> Result<(Vec<AuditEvent>, i64), AuditEventError>;
+}
@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+
+use crate::error::errors::CalendarFeedError;
+
+/// Tokens for the read-only calendar feed. Only a hash of the token is stored.
+#[async_trait]
+pub trait CalendarFeedApi: Send + Sync {
+    /// Creates a new feed token for the user and returns it in plain text. A previous token is revoked.
+    async fn create_feed_token(&self, user_id: i32) -> Result<String, CalendarFeedError>;
+    async fn revoke_feed_token(&self, user_id: i32) -> Result<(), CalendarFeedError>;
+    async fn has_feed_token(&self, user_id: i32) -> Result<bool, CalendarFeedError>;
+    /// Returns the id of the user the token belongs to
+    async fn find_user_id_by_token(&self, token: &str) -> Result<Option<i32>, CalendarFeedError>;
+}
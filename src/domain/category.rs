@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub user_id: i32,
+}
+
+impl Category {
+    pub fn new(id: i32, name: String, user_id: i32) -> Self {
+        Self {
+            id,
+            name,
+            color: None,
+            icon: None,
+            user_id,
+        }
+    }
+}
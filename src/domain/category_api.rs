@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+
+use crate::error::errors::{CategoryUpdateError, QueryCategoryError};
+
+use super::category::Category;
+
+#[async_trait]
+pub trait CategoryApi: Send + Sync {
+    async fn find_all_by_user_id(&self, user_id: i32) -> Result<Vec<Category>, QueryCategoryError>;
+    async fn find_by_id(&self, category_id: i32, user_id: i32) -> Result<Category, QueryCategoryError>;
+    async fn save_category(&self, category: Category) -> Result<Category, CategoryUpdateError>;
+    /// Deletes the category and its goals. Activities of the category are kept without category.
+    async fn delete_category(&self, category_id: i32, user_id: i32) -> Result<(), CategoryUpdateError>;
+}
@@ -0,0 +1,13 @@
+use async_trait::async_trait;
+
+use crate::error::errors::EmailOtpError;
+
+/// Short-lived numeric login codes sent by mail. A user has at most one valid code.
+#[async_trait]
+pub trait EmailOtpApi: Send + Sync {
+    /// Creates a new code, replaces a previous one and returns it in plain text.
+    /// Fails if the previous code was created shortly before.
+    async fn create_code(&self, user_id: i32) -> Result<String, EmailOtpError>;
+    /// Checks the code. It is deleted if it matches. After too many wrong codes no code is accepted until it expired.
+    async fn verify_code(&self, user_id: i32, code: &str) -> Result<bool, EmailOtpError>;
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GoalMetric {
+    /// Target is given in minutes
+    Duration,
+    /// Target is the number of sessions
+    Count,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GoalPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl GoalPeriod {
+    /// First day of the period containing `date`. Weeks start on monday.
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            GoalPeriod::Day => date,
+            GoalPeriod::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            GoalPeriod::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// First day of the following period. Expects a period start.
+    pub fn next(&self, period_start: NaiveDate) -> NaiveDate {
+        match self {
+            GoalPeriod::Day => period_start + Duration::days(1),
+            GoalPeriod::Week => period_start + Duration::weeks(1),
+            GoalPeriod::Month => (period_start.with_day(28).unwrap() + Duration::days(4)).with_day(1).unwrap(),
+        }
+    }
+
+    /// First day of the preceding period. Expects a period start.
+    pub fn previous(&self, period_start: NaiveDate) -> NaiveDate {
+        self.start_of(period_start - Duration::days(1))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Goal {
+    pub id: i32,
+    pub title: String,
+    pub category_id: i32,
+    pub metric: GoalMetric,
+    pub target: i64,
+    pub period: GoalPeriod,
+    pub user_id: i32,
+}
+
+/// A tracked span of time that counts towards a goal
+pub struct TrackedTime {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GoalProgress {
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    /// Minutes or number of sessions in the current period, depending on the metric
+    pub value: i64,
+    pub target: i64,
+    pub achieved: bool,
+    /// Consecutive achieved periods up to now. The current period does not break the streak while it is in progress.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GoalWithProgress {
+    #[serde(flatten)]
+    pub goal: Goal,
+    pub progress: GoalProgress,
+}
+
+impl Goal {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("Title must not be empty".to_owned());
+        }
+
+        if self.target <= 0 {
+            return Err("Target must be greater than 0".to_owned());
+        }
+
+        Ok(())
+    }
+
+    /// Computes the progress of the period containing `today` and the streaks from the tracked times.
+    /// Times are assigned to the periods by their local date in `timezone`, like the stats.
+    pub fn progress(&self, tracked: &[TrackedTime], today: NaiveDate, timezone: Tz) -> GoalProgress {
+        let mut values: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+        for time in tracked {
+            let period_start = self.period.start_of(time.start.with_timezone(&timezone).date_naive());
+            let value = match self.metric {
+                GoalMetric::Duration => (time.end - time.start).num_minutes().max(0),
+                GoalMetric::Count => 1,
+            };
+            *values.entry(period_start).or_insert(0) += value;
+        }
+
+        let is_achieved = |period_start: &NaiveDate| values.get(period_start).is_some_and(|value| *value >= self.target);
+
+        let current_period = self.period.start_of(today);
+        let value = values.get(&current_period).copied().unwrap_or(0);
+
+        let mut current_streak = 0;
+        let mut period = if is_achieved(&current_period) { current_period } else { self.period.previous(current_period) };
+        while is_achieved(&period) {
+            current_streak += 1;
+            period = self.period.previous(period);
+        }
+
+        let mut longest_streak = 0;
+        let mut streak = 0;
+        let mut expected: Option<NaiveDate> = None;
+        for period_start in values.keys().filter(|period_start| is_achieved(period_start)) {
+            streak = if expected == Some(*period_start) { streak + 1 } else { 1 };
+            longest_streak = longest_streak.max(streak);
+            expected = Some(self.period.next(*period_start));
+        }
+
+        GoalProgress {
+            period_start: current_period,
+            period_end: self.period.next(current_period) - Duration::days(1),
+            value,
+            target: self.target,
+            achieved: value >= self.target,
+            current_streak,
+            longest_streak,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use super::{Goal, GoalMetric, GoalPeriod, TrackedTime};
+
+    fn tracked(y: i32, m: u32, d: u32, minutes: i64) -> TrackedTime {
+        let start = Utc.with_ymd_and_hms(y, m, d, 10, 0, 0).unwrap();
+        TrackedTime { start, end: start + chrono::Duration::minutes(minutes) }
+    }
+
+    fn goal(metric: GoalMetric, target: i64, period: GoalPeriod) -> Goal {
+        Goal { id: 1, title: "Goal".to_owned(), category_id: 1, metric, target, period, user_id: 1 }
+    }
+
+    #[test]
+    fn should_compute_weekly_duration_progress_and_streaks() {
+        // 3 hours per week
+        let goal = goal(GoalMetric::Duration, 180, GoalPeriod::Week);
+        let tracked = vec![
+            tracked(2025, 3, 3, 180),
+            // week of 2025-03-10 is missed
+            tracked(2025, 3, 17, 120),
+            tracked(2025, 3, 19, 60),
+            tracked(2025, 3, 24, 200),
+            tracked(2025, 3, 31, 30),
+        ];
+
+        let progress = goal.progress(&tracked, NaiveDate::from_ymd_opt(2025, 4, 2).unwrap(), Tz::UTC);
+
+        assert_eq!(progress.period_start, NaiveDate::from_ymd_opt(2025, 3, 31).unwrap());
+        assert_eq!(progress.period_end, NaiveDate::from_ymd_opt(2025, 4, 6).unwrap());
+        assert_eq!(progress.value, 30);
+        assert!(!progress.achieved);
+        assert_eq!(progress.current_streak, 2);
+        assert_eq!(progress.longest_streak, 2);
+    }
+
+    #[test]
+    fn should_count_sessions_per_month() {
+        // run 2 times a month
+        let goal = goal(GoalMetric::Count, 2, GoalPeriod::Month);
+        let tracked = vec![
+            tracked(2025, 1, 31, 30),
+            tracked(2025, 1, 2, 30),
+            tracked(2025, 2, 10, 30),
+            tracked(2025, 2, 28, 30),
+            tracked(2025, 3, 1, 30),
+            tracked(2025, 3, 5, 30),
+        ];
+
+        let progress = goal.progress(&tracked, NaiveDate::from_ymd_opt(2025, 3, 15).unwrap(), Tz::UTC);
+
+        assert_eq!(progress.value, 2);
+        assert!(progress.achieved);
+        assert_eq!(progress.current_streak, 3);
+        assert_eq!(progress.longest_streak, 3);
+    }
+
+    #[test]
+    fn should_assign_sessions_to_local_days() {
+        let goal = goal(GoalMetric::Count, 1, GoalPeriod::Day);
+        // 2025-04-01 23:30 UTC is already 2025-04-02 in Berlin
+        let start = Utc.with_ymd_and_hms(2025, 4, 1, 23, 30, 0).unwrap();
+        let tracked = vec![TrackedTime { start, end: start + chrono::Duration::minutes(30) }];
+
+        let progress = goal.progress(&tracked, NaiveDate::from_ymd_opt(2025, 4, 2).unwrap(), chrono_tz::Europe::Berlin);
+
+        assert!(progress.achieved);
+        assert!(!goal.progress(&tracked, NaiveDate::from_ymd_opt(2025, 4, 2).unwrap(), Tz::UTC).achieved);
+    }
+}
@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+
+use crate::error::errors::{GoalUpdateError, QueryGoalError};
+
+use super::goal::{Goal, GoalWithProgress};
+
+#[async_trait]
+pub trait GoalApi: Send + Sync {
+    async fn find_all_by_user_id(&self, user_id: i32) -> Result<Vec<Goal>, QueryGoalError>;
+    /// Loads all goals of the user with the progress of the period containing `today`, both in `timezone`
+    async fn find_progress_by_user_id(&self, user_id: i32, today: NaiveDate, timezone: Tz) -> Result<Vec<GoalWithProgress>, QueryGoalError>;
+    /// Fails with `InvalidReference` if the category does not belong to the user
+    async fn save_goal(&self, goal: Goal) -> Result<Goal, GoalUpdateError>;
+    async fn delete_goal(&self, goal_id: i32, user_id: i32) -> Result<(), GoalUpdateError>;
+}
@@ -0,0 +1,20 @@
+/// Failed logins are counted per account and per client, so that neither a single account nor many accounts
+/// can be attacked from one address without being slowed down
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoginSubject {
+    /// The normalized email address. Unknown addresses are counted the same way, so a lockout does not reveal an account.
+    Account(String),
+    Ip(String),
+    /// Mails requested for the normalized email address, e.g. a new verification link
+    Mail(String),
+}
+
+impl LoginSubject {
+    pub fn key(&self) -> String {
+        match self {
+            LoginSubject::Account(email) => format!("account:{}", email),
+            LoginSubject::Ip(ip) => format!("ip:{}", ip),
+            LoginSubject::Mail(email) => format!("mail:{}", email),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{domain::login_attempt::LoginSubject, error::errors::LoginAttemptError};
+
+#[async_trait]
+pub trait LoginAttemptApi: Send + Sync {
+    /// End of the current lockout, `None` if a login may be tried
+    async fn locked_until(&self, subject: &LoginSubject) -> Result<Option<DateTime<Utc>>, LoginAttemptError>;
+    /// Counts a failed password or second factor and locks the subject if the threshold is reached
+    async fn record_failure(&self, subject: &LoginSubject) -> Result<(), LoginAttemptError>;
+    /// Forgets the failures after a successful login
+    async fn reset(&self, subject: &LoginSubject) -> Result<(), LoginAttemptError>;
+}
@@ -0,0 +1,17 @@
+/// A plain text mail
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mail {
+    pub fn new(to: &str, subject: &str, body: String) -> Self {
+        Self {
+            to: to.to_owned(),
+            subject: subject.to_owned(),
+            body,
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use async_trait::async_trait;
+
+use crate::error::errors::MailError;
+
+use super::mail::Mail;
+
+/// Transport for outgoing mails
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+
+use crate::error::errors::RecoveryCodeError;
+
+/// One-time codes that replace the second factor, if the authenticator is lost. Only argon2 hashes are stored.
+#[async_trait]
+pub trait RecoveryCodeApi: Send + Sync {
+    /// Replaces all codes of the user with new ones and returns them in plain text
+    async fn replace_codes(&self, user_id: i32) -> Result<Vec<String>, RecoveryCodeError>;
+    async fn count_remaining(&self, user_id: i32) -> Result<usize, RecoveryCodeError>;
+    /// Returns true and deletes the code, if it is one of the remaining codes of the user
+    async fn use_code(&self, user_id: i32, code: &str) -> Result<bool, RecoveryCodeError>;
+    async fn delete_codes(&self, user_id: i32) -> Result<(), RecoveryCodeError>;
+}
@@ -0,0 +1,346 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound of generated candidates, so that a bad rule cannot keep the server busy
+const MAX_ITERATIONS: i64 = 100_000;
+const MAX_INTERVAL: u32 = 1000;
+const MAX_COUNT: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A subset of the RFC 5545 RRULE. `by_weekday` is only used for weekly rules,
+/// monthly rules repeat on the day of month of the first occurrence.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    #[serde(default)]
+    pub by_weekday: Vec<Weekday>,
+    pub until: Option<DateTime<Utc>>,
+    pub count: Option<u32>,
+    /// Start times of occurrences that were removed from the series
+    #[serde(default)]
+    pub exceptions: Vec<DateTime<Utc>>,
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+impl RecurrenceRule {
+    pub fn new(frequency: Frequency) -> Self {
+        Self {
+            frequency,
+            interval: 1,
+            by_weekday: Vec::new(),
+            until: None,
+            count: None,
+            exceptions: Vec::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 || self.interval > MAX_INTERVAL {
+            return Err(format!("Interval must be between 1 and {}", MAX_INTERVAL));
+        }
+
+        if self.until.is_some() && self.count.is_some() {
+            return Err("Until and count must not be used together".to_owned());
+        }
+
+        if self.count.is_some_and(|count| count == 0 || count > MAX_COUNT) {
+            return Err(format!("Count must be between 1 and {}", MAX_COUNT));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the start times of all occurrences in `[from, to)` for a series starting at `dtstart`
+    pub fn occurrences(&self, dtstart: DateTime<Utc>, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut result = Vec::new();
+        let mut generated: u32 = 0;
+
+        for candidate in self.candidates(dtstart) {
+            if candidate >= to {
+                break;
+            }
+
+            if let Some(until) = self.until {
+                if candidate > until {
+                    break;
+                }
+            }
+
+            if let Some(count) = self.count {
+                if generated >= count {
+                    break;
+                }
+            }
+            generated += 1;
+
+            if candidate >= from && !self.exceptions.contains(&candidate) {
+                result.push(candidate);
+            }
+        }
+
+        result
+    }
+
+    /// Ordered iterator over the candidates starting at `dtstart`. It ends after `MAX_ITERATIONS` periods
+    /// or at the end of the supported date range.
+    fn candidates(&self, dtstart: DateTime<Utc>) -> Box<dyn Iterator<Item = DateTime<Utc>> + '_> {
+        let interval = self.interval.max(1) as i64;
+        let start = dtstart.naive_utc();
+
+        match self.frequency {
+            Frequency::Daily => Box::new(
+                (0..MAX_ITERATIONS).map_while(move |i| {
+                    Duration::try_days(i * interval)
+                        .and_then(|offset| start.checked_add_signed(offset))
+                        .map(|candidate| candidate.and_utc())
+                })
+            ),
+            Frequency::Weekly => {
+                let mut weekdays = self.by_weekday.clone();
+                if weekdays.is_empty() {
+                    weekdays.push(start.weekday());
+                }
+                weekdays.sort_by_key(|day| day.num_days_from_monday());
+                weekdays.dedup();
+
+                let week_start = start - Duration::days(start.weekday().num_days_from_monday() as i64);
+
+                Box::new((0..MAX_ITERATIONS)
+                    .map_while(move |week| Duration::try_weeks(week * interval).and_then(|offset| week_start.checked_add_signed(offset)))
+                    .flat_map(move |week_start| {
+                        weekdays.clone().into_iter()
+                            .filter_map(move |day| week_start.checked_add_signed(Duration::days(day.num_days_from_monday() as i64)))
+                    })
+                    .filter(move |candidate| *candidate >= start)
+                    .map(|candidate| candidate.and_utc()))
+            },
+            Frequency::Monthly => Box::new(
+                (0..MAX_ITERATIONS)
+                    .map_while(move |i| add_months(start, i * interval))
+                    .flatten()
+                    .map(|candidate| candidate.and_utc())
+            ),
+        }
+    }
+}
+
+/// Same day of month and time, `months` later. Returns `Some(None)` if the day does not exist in that month
+/// and `None` if the month is outside of the supported date range.
+fn add_months(start: NaiveDateTime, months: i64) -> Option<Option<NaiveDateTime>> {
+    let total = start.year() as i64 * 12 + start.month0() as i64 + months;
+    let year = i32::try_from(total.div_euclid(12)).ok()?;
+    let month = total.rem_euclid(12) as u32 + 1;
+    NaiveDate::from_ymd_opt(year, month, 1)?;
+
+    Some(NaiveDate::from_ymd_opt(year, month, start.day())
+        .map(|date| date.and_time(start.time())))
+}
+
+fn format_rrule_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+pub fn parse_rrule_date(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S") {
+        return Ok(date_time.and_utc());
+    }
+
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| format!("Invalid date: {}", value))
+}
+
+fn weekday_to_rrule(day: &Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn weekday_from_rrule(value: &str) -> Result<Weekday, String> {
+    match value {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("Unsupported weekday: {}", value)),
+    }
+}
+
+/// Formats the rule as RRULE value, e.g. `FREQ=WEEKLY;INTERVAL=1;BYDAY=MO,WE,FR`.
+/// Exceptions are not part of the RRULE (see EXDATE).
+impl Display for RecurrenceRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={};INTERVAL={}", frequency, self.interval)?;
+
+        if !self.by_weekday.is_empty() {
+            let days: Vec<&str> = self.by_weekday.iter().map(weekday_to_rrule).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+
+        if let Some(until) = &self.until {
+            write!(f, ";UNTIL={}", format_rrule_date(until))?;
+        }
+
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rule = RecurrenceRule::new(Frequency::Daily);
+        let mut has_frequency = false;
+
+        for part in s.trim().trim_start_matches("RRULE:").split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=')
+                .ok_or_else(|| format!("Invalid rule part: {}", part))?;
+
+            match key {
+                "FREQ" => {
+                    rule.frequency = match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("Unsupported frequency: {}", value)),
+                    };
+                    has_frequency = true;
+                },
+                "INTERVAL" => rule.interval = value.parse().map_err(|_| format!("Invalid interval: {}", value))?,
+                "BYDAY" => rule.by_weekday = value.split(',').map(weekday_from_rrule).collect::<Result<_, _>>()?,
+                "UNTIL" => rule.until = Some(parse_rrule_date(value)?),
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| format!("Invalid count: {}", value))?),
+                "WKST" => {},
+                _ => return Err(format!("Unsupported rule part: {}", key)),
+            }
+        }
+
+        if !has_frequency {
+            return Err("FREQ is missing".to_owned());
+        }
+
+        rule.validate()?;
+
+        Ok(rule)
+    }
+}
+
+/// A single (possibly recurring) occurrence of an activity
+#[derive(Clone, Debug, Serialize)]
+pub struct Occurrence {
+    pub activity_id: i32,
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub category_id: Option<i32>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc, Weekday};
+
+    use super::{Frequency, RecurrenceRule};
+
+    fn date(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 7, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn should_expand_weekly_rule_by_weekday() {
+        let mut rule = RecurrenceRule::new(Frequency::Weekly);
+        rule.by_weekday = vec![Weekday::Mon, Weekday::Wed, Weekday::Fri];
+
+        // 2025-04-02 is a wednesday
+        let occurrences = rule.occurrences(date(2025, 4, 2), date(2025, 4, 1), date(2025, 4, 12));
+
+        assert_eq!(occurrences, vec![date(2025, 4, 2), date(2025, 4, 4), date(2025, 4, 7), date(2025, 4, 9), date(2025, 4, 11)]);
+    }
+
+    #[test]
+    fn should_respect_count_and_exceptions() {
+        let mut rule = RecurrenceRule::new(Frequency::Daily);
+        rule.interval = 2;
+        rule.count = Some(3);
+        rule.exceptions = vec![date(2025, 4, 3)];
+
+        let occurrences = rule.occurrences(date(2025, 4, 1), date(2025, 1, 1), date(2026, 1, 1));
+
+        assert_eq!(occurrences, vec![date(2025, 4, 1), date(2025, 4, 5)]);
+    }
+
+    #[test]
+    fn should_skip_months_without_day() {
+        let mut rule = RecurrenceRule::new(Frequency::Monthly);
+        rule.until = Some(date(2025, 5, 31));
+
+        let occurrences = rule.occurrences(date(2025, 1, 31), date(2025, 1, 1), date(2026, 1, 1));
+
+        assert_eq!(occurrences, vec![date(2025, 1, 31), date(2025, 3, 31), date(2025, 5, 31)]);
+    }
+
+    #[test]
+    fn should_end_at_date_range_with_huge_interval() {
+        let far_future = DateTime::<Utc>::MAX_UTC;
+
+        for frequency in [Frequency::Daily, Frequency::Weekly, Frequency::Monthly] {
+            let mut rule = RecurrenceRule::new(frequency);
+            rule.interval = u32::MAX;
+
+            let occurrences = rule.occurrences(date(2025, 4, 1), date(2025, 1, 1), far_future);
+
+            assert_eq!(occurrences, vec![date(2025, 4, 1)]);
+        }
+    }
+
+    #[test]
+    fn should_reject_huge_interval_and_count() {
+        assert!("FREQ=MONTHLY;INTERVAL=4294967295".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;COUNT=4294967295".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=1000;COUNT=10000".parse::<RecurrenceRule>().is_ok());
+    }
+
+    #[test]
+    fn should_format_and_parse_rrule() {
+        let mut rule = RecurrenceRule::new(Frequency::Weekly);
+        rule.interval = 2;
+        rule.by_weekday = vec![Weekday::Mon, Weekday::Fri];
+        rule.until = Some(Utc.with_ymd_and_hms(2025, 12, 31, 23, 59, 59).unwrap());
+
+        let rrule = rule.to_string();
+        assert_eq!(rrule, "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;UNTIL=20251231T235959Z");
+        assert_eq!(rrule.parse::<RecurrenceRule>().unwrap(), rule);
+    }
+}
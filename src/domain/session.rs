@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Session keys written by `middleware::track_sessions`. The session store copies them into own columns.
+pub const SESSION_KEY_PUBLIC_ID: &str = "public_id";
+pub const SESSION_KEY_USER_ID: &str = "user_id";
+pub const SESSION_KEY_USER_AGENT: &str = "user_agent";
+pub const SESSION_KEY_IP: &str = "ip";
+pub const SESSION_KEY_LAST_SEEN: &str = "last_seen";
+
+/// A login session of a user. The id is not the session key, it only identifies the session for revocation.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// The session of the request
+    pub current: bool,
+}
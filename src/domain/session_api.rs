@@ -0,0 +1,12 @@
+use async_trait::async_trait;
+
+use crate::{domain::session::SessionInfo, error::errors::SessionError};
+
+#[async_trait]
+pub trait SessionApi: Send + Sync {
+    /// Active sessions of the user, the most recently used first. `current` is set for `current_id`.
+    async fn find_by_user(&self, user_id: i32, current_id: Option<&str>) -> Result<Vec<SessionInfo>, SessionError>;
+    async fn delete(&self, user_id: i32, id: &str) -> Result<(), SessionError>;
+    /// Ends all sessions of the user
+    async fn delete_all(&self, user_id: i32) -> Result<(), SessionError>;
+}
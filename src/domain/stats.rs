@@ -0,0 +1,51 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGrouping {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsDimension {
+    Category,
+}
+
+/// Both dates are inclusive and interpreted in the time zone of the user
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatsQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group_by: StatsGrouping,
+    pub by: Option<StatsDimension>,
+}
+
+impl StatsQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.to < self.from {
+            return Err("`to` must not be before `from`".to_owned());
+        }
+
+        // the query ends at the start of the following day
+        if self.to.succ_opt().is_none() {
+            return Err("`to` is out of range".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsEntry {
+    /// Local date of the period start: `2025-04-02` (day and week) or `2025-04` (month)
+    pub period: String,
+    /// Only set when grouped by category. `None` for sessions of activities without category.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<i32>,
+    pub total_seconds: i64,
+    pub count: i64,
+}
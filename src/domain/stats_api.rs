@@ -0,0 +1,12 @@
+use async_trait::async_trait;
+use chrono_tz::Tz;
+
+use crate::error::errors::QueryStatsError;
+
+use super::stats::{StatsEntry, StatsQuery};
+
+#[async_trait]
+pub trait StatsApi: Send + Sync {
+    /// Aggregates the tracked sessions of the user. Periods are computed in the given time zone.
+    async fn aggregate(&self, user_id: i32, query: &StatsQuery, timezone: Tz) -> Result<Vec<StatsEntry>, QueryStatsError>;
+}
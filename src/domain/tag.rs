@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    pub user_id: i32,
+}
+
+impl Tag {
+    pub fn new(id: i32, name: String, user_id: i32) -> Self {
+        Self {
+            id,
+            name,
+            user_id,
+        }
+    }
+}
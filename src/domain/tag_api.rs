@@ -0,0 +1,13 @@
+use async_trait::async_trait;
+
+use crate::error::errors::{QueryTagError, TagUpdateError};
+
+use super::tag::Tag;
+
+#[async_trait]
+pub trait TagApi: Send + Sync {
+    async fn find_all_by_user_id(&self, user_id: i32) -> Result<Vec<Tag>, QueryTagError>;
+    async fn save_tag(&self, tag: Tag) -> Result<Tag, TagUpdateError>;
+    /// Deletes the tag and detaches it from all activities
+    async fn delete_tag(&self, tag_id: i32, user_id: i32) -> Result<(), TagUpdateError>;
+}
@@ -1,4 +1,5 @@
 use authfix::AccountInfo;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -6,25 +7,141 @@ pub struct User {
     pub id: i32,
     pub email: String,
     pub name: String,
+    /// IANA time zone name, e.g. `Europe/Berlin`
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    /// Incremented whenever all sessions of the user must end, e.g. after a password reset
+    #[serde(default)]
+    pub session_version: i64,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// Disabled by an admin, the user cannot log in
+    #[serde(default)]
+    pub disabled: bool,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "user" => Some(Role::User),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+fn default_timezone() -> String {
+    DEFAULT_TIMEZONE.to_owned()
+}
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// Argon2 accepts longer passwords, but hashing huge inputs is a cheap way to keep the server busy
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_EMAIL_LENGTH: usize = 254;
+
 impl AccountInfo for User {}
 
+/// Normalizes the email address (trimmed, lowercase) and performs a basic syntax check
+pub fn normalize_email(email: &str) -> Result<String, String> {
+    let email = email.trim().to_lowercase();
+    let valid = email.len() <= MAX_EMAIL_LENGTH
+        && !email.chars().any(char::is_whitespace)
+        && email.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && !domain.contains('@') && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+        });
+
+    if valid {
+        Ok(email)
+    } else {
+        Err("Invalid email address".to_owned())
+    }
+}
+
+/// The email address of a login in the stored form. Unlike `normalize_email` it accepts invalid addresses,
+/// so the login lookup and the throttling of failed logins use the same key for every attempt.
+pub fn login_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Name must not be empty".to_owned());
+    }
+
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("Name must not be longer than {} characters", MAX_NAME_LENGTH));
+    }
+
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Err(format!("Password must have at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+
+    if length > MAX_PASSWORD_LENGTH {
+        return Err(format!("Password must not be longer than {} characters", MAX_PASSWORD_LENGTH));
+    }
+
+    Ok(())
+}
+
 impl User {
     pub fn new(id: i32, email: String, name: String) -> Self {
         User {
             id,
             email: email,
             name: name,
+            timezone: default_timezone(),
+            email_verified: false,
+            session_version: 0,
+            roles: vec![Role::User],
+            disabled: false,
         }
     }
-    
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+
+    /// The time zone of the user, UTC if the stored name is unknown
+    pub fn time_zone(&self) -> Tz {
+        self.timezone.parse().unwrap_or_else(|_| {
+            log::warn!("User {} has an invalid time zone '{}', falling back to UTC", self.id, self.timezone);
+            Tz::UTC
+        })
+    }
+
 }
 
 pub struct Credentials {
     pub id: i32,
     pub password: String,
+    /// The factor used at login (`mfa_id`) and the TOTP secret, if an authenticator is configured
     pub mfa_config: Option<MfaConfig>,
+    /// One-time codes by mail are available as factor
+    pub email_otp_enabled: bool,
     pub user_id: i32,
 
 }
@@ -35,6 +152,7 @@ impl Credentials {
             id,
             password,
             mfa_config: None,
+            email_otp_enabled: false,
             user_id
         }
     }
@@ -62,4 +180,27 @@ impl MfaConfig {
             secret: Some(secret.to_owned()),
         }
     }    
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_email, validate_password};
+
+    #[test]
+    fn should_normalize_and_validate_email() {
+        assert_eq!(normalize_email(" Linda@Example.org ").unwrap(), "linda@example.org");
+        assert!(normalize_email("linda").is_err());
+        assert!(normalize_email("@example.org").is_err());
+        assert!(normalize_email("linda@example").is_err());
+        assert!(normalize_email("linda@@example.org").is_err());
+        assert!(normalize_email("lin da@example.org").is_err());
+    }
+
+    #[test]
+    fn should_validate_password_length() {
+        assert!(validate_password("short").is_err());
+        assert!(validate_password("long enough").is_ok());
+        assert!(validate_password(&"x".repeat(129)).is_err());
+    }
+}
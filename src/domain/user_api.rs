@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use crate::{domain::user::User, error::errors::{QueryUserError, UserUpdateError}};
+use crate::{domain::user::{Role, User}, error::errors::{QueryUserError, UserUpdateError}};
 
 use super::user::Credentials;
 
@@ -8,7 +8,20 @@ use super::user::Credentials;
 pub trait UserApi: Send + Sync {
     async fn find_by_email(&self, email: &str) -> Result<User, QueryUserError>;
     async fn find_by_id(&self, user_id: i32) -> Result<User, QueryUserError>;
+    /// Users whose email or name contains `search`, ordered by email, and the number of all matching users
+    async fn find_users(&self, search: Option<&str>, offset: i64, limit: i64) -> Result<(Vec<User>, i64), QueryUserError>;
+    /// Updates name, email and settings of an existing user without touching the credentials
+    async fn save_user(&self, user: User) -> Result<User, UserUpdateError>;
     async fn save_user_with_credentials(&self, user: User, password: &str) -> Result<User, UserUpdateError>;
+    /// Creates a user with a verified email address and the roles of `user`, all or nothing
+    async fn create_verified_user(&self, user: User, password: &str) -> Result<User, UserUpdateError>;
+    /// Hashes and stores the new password and ends all sessions of the user
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), UserUpdateError>;
+    async fn mark_email_verified(&self, user_id: i32) -> Result<(), UserUpdateError>;
+    /// Replaces the roles of the user
+    async fn set_roles(&self, user_id: i32, roles: &[Role]) -> Result<(), UserUpdateError>;
+    /// A disabled user cannot log in and all sessions end
+    async fn set_disabled(&self, user_id: i32, disabled: bool) -> Result<(), UserUpdateError>;
     async fn save_credentials(&self, credentials: Credentials) -> Result<Credentials, UserUpdateError>;
     async fn find_credentials_by_user_id(&self, user_id: i32) -> Result<Credentials, QueryUserError>;
 }
@@ -0,0 +1,24 @@
+use chrono::Duration;
+
+/// One-time tokens sent to the user by mail
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenPurpose {
+    EmailVerification,
+    PasswordReset,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::EmailVerification => "email_verification",
+            TokenPurpose::PasswordReset => "password_reset",
+        }
+    }
+
+    pub fn validity(&self) -> Duration {
+        match self {
+            TokenPurpose::EmailVerification => Duration::hours(48),
+            TokenPurpose::PasswordReset => Duration::hours(1),
+        }
+    }
+}
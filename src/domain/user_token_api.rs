@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+
+use crate::error::errors::UserTokenError;
+
+use super::user_token::TokenPurpose;
+
+/// Single use tokens of a user. Only a hash of the token is stored.
+#[async_trait]
+pub trait UserTokenApi: Send + Sync {
+    /// Creates a new token and returns it in plain text. Older tokens of the user with the same purpose are revoked.
+    async fn create_token(&self, user_id: i32, purpose: TokenPurpose) -> Result<String, UserTokenError>;
+    /// Deletes the token and returns the id of its user, if the token exists and is not expired
+    async fn consume_token(&self, token: &str, purpose: TokenPurpose) -> Result<Option<i32>, UserTokenError>;
+}
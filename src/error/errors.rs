@@ -1,6 +1,12 @@
+use rusqlite::ErrorCode;
 use thiserror::Error;
 use tokio::task::JoinError;
 
+/// True if the statement failed because of a UNIQUE (or other) constraint
+pub fn is_constraint_violation(e: &rusqlite::Error) -> bool {
+    matches!(e, rusqlite::Error::SqliteFailure(err, _) if err.code == ErrorCode::ConstraintViolation)
+}
+
 #[derive(Error, Debug)]
 #[error("Cannot query user: {msg}")]
 pub struct QueryUserError {
@@ -8,19 +14,36 @@ pub struct QueryUserError {
 }
 
 #[derive(Error, Debug)]
-#[error("Cannot save user: {msg}")]
-pub struct UserUpdateError {
-    msg: String,
+pub enum UserUpdateError {
+    #[error("Email address is already registered")]
+    DuplicateEmail,
+    #[error("Cannot save user: {0}")]
+    Update(String),
 }
 
 impl UserUpdateError {
     pub fn new(msg: &str) -> Self {
-        Self { msg: msg.to_owned() }
+        Self::Update(msg.to_owned())
     }
 }
 
 
 impl From<rusqlite::Error> for UserUpdateError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(_, Some(ref msg)) if is_constraint_violation(&e) && msg.contains("users.email") => Self::DuplicateEmail,
+            e => Self::Update(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for UserUpdateError {
+    fn from(e: JoinError) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for QueryUserError {
     fn from(e: rusqlite::Error) -> Self {
         Self {
             msg:  e.to_string()
@@ -28,7 +51,7 @@ impl From<rusqlite::Error> for UserUpdateError {
     }
 }
 
-impl From<JoinError> for UserUpdateError {
+impl From<JoinError> for QueryUserError {
     fn from(e: JoinError) -> Self {
         Self {
             msg:  e.to_string()
@@ -36,7 +59,144 @@ impl From<JoinError> for UserUpdateError {
     }
 }
 
-impl From<rusqlite::Error> for QueryUserError {
+#[derive(Error, Debug)]
+pub enum QueryActivityError {
+    #[error("Activity not found")]
+    NotFound,
+    #[error("Cannot query activity: {0}")]
+    Query(String),
+}
+
+#[derive(Error, Debug)]
+pub enum ActivityUpdateError {
+    #[error("Activity not found")]
+    NotFound,
+    #[error("Invalid reference: {0}")]
+    InvalidReference(String),
+    #[error("Cannot save activity: {0}")]
+    Update(String),
+}
+
+impl From<rusqlite::Error> for QueryActivityError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e => Self::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for QueryActivityError {
+    fn from(e: JoinError) -> Self {
+        Self::Query(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for ActivityUpdateError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e => Self::Update(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for ActivityUpdateError {
+    fn from(e: JoinError) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TimerError {
+    #[error("Activity not found")]
+    ActivityNotFound,
+    #[error("Another timer is already running")]
+    AlreadyRunning,
+    #[error("No timer is running for this activity")]
+    NotRunning,
+    #[error("Cannot update timer: {0}")]
+    Update(String),
+}
+
+impl From<rusqlite::Error> for TimerError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+impl From<JoinError> for TimerError {
+    fn from(e: JoinError) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum QueryCategoryError {
+    #[error("Category not found")]
+    NotFound,
+    #[error("Cannot query category: {0}")]
+    Query(String),
+}
+
+#[derive(Error, Debug)]
+pub enum CategoryUpdateError {
+    #[error("Category not found")]
+    NotFound,
+    #[error("A category with this name already exists")]
+    Duplicate,
+    #[error("Cannot save category: {0}")]
+    Update(String),
+}
+
+impl From<rusqlite::Error> for QueryCategoryError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e => Self::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for QueryCategoryError {
+    fn from(e: JoinError) -> Self {
+        Self::Query(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for CategoryUpdateError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e if is_constraint_violation(&e) => Self::Duplicate,
+            e => Self::Update(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for CategoryUpdateError {
+    fn from(e: JoinError) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Cannot query tag: {msg}")]
+pub struct QueryTagError {
+    msg: String,
+}
+
+#[derive(Error, Debug)]
+pub enum TagUpdateError {
+    #[error("Tag not found")]
+    NotFound,
+    #[error("A tag with this name already exists")]
+    Duplicate,
+    #[error("Cannot save tag: {0}")]
+    Update(String),
+}
+
+impl From<rusqlite::Error> for QueryTagError {
     fn from(e: rusqlite::Error) -> Self {
         Self {
             msg:  e.to_string()
@@ -44,11 +204,308 @@ impl From<rusqlite::Error> for QueryUserError {
     }
 }
 
-impl From<JoinError> for QueryUserError {
+impl From<JoinError> for QueryTagError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<rusqlite::Error> for TagUpdateError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e if is_constraint_violation(&e) => Self::Duplicate,
+            e => Self::Update(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for TagUpdateError {
+    fn from(e: JoinError) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum QueryGoalError {
+    #[error("Goal not found")]
+    NotFound,
+    #[error("Cannot query goal: {0}")]
+    Query(String),
+}
+
+#[derive(Error, Debug)]
+pub enum GoalUpdateError {
+    #[error("Goal not found")]
+    NotFound,
+    #[error("Invalid reference: {0}")]
+    InvalidReference(String),
+    #[error("Cannot save goal: {0}")]
+    Update(String),
+}
+
+impl From<rusqlite::Error> for QueryGoalError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e => Self::Query(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for QueryGoalError {
+    fn from(e: JoinError) -> Self {
+        Self::Query(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for GoalUpdateError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound,
+            e => Self::Update(e.to_string()),
+        }
+    }
+}
+
+impl From<JoinError> for GoalUpdateError {
     fn from(e: JoinError) -> Self {
+        Self::Update(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Cannot query stats: {msg}")]
+pub struct QueryStatsError {
+    msg: String,
+}
+
+impl From<rusqlite::Error> for QueryStatsError {
+    fn from(e: rusqlite::Error) -> Self {
         Self {
             msg:  e.to_string()
         }
     }
 }
 
+impl From<JoinError> for QueryStatsError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Calendar feed error: {msg}")]
+pub struct CalendarFeedError {
+    msg: String,
+}
+
+impl From<rusqlite::Error> for CalendarFeedError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<JoinError> for CalendarFeedError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Cannot send mail: {msg}")]
+pub struct MailError {
+    msg: String,
+}
+
+impl MailError {
+    pub fn new(msg: &str) -> Self {
+        Self { msg: msg.to_owned() }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("User token error: {msg}")]
+pub struct UserTokenError {
+    msg: String,
+}
+
+impl From<rusqlite::Error> for UserTokenError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<JoinError> for UserTokenError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Recovery code error: {msg}")]
+pub struct RecoveryCodeError {
+    msg: String,
+}
+
+impl RecoveryCodeError {
+    pub fn new(msg: &str) -> Self {
+        Self { msg: msg.to_owned() }
+    }
+}
+
+impl From<rusqlite::Error> for RecoveryCodeError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<JoinError> for RecoveryCodeError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Email OTP error: {msg}")]
+pub struct EmailOtpError {
+    msg: String,
+}
+
+impl EmailOtpError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            msg: msg.to_owned()
+        }
+    }
+}
+
+impl From<rusqlite::Error> for EmailOtpError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<JoinError> for EmailOtpError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Login attempt error: {msg}")]
+pub struct LoginAttemptError {
+    msg: String,
+}
+
+impl From<rusqlite::Error> for LoginAttemptError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<JoinError> for LoginAttemptError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Session not found")]
+    NotFound,
+    #[error("Session error: {0}")]
+    Query(String),
+}
+
+impl From<rusqlite::Error> for SessionError {
+    fn from(e: rusqlite::Error) -> Self {
+        SessionError::Query(e.to_string())
+    }
+}
+
+impl From<JoinError> for SessionError {
+    fn from(e: JoinError) -> Self {
+        SessionError::Query(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Secret cipher error: {msg}")]
+pub struct SecretCipherError {
+    msg: String,
+}
+
+impl SecretCipherError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            msg: msg.to_owned()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ApiTokenError {
+    #[error("Token not found")]
+    NotFound,
+    #[error("Token error: {0}")]
+    Query(String),
+}
+
+impl From<rusqlite::Error> for ApiTokenError {
+    fn from(e: rusqlite::Error) -> Self {
+        ApiTokenError::Query(e.to_string())
+    }
+}
+
+impl From<JoinError> for ApiTokenError {
+    fn from(e: JoinError) -> Self {
+        ApiTokenError::Query(e.to_string())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Audit event error: {msg}")]
+pub struct AuditEventError {
+    msg: String,
+}
+
+impl From<rusqlite::Error> for AuditEventError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
+
+impl From<JoinError> for AuditEventError {
+    fn from(e: JoinError) -> Self {
+        Self {
+            msg:  e.to_string()
+        }
+    }
+}
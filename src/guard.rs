@@ -0,0 +1,42 @@
+use actix_web::{dev::Payload, error, web::Data, Error, FromRequest, HttpRequest};
+use authfix::AuthToken;
+use futures::future::LocalBoxFuture;
+
+use crate::domain::{user::{Role, User}, user_api::UserApi};
+
+/// Extracts the authenticated user and rejects the request with `403 Forbidden` if the user is not an admin.
+/// The roles are loaded from the database, so a revoked role takes effect without a new login.
+pub struct AdminUser(pub User);
+
+impl AdminUser {
+    pub fn get_authenticated_user(&self) -> &User {
+        &self.0
+    }
+}
+
+impl FromRequest for AdminUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let auth_token = AuthToken::<User>::from_request(req, payload);
+        let user_api = req.app_data::<Data<dyn UserApi>>().cloned();
+
+        Box::pin(async move {
+            let auth_token = auth_token.await.map_err(Into::into)?;
+            let user_api = user_api.ok_or_else(|| error::ErrorInternalServerError("UserApi is not configured"))?;
+
+            let user = user_api.find_by_id(auth_token.get_authenticated_user().id).await
+                .map_err(|err| {
+                    log::error!("Cannot load user: {}", err);
+                    error::ErrorInternalServerError("Cannot load user")
+                })?;
+
+            if user.has_role(Role::Admin) {
+                Ok(AdminUser(user))
+            } else {
+                Err(error::ErrorForbidden("Admin role required"))
+            }
+        })
+    }
+}
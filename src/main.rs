@@ -1,11 +1,10 @@
 use std::sync::Arc;
 
-use authfix::actix_session::{config::{PersistentSession, SessionLifecycle}, storage::CookieSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, middleware::Logger, HttpServer};
-use config::{config::Config, db::DbConfig};
-use domain::{user::User, user_api::UserApi};
+use actix_web::{middleware::Logger, HttpServer};
+use config::{config::Config, cookie_key::CookieKeys, db::DbConfig};
+use domain::{user::{Role, User}, user_api::UserApi};
 use rusqlite::Connection;
-use service::user_service::UserService;
+use service::{secret_cipher::SecretCipher, user_service::UserService};
 
 mod config;
 mod controller;
@@ -13,23 +12,47 @@ mod service;
 mod domain;
 mod error;
 mod app_factory;
+mod middleware;
+mod guard;
 
-pub fn create_session_middleware (key: Key) -> SessionMiddleware<CookieSessionStore> {
-    let persistent_session = PersistentSession::default();
-    let lc = SessionLifecycle::PersistentSession(persistent_session);
-    SessionMiddleware::builder(CookieSessionStore::default(), key)
-                .cookie_name("sessionId".to_string())
-                .cookie_http_only(true)
-                .cookie_same_site(actix_web::cookie::SameSite::Lax) 
-                .cookie_secure(true)
-                .session_lifecycle(lc)
-                .build()    
+/// Adds a column to a table that was created by an older version. Returns `true` if the column was added.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> bool {
+    let exists: bool = conn.query_row(
+        "SELECT count(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        (table, column),
+        |row| row.get(0)).unwrap();
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, definition), []).unwrap();
+    }
+
+    !exists
 }
 
 // ToDo: This should placed inside an separate init / config module
 pub fn create_db(db_config: &DbConfig) -> Connection {
     let conn = Connection::open(db_config.get_database()).unwrap();
-    conn.execute("CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY, name TEXT, email TEXT UNIQUE);", []).unwrap();
+    conn.execute("CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY, name TEXT, email TEXT UNIQUE, timezone TEXT NOT NULL DEFAULT 'UTC', email_verified INTEGER NOT NULL DEFAULT 0, session_version INTEGER NOT NULL DEFAULT 0, disabled INTEGER NOT NULL DEFAULT 0);", []).unwrap();
+    add_column_if_missing(&conn, "users", "timezone", "TEXT NOT NULL DEFAULT 'UTC'");
+    if add_column_if_missing(&conn, "users", "email_verified", "INTEGER NOT NULL DEFAULT 0") {
+        // accounts created before the verification was introduced can still log in
+        conn.execute("UPDATE users SET email_verified = 1;", []).unwrap();
+    }
+    add_column_if_missing(&conn, "users", "session_version", "INTEGER NOT NULL DEFAULT 0");
+    add_column_if_missing(&conn, "users", "disabled", "INTEGER NOT NULL DEFAULT 0");
+
+    let user_role_table = r#"
+        CREATE TABLE IF NOT EXISTS user_roles (
+            user_id INTEGER NOT NULL,
+            role TEXT NOT NULL,
+            PRIMARY KEY (user_id, role),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(user_role_table, []).unwrap();
+    // every user has the role `user`, also users created before roles existed
+    conn.execute("INSERT OR IGNORE INTO user_roles (user_id, role) SELECT id, 'user' FROM users;", []).unwrap();
 
     let credential_table = r#"
         CREATE TABLE IF NOT EXISTS credentials (
@@ -37,18 +60,238 @@ pub fn create_db(db_config: &DbConfig) -> Connection {
             password TEXT,
             mfa_id TEXT,
             mfa_secret TEXT,
+            email_otp_enabled INTEGER NOT NULL DEFAULT 0,
             user_id INTEGER UNIQUE,
             FOREIGN KEY (user_id) REFERENCES users(id)
         );
     "#;
 
     conn.execute(credential_table, []).unwrap();
+    add_column_if_missing(&conn, "credentials", "email_otp_enabled", "INTEGER NOT NULL DEFAULT 0");
+
+    let category_table = r#"
+        CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            color TEXT,
+            icon TEXT,
+            user_id INTEGER NOT NULL,
+            UNIQUE (user_id, name),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(category_table, []).unwrap();
+
+    let tag_table = r#"
+        CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            user_id INTEGER NOT NULL,
+            UNIQUE (user_id, name),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(tag_table, []).unwrap();
+
+    let activity_table = r#"
+        CREATE TABLE IF NOT EXISTS activities (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            description TEXT,
+            start_time TEXT NOT NULL,
+            end_time TEXT,
+            category_id INTEGER,
+            rrule TEXT,
+            exdates TEXT,
+            ical_uid TEXT,
+            user_id INTEGER NOT NULL,
+            FOREIGN KEY (category_id) REFERENCES categories(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(activity_table, []).unwrap();
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_activities_ical_uid ON activities (user_id, ical_uid);", []).unwrap();
+
+    let activity_tag_table = r#"
+        CREATE TABLE IF NOT EXISTS activity_tags (
+            activity_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (activity_id, tag_id),
+            FOREIGN KEY (activity_id) REFERENCES activities(id),
+            FOREIGN KEY (tag_id) REFERENCES tags(id)
+        );
+    "#;
+
+    conn.execute(activity_tag_table, []).unwrap();
+
+    let activity_track_table = r#"
+        CREATE TABLE IF NOT EXISTS activity_tracks (
+            activity_id INTEGER PRIMARY KEY,
+            sport TEXT,
+            duration_seconds INTEGER NOT NULL,
+            distance_meters REAL NOT NULL,
+            elevation_gain_meters REAL NOT NULL,
+            track TEXT NOT NULL,
+            FOREIGN KEY (activity_id) REFERENCES activities(id)
+        );
+    "#;
+
+    conn.execute(activity_track_table, []).unwrap();
+
+    let activity_session_table = r#"
+        CREATE TABLE IF NOT EXISTS activity_sessions (
+            id INTEGER PRIMARY KEY,
+            activity_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            start_time TEXT NOT NULL,
+            end_time TEXT,
+            FOREIGN KEY (activity_id) REFERENCES activities(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(activity_session_table, []).unwrap();
+    // a user can only have one running timer
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_activity_sessions_running ON activity_sessions (user_id) WHERE end_time IS NULL;", []).unwrap();
+
+    let goal_table = r#"
+        CREATE TABLE IF NOT EXISTS goals (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            category_id INTEGER NOT NULL,
+            metric TEXT NOT NULL,
+            target INTEGER NOT NULL,
+            period TEXT NOT NULL,
+            user_id INTEGER NOT NULL,
+            FOREIGN KEY (category_id) REFERENCES categories(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(goal_table, []).unwrap();
+
+    let calendar_feed_table = r#"
+        CREATE TABLE IF NOT EXISTS calendar_feeds (
+            user_id INTEGER PRIMARY KEY,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(calendar_feed_table, []).unwrap();
+
+    let user_token_table = r#"
+        CREATE TABLE IF NOT EXISTS user_tokens (
+            token_hash TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            purpose TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(user_token_table, []).unwrap();
+
+    let email_otp_table = r#"
+        CREATE TABLE IF NOT EXISTS email_otps (
+            user_id INTEGER PRIMARY KEY,
+            code_hash TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(email_otp_table, []).unwrap();
+
+    let recovery_code_table = r#"
+        CREATE TABLE IF NOT EXISTS recovery_codes (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            code_hash TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(recovery_code_table, []).unwrap();
+
+    // subject is the account (email address) or the client ip
+    let login_failure_table = r#"
+        CREATE TABLE IF NOT EXISTS login_failures (
+            subject TEXT PRIMARY KEY,
+            failures INTEGER NOT NULL,
+            last_failure TEXT NOT NULL,
+            locked_until TEXT
+        );
+    "#;
+
+    conn.execute(login_failure_table, []).unwrap();
+
+    // user_id, user_agent, ip and public_id are copied from the session state, see `middleware::track_sessions`.
+    // Sessions of api tokens are not listed and keep the expiry of the token.
+    let session_table = r#"
+        CREATE TABLE IF NOT EXISTS sessions (
+            key_hash TEXT PRIMARY KEY,
+            api_token_id INTEGER,
+            public_id TEXT,
+            user_id INTEGER,
+            user_agent TEXT,
+            ip TEXT,
+            state TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            last_seen TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(session_table, []).unwrap();
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions (user_id);", []).unwrap();
+
+    let api_token_table = r#"
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT NOT NULL UNIQUE,
+            scopes TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            last_used_at TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );
+    "#;
+
+    conn.execute(api_token_table, []).unwrap();
+
+    // Append-only, the user id is kept without foreign key so events survive the user.
+    // `email` is the address of a login attempt, which may not belong to any user.
+    let audit_event_table = r#"
+        CREATE TABLE IF NOT EXISTS audit_events (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER,
+            email TEXT,
+            event_type TEXT NOT NULL,
+            details TEXT,
+            ip TEXT,
+            user_agent TEXT,
+            created_at TEXT NOT NULL
+        );
+    "#;
+
+    conn.execute(audit_event_table, []).unwrap();
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_audit_events_user_id ON audit_events (user_id, created_at);", []).unwrap();
+    conn.execute("CREATE TRIGGER IF NOT EXISTS audit_events_no_update BEFORE UPDATE ON audit_events BEGIN SELECT RAISE(ABORT, 'audit_events is append-only'); END;", []).unwrap();
+    conn.execute("CREATE TRIGGER IF NOT EXISTS audit_events_no_delete BEFORE DELETE ON audit_events BEGIN SELECT RAISE(ABORT, 'audit_events is append-only'); END;", []).unwrap();
 
     conn
 }
 
-pub async fn create_test_user(db_config: DbConfig) {
-    let user_service= UserService::new(Arc::new(db_config));
+pub async fn create_test_user(user_service: &UserService) {
 
 
     match user_service.find_by_email("test@example.org").await {
@@ -60,6 +303,7 @@ pub async fn create_test_user(db_config: DbConfig) {
             let user = User::new(0, "test@example.org".to_owned(), "Hans".to_owned());
 
             let user = user_service.save_user_with_credentials(user, "test123").await.expect("Cannot save test user");
+            user_service.mark_email_verified(user.id).await.expect("Cannot verify test user");
             println!("Test user with id = {} created.", user.id);
         },
     }    
@@ -73,25 +317,50 @@ pub async fn create_test_user(db_config: DbConfig) {
             let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
 
             let user = user_service.save_user_with_credentials(user, "linda123").await.expect("Cannot save test user");
+            user_service.mark_email_verified(user.id).await.expect("Cannot verify test user");
             println!("Test user with id = {} created.", user.id);
         },
     }    
 }
 
+/// Grants the admin role to the configured user, so there is an admin to manage the other users
+async fn grant_admin_role(user_service: &UserService, email: &str) {
+    match user_service.find_by_email(email).await {
+        Ok(user) if !user.has_role(Role::Admin) => {
+            let mut roles = user.roles.clone();
+            roles.push(Role::Admin);
+            user_service.set_roles(user.id, &roles).await.expect("Cannot grant admin role");
+            log::info!("Granted admin role to {}", email);
+        },
+        Ok(_) => {},
+        Err(_) => log::warn!("MA_ADMIN_EMAIL is set, but there is no user with email {}", email),
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
     let config = Config::from_env();
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("debug"));
 
     let db_config = DbConfig::new("activities_db.sqlite3");
     create_db(&db_config);
-    create_test_user(db_config).await;
+    let secret_cipher = Arc::new(SecretCipher::from_config(&config)?);
+    let user_service = UserService::new(Arc::new(db_config), Arc::clone(&secret_cipher));
+    let encrypted = user_service.encrypt_plaintext_mfa_secrets().await.expect("Cannot encrypt TOTP secrets");
+    if encrypted > 0 {
+        log::info!("Encrypted {} TOTP secrets", encrypted);
+    }
+    create_test_user(&user_service).await;
+    if let Some(admin_email) = &config.admin_email {
+        grant_admin_role(&user_service, admin_email).await;
+    }
 
-    let encrypt_key_for_cookies = Key::generate();
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("debug"));
+    let cookie_keys = CookieKeys::load(&config.cookie_key)?;
 
+    let app_config = config.clone();
     let server = HttpServer::new(move || {
-        app_factory::create_app(encrypt_key_for_cookies.clone(), DbConfig::new("activities_db.sqlite3"))
+        app_factory::create_app(cookie_keys.clone(), Arc::clone(&secret_cipher), DbConfig::new("activities_db.sqlite3"), app_config.clone())
         .wrap(Logger::default())
     })
     .bind((config.host.clone(), config.port))?
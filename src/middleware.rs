@@ -0,0 +1,363 @@
+use actix_web::{body::{EitherBody, MessageBody}, dev::{Payload, ServiceRequest, ServiceResponse}, error, http::{header::{self, HeaderValue}, Method, StatusCode}, middleware::Next, web::{Bytes, Data}, Error, HttpResponse};
+use authfix::{actix_session::SessionExt, AuthToken};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::{app_factory::{session_cookie, SESSION_COOKIE_NAME}, config::cookie_key::CookieKeys, domain::{api_token::TokenScope, api_token_api::ApiTokenApi, audit_event::{AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi, login_attempt::LoginSubject, login_attempt_api::LoginAttemptApi, session::{SESSION_KEY_IP, SESSION_KEY_LAST_SEEN, SESSION_KEY_PUBLIC_ID, SESSION_KEY_USER_AGENT, SESSION_KEY_USER_ID}, user::{self, User}, user_api::UserApi}, service::{api_token_service, audit_log, secure_token}};
+
+const LOGIN_PATH: &str = "/api/login";
+const LOGIN_MFA_PATH: &str = "/api/login/mfa";
+/// `status` of the login response if the second factor is needed
+const LOGIN_STATUS_MFA_NEEDED: &str = "MfaNeeded";
+/// Paths that manage the account, its credentials or other accounts require a login with password
+const BEARER_DENIED_PATHS: &[&str] = &["/api/login", "/api/logout", "/api/tokens", "/api/account", "/api/password", "/api/totp", "/api/mfa", "/api/sessions", "/api/admin"];
+/// `last_seen` of a session is updated at most once in this period to avoid a write on every request
+const LAST_SEEN_RESOLUTION_MINUTES: i64 = 5;
+
+#[derive(Deserialize)]
+struct LoginEmail {
+    email: String,
+}
+
+#[derive(Deserialize)]
+struct LoginStatus {
+    status: String,
+}
+
+/// The normalized email address of a login request body
+fn login_email(body: &[u8]) -> Option<String> {
+    serde_json::from_slice::<LoginEmail>(body).ok()
+        .map(|login| user::login_email(&login.email))
+}
+
+/// Ends sessions that were created before the `session_version` of the user changed, e.g. by a password reset,
+/// and sessions of disabled users.
+/// Requests without an authenticated user are passed through.
+pub async fn reject_stale_sessions(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Ok(token) = req.extract::<AuthToken<User>>().await {
+        let session_user = token.get_authenticated_user();
+
+        if let Some(user_api) = req.app_data::<Data<dyn UserApi>>() {
+            let is_stale = match user_api.find_by_id(session_user.id).await {
+                Ok(user) => user.disabled || user.session_version != session_user.session_version,
+                Err(err) => {
+                    log::error!("Cannot load user of session: {}", err);
+                    true
+                },
+            };
+
+            if is_stale {
+                req.get_session().purge();
+                return Err(error::ErrorUnauthorized("Session expired"));
+            }
+        }
+    }
+
+    next.call(req).await
+}
+
+/// Writes the user, device and ip of authenticated sessions into the session state, so the session store
+/// can list them. Values are only written if they changed.
+pub async fn track_sessions(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Ok(token) = req.extract::<AuthToken<User>>().await {
+        let session = req.get_session();
+        let user_agent = req.headers().get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(str::to_owned);
+        let ip = req.peer_addr().map(|peer| peer.ip().to_string());
+
+        if session.get::<String>(SESSION_KEY_PUBLIC_ID)?.is_none() {
+            session.insert(SESSION_KEY_PUBLIC_ID, secure_token::generate_token())?;
+        }
+
+        let user_id = token.get_authenticated_user().id;
+        if session.get::<i32>(SESSION_KEY_USER_ID)? != Some(user_id) {
+            session.insert(SESSION_KEY_USER_ID, user_id)?;
+        }
+
+        if session.get::<Option<String>>(SESSION_KEY_USER_AGENT)?.flatten() != user_agent {
+            session.insert(SESSION_KEY_USER_AGENT, user_agent)?;
+        }
+
+        if session.get::<Option<String>>(SESSION_KEY_IP)?.flatten() != ip {
+            session.insert(SESSION_KEY_IP, ip)?;
+        }
+
+        let now = Utc::now();
+        let last_seen = session.get::<DateTime<Utc>>(SESSION_KEY_LAST_SEEN)?;
+        if last_seen.is_none_or(|last_seen| now - last_seen > Duration::minutes(LAST_SEEN_RESOLUTION_MINUTES)) {
+            session.insert(SESSION_KEY_LAST_SEEN, now)?;
+        }
+    }
+
+    next.call(req).await
+}
+
+/// Rejects logins of locked accounts and clients with `429 Too Many Requests` and counts failed logins.
+/// The account is taken from the login request, so unknown email addresses are locked the same way as existing ones.
+/// Wrong second factors are counted per account by `ThrottledFactor`.
+///
+/// Clients are identified by the peer address of the connection. Behind a reverse proxy all clients share the
+/// address of the proxy, so a few failures of anyone lock out everyone. Do not run the server behind a proxy
+/// unless the client address is taken from a forwarded header that only the trusted proxy can set.
+pub async fn throttle_logins(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    // the decoded path, as used for routing
+    let path = req.match_info().as_str();
+    let is_login = req.method() == Method::POST && (path == LOGIN_PATH || path == LOGIN_MFA_PATH);
+    let login_attempt_api = req.app_data::<Data<dyn LoginAttemptApi>>().cloned();
+
+    let (true, Some(login_attempt_api)) = (is_login, login_attempt_api) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let mut subjects = vec![];
+    if let Some(peer) = req.peer_addr() {
+        subjects.push(LoginSubject::Ip(peer.ip().to_string()));
+    }
+
+    if req.match_info().as_str() == LOGIN_PATH {
+        // the body is read here and handed on to the login handler
+        let body = req.extract::<Bytes>().await?;
+        if let Some(email) = login_email(&body) {
+            subjects.push(LoginSubject::Account(email));
+        }
+        req.set_payload(Payload::from(body));
+    }
+
+    for subject in &subjects {
+        match login_attempt_api.locked_until(subject).await {
+            Ok(Some(locked_until)) => {
+                let retry_after = (locked_until - Utc::now()).num_seconds().max(1);
+                let res = HttpResponse::TooManyRequests()
+                    .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                    .body("Too many failed login attempts, try again later");
+                return Ok(req.into_response(res).map_into_right_body());
+            },
+            Ok(None) => {},
+            Err(err) => log::error!("Cannot check login lockout: {}", err),
+        }
+    }
+
+    let res = next.call(req).await?;
+
+    let failed = matches!(res.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN);
+    for subject in &subjects {
+        let recorded = if failed {
+            login_attempt_api.record_failure(subject).await
+        } else if res.status().is_success() && matches!(subject, LoginSubject::Account(_)) {
+            // failures of an ip only expire, otherwise a login to an own account would allow to try other accounts
+            login_attempt_api.reset(subject).await
+        } else {
+            Ok(())
+        };
+
+        if let Err(err) = recorded {
+            log::error!("Cannot record login attempt: {}", err);
+        }
+    }
+
+    Ok(res.map_into_left_body())
+}
+
+/// Records password logins in the audit log, including logins rejected by `throttle_logins`.
+/// The second login step is recorded by `AuditedFactor`.
+pub async fn audit_logins(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let is_login = req.method() == Method::POST && req.match_info().as_str() == LOGIN_PATH;
+    let audit_event_api = req.app_data::<Data<dyn AuditEventApi>>().cloned();
+
+    let (true, Some(audit_event_api)) = (is_login, audit_event_api) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    // the body is read here and handed on to the login handler
+    let body = req.extract::<Bytes>().await?;
+    let email = login_email(&body);
+    req.set_payload(Payload::from(body));
+
+    let user_id = match (&email, req.app_data::<Data<dyn UserApi>>()) {
+        (Some(email), Some(user_api)) => user_api.find_by_email(email).await.ok().map(|user| user.id),
+        _ => None,
+    };
+
+    let res = next.call(req).await?;
+
+    if !res.status().is_success() {
+        if matches!(res.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS) {
+            let mut event = NewAuditEvent::new(user_id, AuditEventType::LoginFailed);
+            event.email = email;
+            if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                event = event.with_details("locked");
+            }
+            audit_log::record_event(audit_event_api.get_ref(), res.request(), event).await;
+        }
+        return Ok(res.map_into_left_body());
+    }
+
+    // only the response tells if the second factor is needed
+    let (http_req, http_res) = res.into_parts();
+    let (http_res, body) = http_res.into_parts();
+    let body = actix_web::body::to_bytes(body).await
+        .map_err(|_| error::ErrorInternalServerError("Cannot read login response"))?;
+    let mfa_needed = serde_json::from_slice::<LoginStatus>(&body).is_ok_and(|login| login.status == LOGIN_STATUS_MFA_NEEDED);
+
+    let mut event = if mfa_needed {
+        NewAuditEvent::new(user_id, AuditEventType::MfaRequired)
+    } else {
+        NewAuditEvent::new(user_id, AuditEventType::LoginSucceeded).with_details("password")
+    };
+    event.email = email;
+    audit_log::record_event(audit_event_api.get_ref(), &http_req, event).await;
+
+    Ok(ServiceResponse::new(http_req, http_res.set_body(body)).map_into_right_body())
+}
+
+/// Accepts session cookies of the previous cookie key during a key rotation. The cookie is encrypted
+/// with the current key before the session middleware reads it, and sent back to the client.
+pub async fn accept_previous_cookie_key(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let renewed = match (req.app_data::<Data<CookieKeys>>(), req.headers().get(header::COOKIE)) {
+        (Some(cookie_keys), Some(cookie_header)) => cookie_header.to_str().ok()
+            .and_then(|cookie_header| cookie_keys.renew_cookie(cookie_header, SESSION_COOKIE_NAME)),
+        _ => None,
+    };
+
+    let Some((cookie_header, cookie)) = renewed else {
+        return next.call(req).await;
+    };
+
+    req.headers_mut().insert(header::COOKIE, HeaderValue::from_str(&cookie_header)?);
+    let mut res = next.call(req).await?;
+
+    // a cookie set by the session middleware is already encrypted with the current key
+    if !res.response().cookies().any(|cookie| cookie.name() == SESSION_COOKIE_NAME) {
+        res.response_mut().add_cookie(&session_cookie(cookie))?;
+    }
+
+    Ok(res)
+}
+
+/// Authenticates requests with `Authorization: Bearer <api token>`. The request gets the cookie of the session that
+/// belongs to the token, so handlers get the user by `AuthToken<User>` as for a browser login. Cookies of the client
+/// are ignored and no cookies are sent back.
+pub async fn authenticate_bearer_tokens(mut req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let bearer_token = req.headers().get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_owned());
+
+    let (Some(bearer_token), Some(api_token_api), Some(cookie_keys)) = (bearer_token, req.app_data::<Data<dyn ApiTokenApi>>().cloned(), req.app_data::<Data<CookieKeys>>().cloned()) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let api_token = api_token_api.authenticate(&bearer_token).await
+        .map_err(|err| {
+            log::error!("Cannot authenticate api token: {}", err);
+            error::ErrorInternalServerError("Cannot authenticate")
+        })?;
+
+    let Some(api_token) = api_token else {
+        return Err(error::ErrorUnauthorized("The token is invalid or expired"));
+    };
+
+    // the decoded path, as used for routing, so that e.g. `/api/%74okens` is denied as well
+    if BEARER_DENIED_PATHS.iter().any(|path| req.match_info().as_str().starts_with(path)) {
+        return Err(error::ErrorForbidden("Not available with an api token"));
+    }
+
+    let required_scope = match *req.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => TokenScope::Read,
+        _ => TokenScope::Write,
+    };
+    if !api_token.has_scope(required_scope) {
+        let res = HttpResponse::Forbidden().body(format!("The token requires the scope `{}`", required_scope.as_str()));
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+
+    let session_cookie_value = cookie_keys.encrypt(SESSION_COOKIE_NAME, api_token_service::session_key_for(&bearer_token))
+        .ok_or_else(|| error::ErrorInternalServerError("Cannot authenticate"))?;
+    req.headers_mut().insert(header::COOKIE, HeaderValue::from_str(&format!("{}={}", SESSION_COOKIE_NAME, session_cookie_value))?);
+
+    let mut res = next.call(req).await?;
+    res.headers_mut().remove(header::SET_COOKIE);
+
+    Ok(res.map_into_left_body())
+}
+
+#[cfg(test)]
+mod middleware_tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use actix_web::{cookie::Key, http::{header, StatusCode}, middleware::from_fn, test, web::{self, Data, Json}, App, HttpResponse};
+    use chrono::{Duration, Utc};
+    use serde_json::{json, Value};
+
+    use crate::{config::{config::{CookieKeyConfig, LoginThrottleConfig}, cookie_key::CookieKeys, db::DbConfig}, create_db, domain::{api_token::TokenScope, api_token_api::ApiTokenApi, login_attempt_api::LoginAttemptApi}, service::{api_token_service::ApiTokenService, login_attempt_service::LoginAttemptService, secure_token}};
+
+    use super::{authenticate_bearer_tokens, throttle_logins};
+
+    async fn login(body: Json<Value>) -> HttpResponse {
+        if body["password"] == "right" {
+            HttpResponse::Ok().finish()
+        } else {
+            HttpResponse::Unauthorized().finish()
+        }
+    }
+
+    fn login_request(email: &str, password: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/api/login")
+            .peer_addr("10.0.0.1:4711".parse().unwrap())
+            .set_json(json!({ "email": email, "password": password }))
+    }
+
+    #[actix_web::test]
+    async fn should_not_unlock_ip_by_login_to_other_account() {
+        let db_config = DbConfig::new("file:middleware_throttle_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let config = LoginThrottleConfig { max_failures: 10, ip_max_failures: 3, ..LoginThrottleConfig::default() };
+        let login_attempt_api: Arc<dyn LoginAttemptApi> = Arc::new(LoginAttemptService::new(Arc::new(db_config), config));
+        let app = test::init_service(App::new()
+            .app_data(Data::from(login_attempt_api))
+            .wrap(from_fn(throttle_logins))
+            .route("/api/login", web::post().to(login))).await;
+
+        for (email, password, status) in [
+            ("a@example.org", "wrong", StatusCode::UNAUTHORIZED),
+            ("a@example.org", "wrong", StatusCode::UNAUTHORIZED),
+            ("a@example.org", "right", StatusCode::OK),
+            ("b@example.org", "wrong", StatusCode::UNAUTHORIZED),
+            ("b@example.org", "right", StatusCode::TOO_MANY_REQUESTS),
+        ] {
+            let res = test::call_service(&app, login_request(email, password).to_request()).await;
+            assert_eq!(res.status(), status, "Login of {} with {} password", email, password);
+        }
+    }
+
+    #[actix_web::test]
+    async fn should_deny_api_tokens_on_encoded_paths() {
+        let db_config = DbConfig::new("file:middleware_bearer_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let api_token_api: Arc<dyn ApiTokenApi> = Arc::new(ApiTokenService::new(Arc::new(db_config)));
+        let (_, token) = api_token_api.create_token(1, "test", &[TokenScope::Read, TokenScope::Write], Utc::now() + Duration::days(1), HashMap::new()).await.unwrap();
+        let cookie_keys = CookieKeys::load(&CookieKeyConfig {
+            key: Some(secure_token::to_hex(Key::generate().master())),
+            key_file: String::new(),
+            previous_key: None,
+            previous_key_valid_until: None,
+        }).unwrap();
+        let app = test::init_service(App::new()
+            .app_data(Data::from(api_token_api))
+            .app_data(Data::new(cookie_keys))
+            .wrap(from_fn(authenticate_bearer_tokens))
+            .route("/api/tokens", web::post().to(|| async { HttpResponse::Created().finish() }))).await;
+
+        for path in ["/api/tokens", "/api/%74okens"] {
+            let req = test::TestRequest::post()
+                .uri(path)
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+                .to_request();
+            let err = test::try_call_service(&app, req).await.unwrap_err();
+
+            assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN, "Request to {}", path);
+        }
+    }
+}
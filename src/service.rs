@@ -1,2 +1,29 @@
 pub mod user_service;
-pub mod auth_service;
\ No newline at end of file
+pub mod auth_service;
+pub mod activity_service;
+pub mod activity_session_service;
+pub mod category_service;
+pub mod tag_service;
+pub mod goal_service;
+pub mod stats_service;
+pub mod secure_token;
+pub mod ical;
+pub mod calendar_feed_service;
+pub mod local_time;
+pub mod activity_csv;
+pub mod track_import;
+pub mod user_token_service;
+pub mod smtp_mailer;
+pub mod file_mailer;
+pub mod recovery_code_service;
+pub mod recovery_code_factor;
+pub mod email_otp_service;
+pub mod email_otp_factor;
+pub mod login_attempt_service;
+pub mod throttled_factor;
+pub mod session_service;
+pub mod secret_cipher;
+pub mod api_token_service;
+pub mod audit_event_service;
+pub mod audit_log;
+pub mod audited_factor;
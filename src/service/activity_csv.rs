@@ -0,0 +1,206 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::{activity::{Activity, ImportRowError}, category::Category, recurrence::RecurrenceRule, tag::Tag}, service::local_time::resolve_local};
+
+/// Column layout of the CSV import and export. The first row must contain the column names, the order is free.
+///
+/// - `title` (required)
+/// - `description`
+/// - `start` (required): RFC 3339 like `2025-04-01T18:00:00Z` or local time like `2025-04-01 18:00` in the time zone of the user
+/// - `end`: same format as `start`
+/// - `category`: name of an existing category
+/// - `tags`: names of existing tags, separated by `|`
+/// - `rrule`: recurrence rule like `FREQ=WEEKLY;BYDAY=MO,WE`
+pub const CSV_HEADER: [&str; 7] = ["title", "description", "start", "end", "category", "tags", "rrule"];
+
+const TAG_SEPARATOR: char = '|';
+const LOCAL_TIME_FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"];
+
+#[derive(Serialize, Deserialize)]
+struct CsvActivity {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    start: String,
+    #[serde(default)]
+    end: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    tags: Option<String>,
+    #[serde(default)]
+    rrule: Option<String>,
+}
+
+fn parse_time(value: &str, timezone: Tz) -> Result<DateTime<Utc>, String> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.with_timezone(&Utc));
+    }
+
+    LOCAL_TIME_FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|date_time| resolve_local(date_time, timezone))
+        .ok_or_else(|| format!("Invalid date: {}", value))
+}
+
+impl CsvActivity {
+    fn into_activity(self, user_id: i32, timezone: Tz, categories: &[Category], tags: &[Tag]) -> Result<Activity, String> {
+        if self.title.is_empty() {
+            return Err("Title must not be empty".to_owned());
+        }
+
+        let start = parse_time(&self.start, timezone)?;
+        let end = match self.end.filter(|end| !end.is_empty()) {
+            Some(end) => Some(parse_time(&end, timezone)?),
+            None => None,
+        };
+
+        if end.is_some_and(|end| end < start) {
+            return Err("End must not be before start".to_owned());
+        }
+
+        let category_id = match self.category.filter(|category| !category.is_empty()) {
+            Some(name) => Some(categories.iter()
+                .find(|category| category.name == name)
+                .map(|category| category.id)
+                .ok_or_else(|| format!("Unknown category: {}", name))?),
+            None => None,
+        };
+
+        let tag_ids = self.tags.unwrap_or_default()
+            .split(TAG_SEPARATOR)
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| tags.iter()
+                .find(|tag| tag.name == name)
+                .map(|tag| tag.id)
+                .ok_or_else(|| format!("Unknown tag: {}", name)))
+            .collect::<Result<Vec<i32>, String>>()?;
+
+        let recurrence = match self.rrule.filter(|rrule| !rrule.is_empty()) {
+            Some(rrule) => Some(rrule.parse::<RecurrenceRule>().map_err(|e| format!("Invalid rrule: {}", e))?),
+            None => None,
+        };
+
+        let mut activity = Activity::new(0, self.title, start, user_id);
+        activity.description = self.description.filter(|description| !description.is_empty());
+        activity.end = end;
+        activity.category_id = category_id;
+        activity.tag_ids = tag_ids;
+        activity.recurrence = recurrence;
+
+        Ok(activity)
+    }
+}
+
+/// Parses and validates all rows. Returns the errors of all invalid rows, if there is at least one.
+/// Row numbers start with 1 for the header row, like in a spreadsheet.
+pub fn parse_activities(data: &[u8], user_id: i32, timezone: Tz, categories: &[Category], tags: &[Tag]) -> Result<Vec<Activity>, Vec<ImportRowError>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let headers = reader.headers()
+        .map_err(|e| vec![ImportRowError { row: 1, reason: e.to_string() }])?
+        .clone();
+
+    if !headers.iter().any(|header| header == "title") || !headers.iter().any(|header| header == "start") {
+        return Err(vec![ImportRowError { row: 1, reason: "The columns `title` and `start` are required".to_owned() }]);
+    }
+
+    let mut activities = Vec::new();
+    let mut errors = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let result = record
+            .map_err(|e| e.to_string())
+            .and_then(|record| record.deserialize::<CsvActivity>(Some(&headers)).map_err(|e| e.to_string()))
+            .and_then(|row| row.into_activity(user_id, timezone, categories, tags));
+
+        match result {
+            Ok(activity) => activities.push(activity),
+            Err(reason) => errors.push(ImportRowError { row: index as u64 + 2, reason }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(activities)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Writes the activities in the import layout. Times are exported as RFC 3339 in UTC.
+pub fn write_activities(activities: &[Activity], categories: &[Category], tags: &[Tag]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(CSV_HEADER)?;
+
+    for activity in activities {
+        let category = activity.category_id
+            .and_then(|category_id| categories.iter().find(|category| category.id == category_id))
+            .map(|category| category.name.clone());
+
+        let tag_names: Vec<&str> = activity.tag_ids.iter()
+            .filter_map(|tag_id| tags.iter().find(|tag| tag.id == *tag_id))
+            .map(|tag| tag.name.as_str())
+            .collect();
+
+        writer.serialize(CsvActivity {
+            title: activity.title.clone(),
+            description: activity.description.clone(),
+            start: activity.start.to_rfc3339(),
+            end: activity.end.map(|end| end.to_rfc3339()),
+            category,
+            tags: Some(tag_names.join(&TAG_SEPARATOR.to_string())),
+            rrule: activity.recurrence.as_ref().map(|rule| rule.to_string()),
+        })?;
+    }
+
+    writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::domain::{activity::Activity, category::Category, tag::Tag};
+
+    use super::{parse_activities, write_activities};
+
+    #[test]
+    fn should_report_all_invalid_rows() {
+        let categories = vec![Category::new(1, "Sport".to_owned(), 1)];
+        let csv = "title,start,end,category\n\
+            Running,2025-04-01 18:00,2025-04-01 19:00,Sport\n\
+            ,2025-04-01 18:00,,\n\
+            Reading,yesterday,,\n\
+            Cooking,2025-04-01T18:00:00Z,,Food\n";
+
+        let errors = parse_activities(csv.as_bytes(), 1, chrono_tz::UTC, &categories, &[]).unwrap_err();
+
+        assert_eq!(errors.iter().map(|e| e.row).collect::<Vec<u64>>(), vec![3, 4, 5]);
+        assert_eq!(errors[2].reason, "Unknown category: Food");
+    }
+
+    #[test]
+    fn should_import_exported_activities() {
+        let categories = vec![Category::new(1, "Sport".to_owned(), 1)];
+        let tags = vec![Tag::new(4, "outdoor".to_owned(), 1), Tag::new(5, "team".to_owned(), 1)];
+        let mut activity = Activity::new(1, "Football, evening".to_owned(), Utc.with_ymd_and_hms(2025, 4, 1, 18, 0, 0).unwrap(), 1);
+        activity.category_id = Some(1);
+        activity.tag_ids = vec![4, 5];
+
+        let csv = write_activities(&[activity], &categories, &tags).unwrap();
+        let activities = parse_activities(&csv, 1, chrono_tz::Europe::Berlin, &categories, &tags).unwrap();
+
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].title, "Football, evening");
+        assert_eq!(activities[0].start, Utc.with_ymd_and_hms(2025, 4, 1, 18, 0, 0).unwrap());
+        assert_eq!(activities[0].category_id, Some(1));
+        assert_eq!(activities[0].tag_ids, vec![4, 5]);
+    }
+}
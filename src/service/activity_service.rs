@@ -0,0 +1,512 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{types::Type, Connection, Row, Transaction};
+
+use crate::{config::db::DbConfig, domain::{activity::{Activity, ActivityFilter, ImportResult, SkippedImport, SportMetrics, TrackPoint}, activity_api::ActivityApi, recurrence::{Occurrence, RecurrenceRule}}, error::errors::{ActivityUpdateError, QueryActivityError}};
+
+const SELECT_ACTIVITY: &str = r#"
+    SELECT id, title, description, start_time, end_time, category_id, user_id, rrule, exdates, ical_uid,
+        (SELECT group_concat(tag_id) FROM activity_tags WHERE activity_tags.activity_id = activities.id) AS tag_ids,
+        activity_tracks.sport, activity_tracks.duration_seconds, activity_tracks.distance_meters, activity_tracks.elevation_gain_meters
+    FROM activities
+    LEFT JOIN activity_tracks ON activity_tracks.activity_id = activities.id
+"#;
+
+pub struct ActivityService {
+    db_config: Arc<DbConfig>
+}
+
+impl ActivityService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    fn map_row(row: &Row) -> Result<Activity, rusqlite::Error> {
+        let tag_ids: Option<String> = row.get(10)?;
+        let tag_ids = tag_ids
+            .map(|ids| ids.split(',').filter_map(|id| id.parse().ok()).collect())
+            .unwrap_or_default();
+
+        let rrule: Option<String> = row.get(7)?;
+        let exdates: Option<String> = row.get(8)?;
+        let recurrence = match rrule {
+            Some(rrule) => {
+                let mut rule: RecurrenceRule = rrule.parse()
+                    .map_err(|e: String| rusqlite::Error::FromSqlConversionFailure(7, Type::Text, e.into()))?;
+
+                if let Some(exdates) = exdates {
+                    rule.exceptions = exdates.split(',')
+                        .filter(|date| !date.is_empty())
+                        .map(|date| DateTime::parse_from_rfc3339(date).map(|date| date.with_timezone(&Utc)))
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(8, Type::Text, Box::new(e)))?;
+                }
+
+                Some(rule)
+            },
+            None => None,
+        };
+
+        let duration_seconds: Option<i64> = row.get(12)?;
+        let metrics = match duration_seconds {
+            Some(duration_seconds) => Some(SportMetrics {
+                sport: row.get(11)?,
+                duration_seconds,
+                distance_meters: row.get(13)?,
+                elevation_gain_meters: row.get(14)?,
+            }),
+            None => None,
+        };
+
+        Ok(Activity {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            description: row.get(2)?,
+            start: row.get(3)?,
+            end: row.get(4)?,
+            category_id: row.get(5)?,
+            tag_ids,
+            recurrence,
+            ical_uid: row.get(9)?,
+            metrics,
+            user_id: row.get(6)?,
+        })
+    }
+
+    /// Splits the recurrence into the RRULE value and the comma separated exceptions (EXDATE)
+    fn recurrence_columns(recurrence: &Option<RecurrenceRule>) -> (Option<String>, Option<String>) {
+        match recurrence {
+            Some(rule) => {
+                let exdates = rule.exceptions.iter()
+                    .map(|date| date.to_rfc3339())
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                (Some(rule.to_string()), Some(exdates))
+            },
+            None => (None, None),
+        }
+    }
+
+    /// Inserts a new activity with its tags and returns the id
+    fn insert_activity(tx: &Transaction, activity: &Activity) -> Result<i32, rusqlite::Error> {
+        let (rrule, exdates) = ActivityService::recurrence_columns(&activity.recurrence);
+        let insert = r#"
+            INSERT INTO activities (title, description, start_time, end_time, category_id, rrule, exdates, ical_uid, user_id)
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#;
+        tx.execute(insert, (&activity.title, &activity.description, activity.start, activity.end, activity.category_id, rrule, exdates, &activity.ical_uid, activity.user_id))?;
+        let activity_id = tx.last_insert_rowid() as i32;
+
+        for tag_id in &activity.tag_ids {
+            tx.execute("INSERT INTO activity_tags (activity_id, tag_id) values (?1, ?2)", [activity_id, *tag_id])?;
+        }
+
+        Ok(activity_id)
+    }
+
+    /// Stores the metrics and the track of a recorded activity
+    fn insert_track(tx: &Transaction, activity_id: i32, metrics: &SportMetrics, track: &[TrackPoint]) -> Result<(), ActivityUpdateError> {
+        let track = serde_json::to_string(track)
+            .map_err(|e| ActivityUpdateError::Update(e.to_string()))?;
+        let insert = r#"
+            INSERT INTO activity_tracks (activity_id, sport, duration_seconds, distance_meters, elevation_gain_meters, track)
+            values (?1, ?2, ?3, ?4, ?5, ?6)
+        "#;
+        tx.execute(insert, (activity_id, &metrics.sport, metrics.duration_seconds, metrics.distance_meters, metrics.elevation_gain_meters, track))?;
+
+        Ok(())
+    }
+
+    /// Ensures that category and tags of the activity are owned by the same user
+    fn check_references(tx: &Transaction, activity: &Activity) -> Result<(), ActivityUpdateError> {
+        if let Some(category_id) = activity.category_id {
+            let count: i32 = tx.query_row("SELECT COUNT(*) FROM categories WHERE id = ?1 AND user_id = ?2", [category_id, activity.user_id], |row| row.get(0))?;
+            if count == 0 {
+                return Err(ActivityUpdateError::InvalidReference(format!("Unknown category: {}", category_id)));
+            }
+        }
+
+        for tag_id in &activity.tag_ids {
+            let count: i32 = tx.query_row("SELECT COUNT(*) FROM tags WHERE id = ?1 AND user_id = ?2", [*tag_id, activity.user_id], |row| row.get(0))?;
+            if count == 0 {
+                return Err(ActivityUpdateError::InvalidReference(format!("Unknown tag: {}", tag_id)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ActivityApi for ActivityService {
+    async fn find_all_by_user_id(&self, user_id: i32, filter: &ActivityFilter) -> Result<Vec<Activity>, QueryActivityError> {
+        let db = self.db_config.get_database().to_owned();
+        let filter = filter.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let query = format!(r#"{}
+                WHERE user_id = ?1
+                AND (?2 IS NULL OR category_id = ?2)
+                AND (?3 IS NULL OR EXISTS (SELECT 1 FROM activity_tags WHERE activity_tags.activity_id = activities.id AND activity_tags.tag_id = ?3))
+                ORDER BY start_time
+            "#, SELECT_ACTIVITY);
+            let mut stmt = conn.prepare(&query)?;
+            let activities = stmt.query_map((user_id, filter.category, filter.tag), ActivityService::map_row)?
+                .collect::<Result<Vec<Activity>, rusqlite::Error>>()?;
+
+            Ok(activities)
+        }).await?
+    }
+
+    async fn find_by_id(&self, activity_id: i32, user_id: i32) -> Result<Activity, QueryActivityError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            Ok(conn.query_row(&format!("{} WHERE id = ?1 AND user_id = ?2", SELECT_ACTIVITY), [activity_id, user_id], ActivityService::map_row)?)
+        }).await?
+    }
+
+    async fn save_activity(&self, activity: Activity) -> Result<Activity, ActivityUpdateError> {
+        if activity.user_id == 0 {
+            return Err(ActivityUpdateError::Update("Cannot save activity if user_id is 0".to_owned()));
+        }
+
+        let db = self.db_config.get_database().to_owned();
+        let user_id = activity.user_id;
+
+        let activity_id = tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let mut activity = activity;
+            activity.tag_ids.sort();
+            activity.tag_ids.dedup();
+            ActivityService::check_references(&tx, &activity)?;
+
+            let activity_id = if activity.id > 0 {
+                let (rrule, exdates) = ActivityService::recurrence_columns(&activity.recurrence);
+                let update = r#"
+                    UPDATE activities SET title = ?1, description = ?2, start_time = ?3, end_time = ?4, category_id = ?5, rrule = ?6, exdates = ?7
+                    WHERE id = ?8 AND user_id = ?9
+                "#;
+                let changed = tx.execute(update, (&activity.title, &activity.description, activity.start, activity.end, activity.category_id, rrule, exdates, activity.id, activity.user_id))?;
+
+                if changed == 0 {
+                    return Err(ActivityUpdateError::NotFound);
+                }
+
+                tx.execute("DELETE FROM activity_tags WHERE activity_id = ?1", [activity.id])?;
+                for tag_id in &activity.tag_ids {
+                    tx.execute("INSERT INTO activity_tags (activity_id, tag_id) values (?1, ?2)", [activity.id, *tag_id])?;
+                }
+
+                activity.id
+            } else {
+                ActivityService::insert_activity(&tx, &activity)?
+            };
+
+            tx.commit()?;
+
+            Ok(activity_id)
+        }).await??;
+
+        self.find_by_id(activity_id, user_id)
+            .await
+            .map_err(|e| ActivityUpdateError::Update(format!("Unable to retrieve activity after save: {}", e)))
+    }
+
+    async fn find_occurrences(&self, user_id: i32, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Occurrence>, QueryActivityError> {
+        let db = self.db_config.get_database().to_owned();
+        let activities = tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let query = format!(r#"{}
+                WHERE user_id = ?1
+                AND start_time < ?3
+                AND (rrule IS NOT NULL OR start_time >= ?2)
+            "#, SELECT_ACTIVITY);
+            let mut stmt = conn.prepare(&query)?;
+            let activities = stmt.query_map((user_id, from, to), ActivityService::map_row)?
+                .collect::<Result<Vec<Activity>, rusqlite::Error>>()?;
+
+            Ok::<Vec<Activity>, QueryActivityError>(activities)
+        }).await??;
+
+        let mut occurrences: Vec<Occurrence> = activities.into_iter()
+            .flat_map(|activity| {
+                let starts = match &activity.recurrence {
+                    Some(rule) => rule.occurrences(activity.start, from, to),
+                    None => vec![activity.start],
+                };
+                let duration = activity.end.map(|end| end - activity.start);
+
+                starts.into_iter().map(move |start| Occurrence {
+                    activity_id: activity.id,
+                    title: activity.title.clone(),
+                    start,
+                    end: duration.map(|duration| start + duration),
+                    category_id: activity.category_id,
+                })
+            })
+            .collect();
+
+        occurrences.sort_by_key(|occurrence| occurrence.start);
+
+        Ok(occurrences)
+    }
+
+    async fn import_activities(&self, user_id: i32, activities: Vec<Activity>, dry_run: bool) -> Result<ImportResult, ActivityUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let mut created = Vec::new();
+            let mut skipped = Vec::new();
+
+            for mut activity in activities {
+                activity.id = 0;
+                activity.user_id = user_id;
+
+                if let Some(uid) = &activity.ical_uid {
+                    let count: i32 = tx.query_row("SELECT COUNT(*) FROM activities WHERE ical_uid = ?1 AND user_id = ?2", (uid, user_id), |row| row.get(0))?;
+                    let imported_before = created.iter().any(|other: &Activity| other.ical_uid.as_ref() == Some(uid));
+
+                    if count > 0 || imported_before {
+                        skipped.push(SkippedImport { uid: uid.clone(), reason: "Already imported".to_owned() });
+                        continue;
+                    }
+                }
+
+                ActivityService::check_references(&tx, &activity)?;
+                activity.id = ActivityService::insert_activity(&tx, &activity)?;
+                created.push(activity);
+            }
+
+            if dry_run {
+                tx.rollback()?;
+                created.iter_mut().for_each(|activity| activity.id = 0);
+            } else {
+                tx.commit()?;
+            }
+
+            Ok(ImportResult { dry_run, created, skipped })
+        }).await?
+    }
+
+    async fn import_recording(&self, activity: Activity, track: Vec<TrackPoint>) -> Result<Activity, ActivityUpdateError> {
+        if activity.user_id == 0 {
+            return Err(ActivityUpdateError::Update("Cannot save activity if user_id is 0".to_owned()));
+        }
+
+        let metrics = activity.metrics.clone()
+            .ok_or_else(|| ActivityUpdateError::Update("Cannot import recording without metrics".to_owned()))?;
+
+        let db = self.db_config.get_database().to_owned();
+        let user_id = activity.user_id;
+
+        let activity_id = tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            ActivityService::check_references(&tx, &activity)?;
+            let activity_id = ActivityService::insert_activity(&tx, &activity)?;
+            ActivityService::insert_track(&tx, activity_id, &metrics, &track)?;
+            tx.commit()?;
+
+            Ok::<i32, ActivityUpdateError>(activity_id)
+        }).await??;
+
+        self.find_by_id(activity_id, user_id)
+            .await
+            .map_err(|e| ActivityUpdateError::Update(format!("Unable to retrieve activity after save: {}", e)))
+    }
+
+    async fn find_track(&self, activity_id: i32, user_id: i32) -> Result<Vec<TrackPoint>, QueryActivityError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let query = r#"
+                SELECT track FROM activity_tracks
+                JOIN activities ON activities.id = activity_tracks.activity_id
+                WHERE activities.id = ?1 AND activities.user_id = ?2
+            "#;
+            let track: String = conn.query_row(query, [activity_id, user_id], |row| row.get(0))?;
+
+            serde_json::from_str(&track).map_err(|e| QueryActivityError::Query(e.to_string()))
+        }).await?
+    }
+
+    async fn delete_activity(&self, activity_id: i32, user_id: i32) -> Result<(), ActivityUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let deleted = tx.execute("DELETE FROM activities WHERE id = ?1 AND user_id = ?2", [activity_id, user_id])?;
+            if deleted == 0 {
+                return Err(ActivityUpdateError::NotFound);
+            }
+
+            tx.execute("DELETE FROM activity_sessions WHERE activity_id = ?1", [activity_id])?;
+            tx.execute("DELETE FROM activity_tags WHERE activity_id = ?1", [activity_id])?;
+            tx.execute("DELETE FROM activity_tracks WHERE activity_id = ?1", [activity_id])?;
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod activity_service_tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::{config::db::DbConfig, create_db, domain::{activity::{Activity, ActivityFilter, SportMetrics, TrackPoint}, activity_api::ActivityApi, recurrence::{Frequency, RecurrenceRule}, tag::Tag, tag_api::TagApi}, error::errors::{ActivityUpdateError, QueryActivityError}, service::tag_service::TagService};
+
+    use super::ActivityService;
+
+    #[tokio::test]
+    async fn should_save_and_load_activity() {
+        let db_config = DbConfig::new("file:activity_service_save_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::new(db_config));
+
+        let start = Utc.with_ymd_and_hms(2025, 4, 1, 18, 0, 0).unwrap();
+        let mut activity = Activity::new(0, "Running".to_owned(), start, 1);
+        activity.end = Some(Utc.with_ymd_and_hms(2025, 4, 1, 19, 0, 0).unwrap());
+
+        let saved = activity_service.save_activity(activity).await.unwrap();
+        let loaded = activity_service.find_by_id(saved.id, 1).await.unwrap();
+
+        assert_eq!(loaded.title, "Running");
+        assert_eq!(loaded.start, start);
+        assert_eq!(loaded.end, Some(Utc.with_ymd_and_hms(2025, 4, 1, 19, 0, 0).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn should_not_expose_activity_of_other_user() {
+        let db_config = DbConfig::new("file:activity_service_owner_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::new(db_config));
+
+        let activity = Activity::new(0, "Reading".to_owned(), Utc::now(), 1);
+        let saved = activity_service.save_activity(activity).await.unwrap();
+
+        let mut foreign = saved.clone();
+        foreign.user_id = 2;
+        foreign.title = "Hijacked".to_owned();
+
+        assert!(matches!(activity_service.find_by_id(saved.id, 2).await, Err(QueryActivityError::NotFound)));
+        assert!(matches!(activity_service.save_activity(foreign).await, Err(ActivityUpdateError::NotFound)));
+        assert!(matches!(activity_service.delete_activity(saved.id, 2).await, Err(ActivityUpdateError::NotFound)));
+        assert!(activity_service.find_all_by_user_id(2, &ActivityFilter::default()).await.unwrap().is_empty());
+        assert_eq!(activity_service.find_by_id(saved.id, 1).await.unwrap().title, "Reading");
+    }
+
+    #[tokio::test]
+    async fn should_filter_by_tag_and_reject_tag_of_other_user() {
+        let db_config = Arc::new(DbConfig::new("file:activity_service_tag_test?mode=memory&cache=shared"));
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::clone(&db_config));
+        let tag_service = TagService::new(Arc::clone(&db_config));
+
+        let outdoor = tag_service.save_tag(Tag::new(0, "outdoor".to_owned(), 1)).await.unwrap();
+        let foreign = tag_service.save_tag(Tag::new(0, "foreign".to_owned(), 2)).await.unwrap();
+
+        let mut hiking = Activity::new(0, "Hiking".to_owned(), Utc::now(), 1);
+        hiking.tag_ids = vec![outdoor.id];
+        activity_service.save_activity(hiking).await.unwrap();
+        activity_service.save_activity(Activity::new(0, "Reading".to_owned(), Utc::now(), 1)).await.unwrap();
+
+        let mut invalid = Activity::new(0, "Invalid".to_owned(), Utc::now(), 1);
+        invalid.tag_ids = vec![foreign.id];
+
+        let filter = ActivityFilter { tag: Some(outdoor.id), ..Default::default() };
+        let filtered = activity_service.find_all_by_user_id(1, &filter).await.unwrap();
+
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].title, "Hiking");
+        assert_eq!(filtered[0].tag_ids, vec![outdoor.id]);
+        assert!(matches!(activity_service.save_activity(invalid).await, Err(ActivityUpdateError::InvalidReference(_))));
+    }
+
+    #[tokio::test]
+    async fn should_expand_recurring_activity_to_occurrences() {
+        let db_config = DbConfig::new("file:activity_service_occurrence_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::new(db_config));
+
+        let mut running = Activity::new(0, "Running".to_owned(), Utc.with_ymd_and_hms(2025, 4, 1, 18, 0, 0).unwrap(), 1);
+        running.end = Some(Utc.with_ymd_and_hms(2025, 4, 1, 19, 0, 0).unwrap());
+        let mut rule = RecurrenceRule::new(Frequency::Daily);
+        rule.exceptions = vec![Utc.with_ymd_and_hms(2025, 4, 3, 18, 0, 0).unwrap()];
+        running.recurrence = Some(rule);
+        let saved = activity_service.save_activity(running).await.unwrap();
+        assert_eq!(saved.recurrence.as_ref().unwrap().exceptions.len(), 1);
+
+        let occurrences = activity_service.find_occurrences(1, Utc.with_ymd_and_hms(2025, 4, 2, 0, 0, 0).unwrap(), Utc.with_ymd_and_hms(2025, 4, 5, 0, 0, 0).unwrap()).await.unwrap();
+
+        assert_eq!(occurrences.len(), 2);
+        assert_eq!(occurrences[0].start, Utc.with_ymd_and_hms(2025, 4, 2, 18, 0, 0).unwrap());
+        assert_eq!(occurrences[1].end, Some(Utc.with_ymd_and_hms(2025, 4, 4, 19, 0, 0).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn should_skip_already_imported_uids() {
+        let db_config = DbConfig::new("file:activity_service_import_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::new(db_config));
+
+        let imported = |uid: &str| {
+            let mut activity = Activity::new(0, "Imported".to_owned(), Utc::now(), 0);
+            activity.ical_uid = Some(uid.to_owned());
+            activity
+        };
+
+        let dry_run = activity_service.import_activities(1, vec![imported("a"), imported("b")], true).await.unwrap();
+        assert_eq!(dry_run.created.len(), 2);
+        assert!(activity_service.find_all_by_user_id(1, &ActivityFilter::default()).await.unwrap().is_empty());
+
+        activity_service.import_activities(1, vec![imported("a")], false).await.unwrap();
+        let result = activity_service.import_activities(1, vec![imported("a"), imported("b"), imported("b")], false).await.unwrap();
+
+        assert_eq!(result.created.len(), 1);
+        assert_eq!(result.skipped.len(), 2);
+        assert_eq!(activity_service.find_all_by_user_id(1, &ActivityFilter::default()).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_store_metrics_and_track_of_recording() {
+        let db_config = DbConfig::new("file:activity_service_recording_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::new(db_config));
+
+        let metrics = SportMetrics { sport: Some("running".to_owned()), duration_seconds: 1800, distance_meters: 5000.0, elevation_gain_meters: 42.0 };
+        let track = vec![
+            TrackPoint { latitude: 48.0, longitude: 11.0, elevation: Some(500.0) },
+            TrackPoint { latitude: 48.01, longitude: 11.02, elevation: None },
+        ];
+        let mut activity = Activity::new(0, "Morning Run".to_owned(), Utc::now(), 1);
+        activity.metrics = Some(metrics.clone());
+
+        let saved = activity_service.import_recording(activity, track.clone()).await.unwrap();
+        let plain = activity_service.save_activity(Activity::new(0, "Reading".to_owned(), Utc::now(), 1)).await.unwrap();
+
+        assert_eq!(activity_service.find_by_id(saved.id, 1).await.unwrap().metrics, Some(metrics));
+        assert_eq!(activity_service.find_track(saved.id, 1).await.unwrap(), track);
+        assert!(matches!(activity_service.find_track(saved.id, 2).await, Err(QueryActivityError::NotFound)));
+        assert!(matches!(activity_service.find_track(plain.id, 1).await, Err(QueryActivityError::NotFound)));
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, Row};
+
+use crate::{config::db::DbConfig, domain::{activity_session::ActivitySession, activity_session_api::ActivitySessionApi}, error::errors::{QueryActivityError, TimerError}};
+
+const SELECT_SESSION: &str = "SELECT id, activity_id, user_id, start_time, end_time FROM activity_sessions";
+
+pub struct ActivitySessionService {
+    db_config: Arc<DbConfig>
+}
+
+impl ActivitySessionService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    fn map_row(row: &Row) -> Result<ActivitySession, rusqlite::Error> {
+        Ok(ActivitySession {
+            id: row.get(0)?,
+            activity_id: row.get(1)?,
+            user_id: row.get(2)?,
+            start: row.get(3)?,
+            end: row.get(4)?,
+        })
+    }
+}
+
+#[async_trait]
+impl ActivitySessionApi for ActivitySessionService {
+    async fn start_timer(&self, activity_id: i32, user_id: i32) -> Result<ActivitySession, TimerError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let activity: Option<i32> = tx.query_row("SELECT id FROM activities WHERE id = ?1 AND user_id = ?2", [activity_id, user_id], |row| row.get(0))
+                .optional()?;
+            if activity.is_none() {
+                return Err(TimerError::ActivityNotFound);
+            }
+
+            let running: Option<i32> = tx.query_row("SELECT id FROM activity_sessions WHERE user_id = ?1 AND end_time IS NULL", [user_id], |row| row.get(0))
+                .optional()?;
+            if running.is_some() {
+                return Err(TimerError::AlreadyRunning);
+            }
+
+            tx.execute("INSERT INTO activity_sessions (activity_id, user_id, start_time) values (?1, ?2, ?3)", (activity_id, user_id, Utc::now()))?;
+            let session_id = tx.last_insert_rowid() as i32;
+            let session = tx.query_row(&format!("{} WHERE id = ?1", SELECT_SESSION), [session_id], ActivitySessionService::map_row)?;
+
+            tx.commit()?;
+
+            Ok(session)
+        }).await?
+    }
+
+    async fn stop_timer(&self, activity_id: i32, user_id: i32) -> Result<ActivitySession, TimerError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let running: Option<i32> = tx.query_row(
+                "SELECT id FROM activity_sessions WHERE activity_id = ?1 AND user_id = ?2 AND end_time IS NULL",
+                [activity_id, user_id], |row| row.get(0))
+                .optional()?;
+
+            let session_id = match running {
+                Some(session_id) => session_id,
+                None => return Err(TimerError::NotRunning),
+            };
+
+            tx.execute("UPDATE activity_sessions SET end_time = ?1 WHERE id = ?2", (Utc::now(), session_id))?;
+            let session = tx.query_row(&format!("{} WHERE id = ?1", SELECT_SESSION), [session_id], ActivitySessionService::map_row)?;
+
+            tx.commit()?;
+
+            Ok(session)
+        }).await?
+    }
+
+    async fn find_running_by_user_id(&self, user_id: i32) -> Result<Option<ActivitySession>, QueryActivityError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            Ok(conn.query_row(&format!("{} WHERE user_id = ?1 AND end_time IS NULL", SELECT_SESSION), [user_id], ActivitySessionService::map_row)
+                .optional()?)
+        }).await?
+    }
+
+    async fn find_all_by_activity_id(&self, activity_id: i32, user_id: i32) -> Result<Vec<ActivitySession>, QueryActivityError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let mut stmt = conn.prepare(&format!("{} WHERE activity_id = ?1 AND user_id = ?2 ORDER BY start_time", SELECT_SESSION))?;
+            let sessions = stmt.query_map([activity_id, user_id], ActivitySessionService::map_row)?
+                .collect::<Result<Vec<ActivitySession>, rusqlite::Error>>()?;
+
+            Ok(sessions)
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod activity_session_service_tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+
+    use crate::{config::db::DbConfig, create_db, domain::{activity::Activity, activity_api::ActivityApi, activity_session_api::ActivitySessionApi}, error::errors::TimerError, service::activity_service::ActivityService};
+
+    use super::ActivitySessionService;
+
+    #[tokio::test]
+    async fn should_start_and_stop_timer() {
+        let db_config = Arc::new(DbConfig::new("file:activity_session_start_stop_test?mode=memory&cache=shared"));
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::clone(&db_config));
+        let session_service = ActivitySessionService::new(Arc::clone(&db_config));
+        let activity = activity_service.save_activity(Activity::new(0, "Reading".to_owned(), Utc::now(), 1)).await.unwrap();
+
+        let started = session_service.start_timer(activity.id, 1).await.unwrap();
+        assert!(started.is_running());
+        assert_eq!(session_service.find_running_by_user_id(1).await.unwrap().unwrap().id, started.id);
+
+        let stopped = session_service.stop_timer(activity.id, 1).await.unwrap();
+        assert!(!stopped.is_running());
+        assert!(session_service.find_running_by_user_id(1).await.unwrap().is_none());
+        assert_eq!(session_service.find_all_by_activity_id(activity.id, 1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_reject_second_running_timer() {
+        let db_config = Arc::new(DbConfig::new("file:activity_session_second_timer_test?mode=memory&cache=shared"));
+        let _db = create_db(&db_config);
+        let activity_service = ActivityService::new(Arc::clone(&db_config));
+        let session_service = ActivitySessionService::new(Arc::clone(&db_config));
+        let reading = activity_service.save_activity(Activity::new(0, "Reading".to_owned(), Utc::now(), 1)).await.unwrap();
+        let running = activity_service.save_activity(Activity::new(0, "Running".to_owned(), Utc::now(), 1)).await.unwrap();
+
+        session_service.start_timer(reading.id, 1).await.unwrap();
+
+        assert!(matches!(session_service.start_timer(running.id, 1).await, Err(TimerError::AlreadyRunning)));
+        assert!(matches!(session_service.stop_timer(running.id, 1).await, Err(TimerError::NotRunning)));
+        assert!(matches!(session_service.start_timer(reading.id, 2).await, Err(TimerError::ActivityNotFound)));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row};
+
+use crate::{config::db::DbConfig, domain::{api_token::{ApiToken, TokenScope}, api_token_api::ApiTokenApi, session::{SESSION_KEY_IP, SESSION_KEY_LAST_SEEN, SESSION_KEY_PUBLIC_ID, SESSION_KEY_USER_AGENT}}, error::errors::ApiTokenError, service::secure_token};
+
+/// Makes tokens recognizable, e.g. for secret scanners
+const TOKEN_PREFIX: &str = "ma_";
+const SELECT_API_TOKEN: &str = "SELECT id, user_id, name, scopes, created_at, expires_at, last_used_at FROM api_tokens";
+
+/// The key of the session that belongs to the token. It is derived from the token, so it does not need to be stored.
+pub fn session_key_for(token: &str) -> String {
+    secure_token::hash_token(&format!("session:{}", token))
+}
+
+pub struct ApiTokenService {
+    db_config: Arc<DbConfig>
+}
+
+impl ApiTokenService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    fn map_api_token(row: &Row) -> Result<ApiToken, rusqlite::Error> {
+        let scopes: String = row.get(3)?;
+        Ok(ApiToken {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            name: row.get(2)?,
+            scopes: scopes.split(',').filter_map(TokenScope::parse).collect(),
+            created_at: row.get(4)?,
+            expires_at: row.get(5)?,
+            last_used_at: row.get(6)?,
+        })
+    }
+}
+
+#[async_trait]
+impl ApiTokenApi for ApiTokenService {
+    async fn create_token(&self, user_id: i32, name: &str, scopes: &[TokenScope], expires_at: DateTime<Utc>, mut session_state: HashMap<String, String>) -> Result<(ApiToken, String), ApiTokenError> {
+        let db = self.db_config.get_database().to_owned();
+        let name = name.to_owned();
+        let token = format!("{}{}", TOKEN_PREFIX, secure_token::generate_token());
+        let token_hash = secure_token::hash_token(&token);
+        let session_key_hash = secure_token::hash_token(&session_key_for(&token));
+        let scopes = scopes.iter().map(TokenScope::as_str).collect::<Vec<&str>>().join(",");
+
+        // the values of the creating session do not describe the token session
+        for key in [SESSION_KEY_PUBLIC_ID, SESSION_KEY_USER_AGENT, SESSION_KEY_IP, SESSION_KEY_LAST_SEEN] {
+            session_state.remove(key);
+        }
+        let state = serde_json::to_string(&session_state)
+            .map_err(|e| ApiTokenError::Query(e.to_string()))?;
+
+        let api_token = tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+            let now = Utc::now();
+
+            tx.execute(
+                "INSERT INTO api_tokens (user_id, name, token_hash, scopes, created_at, expires_at) values (?1, ?2, ?3, ?4, ?5, ?6)",
+                (user_id, &name, token_hash, &scopes, now, expires_at))?;
+            let id = tx.last_insert_rowid();
+            tx.execute(
+                "INSERT INTO sessions (key_hash, api_token_id, user_id, state, created_at, last_seen, expires_at) values (?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+                (session_key_hash, id, user_id, state, now, expires_at))?;
+
+            let api_token = tx.query_row(&format!("{} WHERE id = ?1", SELECT_API_TOKEN), [id], ApiTokenService::map_api_token)?;
+            tx.commit()?;
+
+            Ok::<ApiToken, ApiTokenError>(api_token)
+        }).await??;
+
+        Ok((api_token, token))
+    }
+
+    async fn find_by_user(&self, user_id: i32) -> Result<Vec<ApiToken>, ApiTokenError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let mut stmt = conn.prepare(&format!("{} WHERE user_id = ?1 ORDER BY created_at DESC", SELECT_API_TOKEN))?;
+            let api_tokens = stmt.query_map([user_id], ApiTokenService::map_api_token)?
+                .collect::<Result<Vec<ApiToken>, rusqlite::Error>>()?;
+
+            Ok(api_tokens)
+        }).await?
+    }
+
+    async fn revoke(&self, user_id: i32, id: i32) -> Result<(), ApiTokenError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let deleted = tx.execute("DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2", (id, user_id))?;
+            if deleted == 0 {
+                return Err(ApiTokenError::NotFound);
+            }
+            tx.execute("DELETE FROM sessions WHERE api_token_id = ?1", [id])?;
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn authenticate(&self, token: &str) -> Result<Option<ApiToken>, ApiTokenError> {
+        let db = self.db_config.get_database().to_owned();
+        let token_hash = secure_token::hash_token(token);
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let now = Utc::now();
+
+            let api_token = conn.query_row(
+                &format!("{} WHERE token_hash = ?1 AND expires_at > ?2", SELECT_API_TOKEN),
+                (&token_hash, now),
+                ApiTokenService::map_api_token)
+                .optional()?;
+
+            if let Some(api_token) = &api_token {
+                conn.execute("UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2", (now, api_token.id))?;
+            }
+
+            Ok(api_token)
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod api_token_service_tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use chrono::{Duration, Utc};
+
+    use crate::{config::db::DbConfig, create_db, domain::{api_token::TokenScope, api_token_api::ApiTokenApi}, error::errors::ApiTokenError};
+
+    use super::ApiTokenService;
+
+    #[tokio::test]
+    async fn should_authenticate_until_revoked() {
+        let db_config = DbConfig::new("file:api_token_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let api_token_service = ApiTokenService::new(Arc::new(db_config));
+
+        let (api_token, token) = api_token_service.create_token(1, "backup", &[TokenScope::Read], Utc::now() + Duration::days(1), HashMap::new()).await.unwrap();
+
+        assert!(token.starts_with("ma_"));
+        let authenticated = api_token_service.authenticate(&token).await.unwrap().expect("Token should be valid");
+        assert_eq!(authenticated.id, api_token.id);
+        assert!(authenticated.has_scope(TokenScope::Read));
+        assert!(!authenticated.has_scope(TokenScope::Write));
+
+        assert!(matches!(api_token_service.revoke(2, api_token.id).await, Err(ApiTokenError::NotFound)));
+        api_token_service.revoke(1, api_token.id).await.unwrap();
+        assert!(api_token_service.authenticate(&token).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_not_authenticate_expired_token() {
+        let db_config = DbConfig::new("file:api_token_service_expired_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let api_token_service = ApiTokenService::new(Arc::new(db_config));
+
+        let (_, token) = api_token_service.create_token(1, "old", &[TokenScope::Read], Utc::now() - Duration::days(1), HashMap::new()).await.unwrap();
+
+        assert!(api_token_service.authenticate(&token).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::{types::Type, Connection, Row};
+
+use crate::{config::db::DbConfig, domain::{audit_event::{AuditEvent, AuditEventFilter, AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi}, error::errors::AuditEventError};
+
+const FILTER: &str = "WHERE (?1 IS NULL OR user_id = ?1) AND (?2 IS NULL OR event_type = ?2) AND (?3 IS NULL OR created_at >= ?3) AND (?4 IS NULL OR created_at < ?4)";
+
+pub struct AuditEventService {
+    db_config: Arc<DbConfig>
+}
+
+impl AuditEventService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    fn map_audit_event(row: &Row) -> Result<AuditEvent, rusqlite::Error> {
+        let event_type: String = row.get(3)?;
+        Ok(AuditEvent {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            email: row.get(2)?,
+            event_type: AuditEventType::parse(&event_type)
+                .ok_or_else(|| rusqlite::Error::InvalidColumnType(3, "event_type".to_owned(), Type::Text))?,
+            details: row.get(4)?,
+            ip: row.get(5)?,
+            user_agent: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+}
+
+#[async_trait]
+impl AuditEventApi for AuditEventService {
+    async fn record(&self, event: NewAuditEvent) -> Result<(), AuditEventError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute(
+                "INSERT INTO audit_events (user_id, email, event_type, details, ip, user_agent, created_at) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                (event.user_id, event.email, event.event_type.as_str(), event.details, event.ip, event.user_agent, Utc::now()))?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn find(&self, filter: &AuditEventFilter, offset: i64, limit: i64) -> Result<(Vec<AuditEvent>, i64), AuditEventError> {
+        let db = self.db_config.get_database().to_owned();
+        let filter = filter.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let params = (filter.user_id, filter.event_type.map(|event_type| event_type.as_str()), filter.from, filter.to);
+
+            let total: i64 = conn.query_row(&format!("SELECT count(*) FROM audit_events {}", FILTER), params, |row| row.get(0))?;
+            // the id grows with every event, so it orders by time
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, user_id, email, event_type, details, ip, user_agent, created_at FROM audit_events {} ORDER BY id DESC LIMIT ?5 OFFSET ?6", FILTER))?;
+            let events = stmt.query_map((params.0, params.1, params.2, params.3, limit, offset), AuditEventService::map_audit_event)?
+                .collect::<Result<Vec<AuditEvent>, rusqlite::Error>>()?;
+
+            Ok((events, total))
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod audit_event_service_tests {
+    use std::sync::Arc;
+
+    use rusqlite::Connection;
+
+    use crate::{config::db::DbConfig, create_db, domain::{audit_event::{AuditEventFilter, AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi}};
+
+    use super::AuditEventService;
+
+    #[tokio::test]
+    async fn should_find_events_by_filter() {
+        let db_config = DbConfig::new("file:audit_event_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let audit_event_service = AuditEventService::new(Arc::new(db_config));
+
+        audit_event_service.record(NewAuditEvent::for_user(1, AuditEventType::LoginSucceeded)).await.unwrap();
+        audit_event_service.record(NewAuditEvent::for_user(1, AuditEventType::PasswordChanged)).await.unwrap();
+        audit_event_service.record(NewAuditEvent::for_user(2, AuditEventType::LoginSucceeded).with_details("recovery code")).await.unwrap();
+
+        let (events, total) = audit_event_service.find(&AuditEventFilter { user_id: Some(1), ..AuditEventFilter::default() }, 0, 1).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(events[0].event_type, AuditEventType::PasswordChanged, "Newest event first");
+
+        let filter = AuditEventFilter { event_type: Some(AuditEventType::LoginSucceeded), ..AuditEventFilter::default() };
+        let (events, total) = audit_event_service.find(&filter, 0, 10).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(events[0].details.as_deref(), Some("recovery code"));
+    }
+
+    #[test]
+    fn should_reject_changes_of_events() {
+        let db_config = DbConfig::new("file:audit_event_service_append_only_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let conn = Connection::open(db_config.get_database()).unwrap();
+
+        conn.execute("INSERT INTO audit_events (user_id, event_type, created_at) values (1, 'login_failed', '2024-01-01 00:00:00+00:00')", []).unwrap();
+
+        assert!(conn.execute("UPDATE audit_events SET event_type = 'login_succeeded'", []).is_err());
+        assert!(conn.execute("DELETE FROM audit_events", []).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+use actix_web::{http::header, HttpRequest};
+
+use crate::domain::{audit_event::NewAuditEvent, audit_event_api::AuditEventApi};
+
+/// Records the event with ip and user agent of the request. A failure is logged and does not fail the request.
+pub async fn record_event(audit_event_api: &dyn AuditEventApi, req: &HttpRequest, mut event: NewAuditEvent) {
+    event.ip = req.peer_addr().map(|peer| peer.ip().to_string());
+    event.user_agent = req.headers().get(header::USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .map(str::to_owned);
+
+    let event_type = event.event_type;
+    if let Err(err) = audit_event_api.record(event).await {
+        log::error!("Cannot record audit event {}: {}", event_type.as_str(), err);
+    }
+}
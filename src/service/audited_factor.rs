@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use actix_web::HttpRequest;
+use async_trait::async_trait;
+use authfix::multifactor::{CheckCodeError, Factor, GenerateCodeError};
+
+use crate::{domain::{audit_event::{AuditEventType, NewAuditEvent}, audit_event_api::AuditEventApi}, service::{audit_log, email_otp_factor::EmailOtpFactor, recovery_code_service}};
+
+/// Records the result of the second login step of the wrapped factor in the audit log
+pub struct AuditedFactor {
+    inner: Box<dyn Factor>,
+    audit_event_api: Arc<dyn AuditEventApi>,
+}
+
+impl AuditedFactor {
+    pub fn new(inner: Box<dyn Factor>, audit_event_api: Arc<dyn AuditEventApi>) -> Self {
+        Self {
+            inner,
+            audit_event_api,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Factor for AuditedFactor {
+    async fn generate_code(&self, req: &HttpRequest) -> Result<(), GenerateCodeError> {
+        self.inner.generate_code(req).await
+    }
+
+    fn get_unique_id(&self) -> String {
+        self.inner.get_unique_id()
+    }
+
+    async fn check_code(&self, code: &str, req: &HttpRequest) -> Result<(), CheckCodeError> {
+        let user_id = EmailOtpFactor::pending_user_id(req);
+        let result = self.inner.check_code(code, req).await;
+
+        let event_type = match result {
+            Ok(_) => AuditEventType::LoginSucceeded,
+            Err(_) => AuditEventType::MfaFailed,
+        };
+        let factor = if recovery_code_service::is_recovery_code(code) { "recovery code".to_owned() } else { self.get_unique_id() };
+        let event = NewAuditEvent::new(user_id, event_type).with_details(&factor);
+        audit_log::record_event(self.audit_event_api.as_ref(), req, event).await;
+
+        result
+    }
+}
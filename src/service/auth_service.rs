@@ -3,8 +3,8 @@ use std::sync::Arc;
 use actix_web::HttpRequest;
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use async_trait::async_trait;
-use authfix::{login::LoadUserByCredentials, mfa::{HandleMfaRequest, MfaError}};
-use crate::{domain::{auth_api::AuthenticationApi, user::User, user_api::UserApi}, error::errors::QueryUserError};
+use authfix::{actix_session::SessionExt, login::LoadUserByCredentials, mfa::{HandleMfaRequest, MfaError}};
+use crate::{domain::{auth_api::AuthenticationApi, user::{self, User}, user_api::UserApi}, error::errors::QueryUserError, service::email_otp_factor::SESSION_KEY_MFA_USER_ID};
 
 pub struct AuthenticationService<U: UserApi> {
     user_api: Arc<U>
@@ -47,19 +47,27 @@ impl<U: UserApi> AuthenticationApi for AuthenticationService<U> {
 impl<U: UserApi> LoadUserByCredentials for AuthenticationService<U> {
     type User = User;
 
+    /// Failed logins are counted and locked out by `middleware::throttle_logins`
     async fn load_user(
         &self,
         login_token: &authfix::login::LoginToken,
     ) -> Result<Self::User, authfix::login::LoadUserError> {
-        let email = login_token.email.clone();
+        // registration stores the address normalized
+        let email = user::login_email(&login_token.email);
         let password = login_token.password.clone();
         
         match self.user_api.find_by_email(&email).await {
             Ok(user) => {
-                if self.is_password_correct(&user, &password).await {
-                    Ok(user)
-                } else {
+                if !self.is_password_correct(&user, &password).await {
+                    Err(authfix::login::LoadUserError::LoginFailed)
+                } else if !user.email_verified {
+                    log::info!("Login of user {} rejected, email address is not verified", user.id);
                     Err(authfix::login::LoadUserError::LoginFailed)
+                } else if user.disabled {
+                    log::info!("Login of user {} rejected, account is disabled", user.id);
+                    Err(authfix::login::LoadUserError::LoginFailed)
+                } else {
+                    Ok(user)
                 }
             },
             Err(_) => Err(authfix::login::LoadUserError::LoginFailed),
@@ -84,6 +92,7 @@ impl<S: UserApi> HandleMfaRequestImpl<S> {
 impl<S: UserApi> HandleMfaRequest for HandleMfaRequestImpl<S> {
     type User = User;
 
+    /// The preferred factor of the user, if mfa is configured
     async fn get_mfa_id_by_user(&self, user: &Self::User) -> Result<Option<String>, MfaError> {
         let creds = self.user_api.find_credentials_by_user_id(user.id).await?;
         if let Some(mfa_config) = creds.mfa_config {
@@ -93,11 +102,17 @@ impl<S: UserApi> HandleMfaRequest for HandleMfaRequestImpl<S> {
         }
     }
 
-    #[allow(unused)]
     async fn is_condition_met(&self, user: &Self::User, req: HttpRequest) -> bool {
         match self.user_api.find_credentials_by_user_id(user.id).await {
-            Ok(creds) => creds.mfa_config.is_some(),
-            Err(_) => false,
+            Ok(creds) if creds.mfa_config.is_some() => {
+                // factors that are not bound to a secret (e.g. email and recovery codes) need to know whose login is pending
+                if let Err(err) = req.get_session().insert(SESSION_KEY_MFA_USER_ID, user.id) {
+                    log::error!("Cannot store user of pending login: {}", err);
+                }
+
+                true
+            },
+            _ => false,
         }
     }
 }
@@ -113,7 +128,9 @@ impl From<QueryUserError> for MfaError {
 mod tests {
     use std::sync::Arc;
 
-    use crate::{config::db::DbConfig, create_db, domain::{auth_api::AuthenticationApi, user::User, user_api::UserApi}, service::user_service::UserService};
+    use authfix::login::{LoadUserByCredentials, LoginToken};
+
+    use crate::{config::db::DbConfig, create_db, domain::{auth_api::AuthenticationApi, user::User, user_api::UserApi}, service::{secret_cipher::SecretCipher, user_service::UserService}};
 
     use super::AuthenticationService;
 
@@ -123,7 +140,7 @@ mod tests {
         // Arrange
         let db_config = DbConfig::new(":memory");
         create_db(&db_config);
-        let user_service = Arc::new(UserService::new(Arc::new(db_config)));
+        let user_service = Arc::new(UserService::new(Arc::new(db_config), Arc::new(SecretCipher::new(&SecretCipher::generate_key()).unwrap())));
         let auth = AuthenticationService::new(Arc::clone(&user_service));
         let user = User::new(0, "test@example.org".to_owned(), "Hans".to_owned());
         let saved_user = user_service.save_user_with_credentials(user, "test123").await.unwrap();
@@ -136,7 +153,7 @@ mod tests {
     async fn should_return_false_when_password_incorrect() {
         let db_config = DbConfig::new(":memory");
         create_db(&db_config);
-        let user_service = Arc::new(UserService::new(Arc::new(db_config)));
+        let user_service = Arc::new(UserService::new(Arc::new(db_config), Arc::new(SecretCipher::new(&SecretCipher::generate_key()).unwrap())));
         let auth = AuthenticationService::new(Arc::clone(&user_service));
         let user = User::new(0, "test@example.org".to_owned(), "Hans".to_owned());
         let saved_user = user_service.save_user_with_credentials(user, "test123").await.unwrap();
@@ -144,4 +161,36 @@ mod tests {
         assert!(!auth.is_password_correct(&saved_user, "some123").await, "Password is not correct. This should return false");
     }
 
+    #[tokio::test]
+    async fn should_load_user_by_email_in_any_case() {
+        let db_config = DbConfig::new("file:auth_service_login_email_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = Arc::new(UserService::new(Arc::new(db_config), Arc::new(SecretCipher::new(&SecretCipher::generate_key()).unwrap())));
+        let auth = AuthenticationService::new(Arc::clone(&user_service));
+        let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        let saved_user = user_service.save_user_with_credentials(user, "linda123").await.unwrap();
+        user_service.mark_email_verified(saved_user.id).await.unwrap();
+
+        let login_token: LoginToken = serde_json::from_str(r#"{"email": " Linda@Example.org ", "password": "linda123"}"#).unwrap();
+        let loaded = auth.load_user(&login_token).await.unwrap_or_else(|_| panic!("Login should succeed"));
+
+        assert_eq!(loaded.id, saved_user.id);
+    }
+
+    #[tokio::test]
+    async fn should_reject_disabled_user() {
+        let db_config = DbConfig::new("file:auth_service_disabled_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = Arc::new(UserService::new(Arc::new(db_config), Arc::new(SecretCipher::new(&SecretCipher::generate_key()).unwrap())));
+        let auth = AuthenticationService::new(Arc::clone(&user_service));
+        let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        let saved_user = user_service.save_user_with_credentials(user, "linda123").await.unwrap();
+        user_service.mark_email_verified(saved_user.id).await.unwrap();
+        user_service.set_disabled(saved_user.id, true).await.unwrap();
+
+        let login_token: LoginToken = serde_json::from_str(r#"{"email": "linda@example.org", "password": "linda123"}"#).unwrap();
+
+        assert!(auth.load_user(&login_token).await.is_err(), "A disabled user must not log in");
+    }
+
 }
\ No newline at end of file
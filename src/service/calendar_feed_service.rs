@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{config::db::DbConfig, domain::calendar_feed_api::CalendarFeedApi, error::errors::CalendarFeedError, service::secure_token};
+
+pub struct CalendarFeedService {
+    db_config: Arc<DbConfig>
+}
+
+impl CalendarFeedService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+}
+
+#[async_trait]
+impl CalendarFeedApi for CalendarFeedService {
+    async fn create_feed_token(&self, user_id: i32) -> Result<String, CalendarFeedError> {
+        let db = self.db_config.get_database().to_owned();
+        let token = secure_token::generate_token();
+        let token_hash = secure_token::hash_token(&token);
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("INSERT OR REPLACE INTO calendar_feeds (user_id, token_hash, created_at) values (?1, ?2, ?3)", (user_id, token_hash, Utc::now()))?;
+
+            Ok::<(), CalendarFeedError>(())
+        }).await??;
+
+        Ok(token)
+    }
+
+    async fn revoke_feed_token(&self, user_id: i32) -> Result<(), CalendarFeedError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("DELETE FROM calendar_feeds WHERE user_id = ?1", [user_id])?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn has_feed_token(&self, user_id: i32) -> Result<bool, CalendarFeedError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let count: i32 = conn.query_row("SELECT COUNT(*) FROM calendar_feeds WHERE user_id = ?1", [user_id], |row| row.get(0))?;
+
+            Ok(count > 0)
+        }).await?
+    }
+
+    async fn find_user_id_by_token(&self, token: &str) -> Result<Option<i32>, CalendarFeedError> {
+        let db = self.db_config.get_database().to_owned();
+        let token_hash = secure_token::hash_token(token);
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            Ok(conn.query_row("SELECT user_id FROM calendar_feeds WHERE token_hash = ?1", [token_hash], |row| row.get(0))
+                .optional()?)
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod calendar_feed_service_tests {
+    use std::sync::Arc;
+
+    use crate::{config::db::DbConfig, create_db, domain::calendar_feed_api::CalendarFeedApi};
+
+    use super::CalendarFeedService;
+
+    #[tokio::test]
+    async fn should_resolve_token_until_rotated_or_revoked() {
+        let db_config = DbConfig::new("file:calendar_feed_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let feed_service = CalendarFeedService::new(Arc::new(db_config));
+
+        let first = feed_service.create_feed_token(1).await.unwrap();
+        assert_eq!(feed_service.find_user_id_by_token(&first).await.unwrap(), Some(1));
+
+        let second = feed_service.create_feed_token(1).await.unwrap();
+        assert_eq!(feed_service.find_user_id_by_token(&first).await.unwrap(), None);
+        assert_eq!(feed_service.find_user_id_by_token(&second).await.unwrap(), Some(1));
+
+        feed_service.revoke_feed_token(1).await.unwrap();
+        assert_eq!(feed_service.find_user_id_by_token(&second).await.unwrap(), None);
+        assert!(!feed_service.has_feed_token(1).await.unwrap());
+    }
+}
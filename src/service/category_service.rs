@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rusqlite::{Connection, Row};
+
+use crate::{config::db::DbConfig, domain::{category::Category, category_api::CategoryApi}, error::errors::{CategoryUpdateError, QueryCategoryError}};
+
+const SELECT_CATEGORY: &str = "SELECT id, name, color, icon, user_id FROM categories";
+
+pub struct CategoryService {
+    db_config: Arc<DbConfig>
+}
+
+impl CategoryService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    fn map_row(row: &Row) -> Result<Category, rusqlite::Error> {
+        Ok(Category {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            color: row.get(2)?,
+            icon: row.get(3)?,
+            user_id: row.get(4)?,
+        })
+    }
+}
+
+#[async_trait]
+impl CategoryApi for CategoryService {
+    async fn find_all_by_user_id(&self, user_id: i32) -> Result<Vec<Category>, QueryCategoryError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let mut stmt = conn.prepare(&format!("{} WHERE user_id = ?1 ORDER BY name", SELECT_CATEGORY))?;
+            let categories = stmt.query_map([user_id], CategoryService::map_row)?
+                .collect::<Result<Vec<Category>, rusqlite::Error>>()?;
+
+            Ok(categories)
+        }).await?
+    }
+
+    async fn find_by_id(&self, category_id: i32, user_id: i32) -> Result<Category, QueryCategoryError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            Ok(conn.query_row(&format!("{} WHERE id = ?1 AND user_id = ?2", SELECT_CATEGORY), [category_id, user_id], CategoryService::map_row)?)
+        }).await?
+    }
+
+    async fn save_category(&self, category: Category) -> Result<Category, CategoryUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        let user_id = category.user_id;
+
+        let category_id = tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            if category.id > 0 {
+                let changed = conn.execute("UPDATE categories SET name = ?1, color = ?2, icon = ?3 WHERE id = ?4 AND user_id = ?5",
+                    (category.name, category.color, category.icon, category.id, category.user_id))?;
+
+                if changed == 0 {
+                    return Err(CategoryUpdateError::NotFound);
+                }
+
+                Ok(category.id)
+            } else {
+                conn.execute("INSERT INTO categories (name, color, icon, user_id) values (?1, ?2, ?3, ?4)",
+                    (category.name, category.color, category.icon, category.user_id))?;
+
+                Ok(conn.last_insert_rowid() as i32)
+            }
+        }).await??;
+
+        self.find_by_id(category_id, user_id)
+            .await
+            .map_err(|e| CategoryUpdateError::Update(format!("Unable to retrieve category after save: {}", e)))
+    }
+
+    async fn delete_category(&self, category_id: i32, user_id: i32) -> Result<(), CategoryUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let deleted = tx.execute("DELETE FROM categories WHERE id = ?1 AND user_id = ?2", [category_id, user_id])?;
+            if deleted == 0 {
+                return Err(CategoryUpdateError::NotFound);
+            }
+
+            tx.execute("UPDATE activities SET category_id = NULL WHERE category_id = ?1", [category_id])?;
+            tx.execute("DELETE FROM goals WHERE category_id = ?1", [category_id])?;
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod category_service_tests {
+    use std::sync::Arc;
+
+    use crate::{config::db::DbConfig, create_db, domain::{category::Category, category_api::CategoryApi}, error::errors::{CategoryUpdateError, QueryCategoryError}};
+
+    use super::CategoryService;
+
+    #[tokio::test]
+    async fn should_reject_duplicate_category_name_of_same_user() {
+        let db_config = DbConfig::new("file:category_service_duplicate_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let category_service = CategoryService::new(Arc::new(db_config));
+
+        category_service.save_category(Category::new(0, "Sport".to_owned(), 1)).await.unwrap();
+
+        assert!(matches!(category_service.save_category(Category::new(0, "Sport".to_owned(), 1)).await, Err(CategoryUpdateError::Duplicate)));
+        assert!(category_service.save_category(Category::new(0, "Sport".to_owned(), 2)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_not_expose_category_of_other_user() {
+        let db_config = DbConfig::new("file:category_service_owner_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let category_service = CategoryService::new(Arc::new(db_config));
+
+        let mut category = Category::new(0, "Music".to_owned(), 1);
+        category.color = Some("#ff0000".to_owned());
+        let saved = category_service.save_category(category).await.unwrap();
+
+        assert_eq!(saved.color.unwrap(), "#ff0000");
+        assert!(matches!(category_service.find_by_id(saved.id, 2).await, Err(QueryCategoryError::NotFound)));
+        assert!(matches!(category_service.delete_category(saved.id, 2).await, Err(CategoryUpdateError::NotFound)));
+    }
+}
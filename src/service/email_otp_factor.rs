@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use actix_web::HttpRequest;
+use async_trait::async_trait;
+use authfix::{actix_session::SessionExt, multifactor::{CheckCodeError, Factor, GenerateCodeError}};
+
+use crate::{domain::{email_otp_api::EmailOtpApi, mail::Mail, mailer_api::Mailer, user_api::UserApi}, service::email_otp_service::CODE_VALIDITY_MINUTES};
+
+pub const MFA_ID_EMAIL_OTP: &str = "EMAIL_OTP";
+/// Id of the user whose login waits for the second factor. Set by `HandleMfaRequestImpl`.
+pub const SESSION_KEY_MFA_USER_ID: &str = "mfa_user_id";
+
+/// Second factor with a one-time code sent by mail
+pub struct EmailOtpFactor {
+    user_api: Arc<dyn UserApi>,
+    email_otp_api: Arc<dyn EmailOtpApi>,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl EmailOtpFactor {
+    pub fn new(user_api: Arc<dyn UserApi>, email_otp_api: Arc<dyn EmailOtpApi>, mailer: Arc<dyn Mailer>) -> Self {
+        Self {
+            user_api,
+            email_otp_api,
+            mailer,
+        }
+    }
+
+    pub fn pending_user_id(req: &HttpRequest) -> Option<i32> {
+        req.get_session().get::<i32>(SESSION_KEY_MFA_USER_ID).ok().flatten()
+    }
+}
+
+#[async_trait(?Send)]
+impl Factor for EmailOtpFactor {
+    async fn generate_code(&self, req: &HttpRequest) -> Result<(), GenerateCodeError> {
+        let user_id = EmailOtpFactor::pending_user_id(req)
+            .ok_or_else(|| GenerateCodeError::new("No login in progress"))?;
+
+        let user = self.user_api.find_by_id(user_id).await
+            .map_err(|e| GenerateCodeError::new(&format!("Cannot load user: {}", e)))?;
+        let code = self.email_otp_api.create_code(user_id).await
+            .map_err(|e| GenerateCodeError::new(&e.to_string()))?;
+
+        let body = format!(
+            "Hello {},\n\nyour login code is:\n\n{}\n\nThe code is valid for {} minutes. If you did not try to log in, please change your password.",
+            user.name, code, CODE_VALIDITY_MINUTES);
+
+        self.mailer.send(Mail::new(&user.email, "Your login code", body)).await
+            .map_err(|e| GenerateCodeError::new(&e.to_string()))
+    }
+
+    fn get_unique_id(&self) -> String {
+        MFA_ID_EMAIL_OTP.to_owned()
+    }
+
+    async fn check_code(&self, code: &str, req: &HttpRequest) -> Result<(), CheckCodeError> {
+        let user_id = EmailOtpFactor::pending_user_id(req)
+            .ok_or_else(|| CheckCodeError::new("No login in progress"))?;
+
+        match self.email_otp_api.verify_code(user_id, code).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(CheckCodeError::new("Invalid code")),
+            Err(err) => {
+                log::error!("Cannot verify email code: {}", err);
+                Err(CheckCodeError::new("Cannot verify code"))
+            },
+        }
+    }
+}
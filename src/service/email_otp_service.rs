@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use rusqlite::Connection;
+
+use crate::{config::db::DbConfig, domain::email_otp_api::EmailOtpApi, error::errors::EmailOtpError, service::secure_token};
+
+pub const CODE_VALIDITY_MINUTES: i64 = 10;
+/// Wrong codes until no code is accepted anymore. New codes keep the count until the previous code expired.
+const MAX_ATTEMPTS: i32 = 5;
+/// Minimum time between two codes of a user
+const MIN_CODE_INTERVAL_SECONDS: i64 = 30;
+
+pub struct EmailOtpService {
+    db_config: Arc<DbConfig>
+}
+
+impl EmailOtpService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    /// The code space is small, so the hash includes the user id
+    fn hash_code(user_id: i32, code: &str) -> String {
+        secure_token::hash_token(&format!("{}:{}", user_id, code.trim()))
+    }
+}
+
+#[async_trait]
+impl EmailOtpApi for EmailOtpService {
+    async fn create_code(&self, user_id: i32) -> Result<String, EmailOtpError> {
+        let db = self.db_config.get_database().to_owned();
+        let code = format!("{:06}", OsRng.next_u32() % 1_000_000);
+        let code_hash = EmailOtpService::hash_code(user_id, &code);
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(CODE_VALIDITY_MINUTES);
+        // a previous code that expires before this was created at least the minimum interval ago
+        let replaceable_until = expires_at - Duration::seconds(MIN_CODE_INTERVAL_SECONDS);
+
+        let changed = tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let changed = conn.execute(
+                "INSERT INTO email_otps (user_id, code_hash, expires_at, attempts) values (?1, ?2, ?3, 0) \
+                 ON CONFLICT (user_id) DO UPDATE SET code_hash = excluded.code_hash, expires_at = excluded.expires_at, \
+                 attempts = CASE WHEN email_otps.expires_at > ?4 THEN email_otps.attempts ELSE 0 END \
+                 WHERE email_otps.expires_at <= ?5",
+                (user_id, code_hash, expires_at, now, replaceable_until))?;
+
+            Ok::<usize, EmailOtpError>(changed)
+        }).await??;
+
+        if changed == 0 {
+            return Err(EmailOtpError::new("A code was requested recently, try again later"));
+        }
+
+        Ok(code)
+    }
+
+    async fn verify_code(&self, user_id: i32, code: &str) -> Result<bool, EmailOtpError> {
+        let db = self.db_config.get_database().to_owned();
+        let code_hash = EmailOtpService::hash_code(user_id, code);
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            // the attempt is counted before the code is compared, so parallel guesses cannot exceed the limit
+            let counted = conn.execute(
+                "UPDATE email_otps SET attempts = attempts + 1 WHERE user_id = ?1 AND attempts < ?2 AND expires_at > ?3",
+                (user_id, MAX_ATTEMPTS, Utc::now()))?;
+            if counted == 0 {
+                return Ok(false);
+            }
+
+            let matched = conn.execute("DELETE FROM email_otps WHERE user_id = ?1 AND code_hash = ?2", (user_id, code_hash))?;
+
+            Ok(matched > 0)
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod email_otp_service_tests {
+    use std::sync::Arc;
+
+    use crate::{config::db::DbConfig, create_db, domain::email_otp_api::EmailOtpApi};
+
+    use super::EmailOtpService;
+
+    #[tokio::test]
+    async fn should_accept_code_once() {
+        let db_config = DbConfig::new("file:email_otp_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let otp_service = EmailOtpService::new(Arc::new(db_config));
+
+        let code = otp_service.create_code(1).await.unwrap();
+
+        assert_eq!(code.len(), 6);
+        assert!(!otp_service.verify_code(2, &code).await.unwrap());
+        assert!(otp_service.verify_code(1, &code).await.unwrap());
+        assert!(!otp_service.verify_code(1, &code).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_reject_code_after_too_many_attempts() {
+        let db_config = DbConfig::new("file:email_otp_service_attempt_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let otp_service = EmailOtpService::new(Arc::new(db_config));
+
+        let code = otp_service.create_code(1).await.unwrap();
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+        for _ in 0..5 {
+            assert!(!otp_service.verify_code(1, wrong).await.unwrap());
+        }
+
+        assert!(!otp_service.verify_code(1, &code).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_reject_new_code_in_quick_succession() {
+        let db_config = DbConfig::new("file:email_otp_service_interval_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let otp_service = EmailOtpService::new(Arc::new(db_config));
+
+        let code = otp_service.create_code(1).await.unwrap();
+
+        assert!(otp_service.create_code(1).await.is_err());
+        assert!(otp_service.verify_code(1, &code).await.unwrap(), "The previous code stays valid");
+    }
+}
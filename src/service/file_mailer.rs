@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::Utc;
+
+use crate::{domain::{mail::Mail, mailer_api::Mailer}, error::errors::MailError};
+
+/// Writes every mail to a file in `directory` instead of sending it. For local development and tests.
+pub struct FileMailer {
+    directory: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(directory: &str) -> Self {
+        Self {
+            directory: PathBuf::from(directory),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        tokio::fs::create_dir_all(&self.directory).await
+            .map_err(|e| MailError::new(&format!("Cannot create mail directory: {}", e)))?;
+
+        let recipient: String = mail.to.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '@' { c } else { '_' })
+            .collect();
+        let path = self.directory.join(format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S%.6f"), recipient));
+
+        let content = format!("To: {}\r\nSubject: {}\r\n\r\n{}\r\n", mail.to, mail.subject, mail.body);
+        tokio::fs::write(&path, content).await
+            .map_err(|e| MailError::new(&format!("Cannot write mail: {}", e)))?;
+
+        log::info!("Mail to {} written to {}", mail.to, path.display());
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{mail::Mail, mailer_api::Mailer};
+
+    use super::FileMailer;
+
+    #[tokio::test]
+    async fn should_write_mail_to_directory() {
+        let directory = std::env::temp_dir().join(format!("file_mailer_test_{}", std::process::id()));
+        let mailer = FileMailer::new(directory.to_str().unwrap());
+
+        mailer.send(Mail::new("linda@example.org", "Hello", "Hello Linda".to_owned())).await.unwrap();
+
+        let mut entries = std::fs::read_dir(&directory).unwrap();
+        let content = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(content.contains("To: linda@example.org"));
+        assert!(content.contains("Hello Linda"));
+    }
+}
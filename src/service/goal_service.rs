@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use rusqlite::{types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef}, Connection, Row, ToSql};
+
+use crate::{config::db::DbConfig, domain::{goal::{Goal, GoalMetric, GoalPeriod, GoalWithProgress, TrackedTime}, goal_api::GoalApi}, error::errors::{GoalUpdateError, QueryGoalError}};
+
+const SELECT_GOAL: &str = "SELECT id, title, category_id, metric, target, period, user_id FROM goals";
+
+impl ToSql for GoalMetric {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value = match self {
+            GoalMetric::Duration => "duration",
+            GoalMetric::Count => "count",
+        };
+        Ok(ToSqlOutput::from(value))
+    }
+}
+
+impl FromSql for GoalMetric {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "duration" => Ok(GoalMetric::Duration),
+            "count" => Ok(GoalMetric::Count),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for GoalPeriod {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value = match self {
+            GoalPeriod::Day => "day",
+            GoalPeriod::Week => "week",
+            GoalPeriod::Month => "month",
+        };
+        Ok(ToSqlOutput::from(value))
+    }
+}
+
+impl FromSql for GoalPeriod {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "day" => Ok(GoalPeriod::Day),
+            "week" => Ok(GoalPeriod::Week),
+            "month" => Ok(GoalPeriod::Month),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+pub struct GoalService {
+    db_config: Arc<DbConfig>
+}
+
+impl GoalService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    fn map_row(row: &Row) -> Result<Goal, rusqlite::Error> {
+        Ok(Goal {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            category_id: row.get(2)?,
+            metric: row.get(3)?,
+            target: row.get(4)?,
+            period: row.get(5)?,
+            user_id: row.get(6)?,
+        })
+    }
+
+    /// Tracked times of all sessions of activities in the category. Running sessions count until now.
+    fn find_tracked_times(conn: &Connection, category_id: i32, user_id: i32) -> Result<Vec<TrackedTime>, rusqlite::Error> {
+        let query = r#"
+            SELECT activity_sessions.start_time, activity_sessions.end_time
+            FROM activity_sessions
+            JOIN activities ON activities.id = activity_sessions.activity_id
+            WHERE activity_sessions.user_id = ?1 AND activities.category_id = ?2
+        "#;
+        let now = Utc::now();
+        let mut stmt = conn.prepare(query)?;
+        let tracked = stmt.query_map([user_id, category_id], |row| {
+            Ok(TrackedTime {
+                start: row.get(0)?,
+                end: row.get::<_, Option<_>>(1)?.unwrap_or(now),
+            })
+        })?
+        .collect::<Result<Vec<TrackedTime>, rusqlite::Error>>()?;
+
+        Ok(tracked)
+    }
+}
+
+#[async_trait]
+impl GoalApi for GoalService {
+    async fn find_all_by_user_id(&self, user_id: i32) -> Result<Vec<Goal>, QueryGoalError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let mut stmt = conn.prepare(&format!("{} WHERE user_id = ?1 ORDER BY title", SELECT_GOAL))?;
+            let goals = stmt.query_map([user_id], GoalService::map_row)?
+                .collect::<Result<Vec<Goal>, rusqlite::Error>>()?;
+
+            Ok(goals)
+        }).await?
+    }
+
+    async fn find_progress_by_user_id(&self, user_id: i32, today: NaiveDate, timezone: Tz) -> Result<Vec<GoalWithProgress>, QueryGoalError> {
+        let goals = self.find_all_by_user_id(user_id).await?;
+
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            goals.into_iter()
+                .map(|goal| {
+                    let tracked = GoalService::find_tracked_times(&conn, goal.category_id, user_id)?;
+                    let progress = goal.progress(&tracked, today, timezone);
+                    Ok::<GoalWithProgress, QueryGoalError>(GoalWithProgress { goal, progress })
+                })
+                .collect()
+        }).await?
+    }
+
+    async fn save_goal(&self, goal: Goal) -> Result<Goal, GoalUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let count: i32 = conn.query_row("SELECT COUNT(*) FROM categories WHERE id = ?1 AND user_id = ?2", [goal.category_id, goal.user_id], |row| row.get(0))?;
+            if count == 0 {
+                return Err(GoalUpdateError::InvalidReference(format!("Unknown category: {}", goal.category_id)));
+            }
+
+            let goal_id = if goal.id > 0 {
+                let changed = conn.execute("UPDATE goals SET title = ?1, category_id = ?2, metric = ?3, target = ?4, period = ?5 WHERE id = ?6 AND user_id = ?7",
+                    (&goal.title, goal.category_id, goal.metric, goal.target, goal.period, goal.id, goal.user_id))?;
+                if changed == 0 {
+                    return Err(GoalUpdateError::NotFound);
+                }
+
+                goal.id
+            } else {
+                conn.execute("INSERT INTO goals (title, category_id, metric, target, period, user_id) values (?1, ?2, ?3, ?4, ?5, ?6)",
+                    (&goal.title, goal.category_id, goal.metric, goal.target, goal.period, goal.user_id))?;
+                conn.last_insert_rowid() as i32
+            };
+
+            Ok(Goal { id: goal_id, ..goal })
+        }).await?
+    }
+
+    async fn delete_goal(&self, goal_id: i32, user_id: i32) -> Result<(), GoalUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let deleted = conn.execute("DELETE FROM goals WHERE id = ?1 AND user_id = ?2", [goal_id, user_id])?;
+            if deleted == 0 {
+                Err(GoalUpdateError::NotFound)
+            } else {
+                Ok(())
+            }
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod goal_service_tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use chrono_tz::Tz;
+
+    use crate::{config::db::DbConfig, create_db, domain::{activity::Activity, activity_api::ActivityApi, activity_session_api::ActivitySessionApi, category::Category, category_api::CategoryApi, goal::{Goal, GoalMetric, GoalPeriod}, goal_api::GoalApi}, error::errors::GoalUpdateError, service::{activity_service::ActivityService, activity_session_service::ActivitySessionService, category_service::CategoryService}};
+
+    use super::GoalService;
+
+    #[tokio::test]
+    async fn should_compute_progress_from_sessions_of_category() {
+        let db_config = Arc::new(DbConfig::new("file:goal_service_progress_test?mode=memory&cache=shared"));
+        let _db = create_db(&db_config);
+        let category_service = CategoryService::new(Arc::clone(&db_config));
+        let activity_service = ActivityService::new(Arc::clone(&db_config));
+        let session_service = ActivitySessionService::new(Arc::clone(&db_config));
+        let goal_service = GoalService::new(Arc::clone(&db_config));
+
+        let sport = category_service.save_category(Category::new(0, "Sport".to_owned(), 1)).await.unwrap();
+        let mut running = Activity::new(0, "Running".to_owned(), Utc::now(), 1);
+        running.category_id = Some(sport.id);
+        let running = activity_service.save_activity(running).await.unwrap();
+        session_service.start_timer(running.id, 1).await.unwrap();
+        session_service.stop_timer(running.id, 1).await.unwrap();
+
+        let goal = Goal { id: 0, title: "Run twice a week".to_owned(), category_id: sport.id, metric: GoalMetric::Count, target: 2, period: GoalPeriod::Week, user_id: 1 };
+        goal_service.save_goal(goal).await.unwrap();
+
+        let goals = goal_service.find_progress_by_user_id(1, Utc::now().date_naive(), Tz::UTC).await.unwrap();
+
+        assert_eq!(goals.len(), 1);
+        assert_eq!(goals[0].progress.value, 1);
+        assert!(!goals[0].progress.achieved);
+    }
+
+    #[tokio::test]
+    async fn should_reject_goal_for_category_of_other_user() {
+        let db_config = Arc::new(DbConfig::new("file:goal_service_owner_test?mode=memory&cache=shared"));
+        let _db = create_db(&db_config);
+        let category_service = CategoryService::new(Arc::clone(&db_config));
+        let goal_service = GoalService::new(Arc::clone(&db_config));
+
+        let foreign = category_service.save_category(Category::new(0, "Sport".to_owned(), 2)).await.unwrap();
+        let goal = Goal { id: 0, title: "Goal".to_owned(), category_id: foreign.id, metric: GoalMetric::Duration, target: 60, period: GoalPeriod::Day, user_id: 1 };
+
+        assert!(matches!(goal_service.save_goal(goal).await, Err(GoalUpdateError::InvalidReference(_))));
+    }
+}
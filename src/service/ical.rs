@@ -0,0 +1,419 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::{domain::{activity::{Activity, SkippedImport}, recurrence::RecurrenceRule}, service::{local_time::resolve_local, secure_token}};
+
+const PRODID: &str = "-//MyActivities//MyActivities//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+fn format_date_time(date: &DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes TEXT values (RFC 5545, 3.3.11)
+fn escape_text(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds content lines longer than 75 octets (RFC 5545, 3.1) and terminates them with CRLF
+fn push_line(ics: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            ics.push_str("\r\n ");
+            // the leading space counts towards the line length
+            octets = 1;
+        }
+        ics.push(c);
+        octets += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// UID of the VEVENT of an activity. Imported activities keep their original UID.
+pub fn activity_uid(activity: &Activity) -> String {
+    match &activity.ical_uid {
+        Some(uid) => uid.clone(),
+        None => format!("activity-{}@myactivities", activity.id),
+    }
+}
+
+/// Writes the activities as RFC 5545 VCALENDAR
+pub fn write_calendar(activities: &[Activity], now: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:{}", PRODID));
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+
+    for activity in activities {
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", activity_uid(activity)));
+        push_line(&mut ics, &format!("DTSTAMP:{}", format_date_time(&now)));
+        push_line(&mut ics, &format!("DTSTART:{}", format_date_time(&activity.start)));
+        if let Some(end) = &activity.end {
+            push_line(&mut ics, &format!("DTEND:{}", format_date_time(end)));
+        }
+        push_line(&mut ics, &format!("SUMMARY:{}", escape_text(&activity.title)));
+        if let Some(description) = &activity.description {
+            push_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        if let Some(recurrence) = &activity.recurrence {
+            push_line(&mut ics, &format!("RRULE:{}", recurrence));
+            for exception in &recurrence.exceptions {
+                push_line(&mut ics, &format!("EXDATE:{}", format_date_time(exception)));
+            }
+        }
+        push_line(&mut ics, "END:VEVENT");
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+
+/// A content line `NAME;PARAM=value:VALUE`
+struct ContentLine {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl ContentLine {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits the document into lines and joins folded lines
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(previous) = lines.last_mut() {
+                previous.push_str(&line[1..]);
+                continue;
+            }
+        }
+
+        if !line.is_empty() {
+            lines.push(line.to_owned());
+        }
+    }
+    lines
+}
+
+fn parse_content_line(line: &str) -> Option<ContentLine> {
+    // parameter values may contain colons if they are quoted
+    let mut in_quotes = false;
+    let mut value_start = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => {
+                value_start = Some(i);
+                break;
+            },
+            _ => {},
+        }
+    }
+
+    let value_start = value_start?;
+    let mut parts = line[..value_start].split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_ascii_uppercase(), value.trim_matches('"').to_owned()))
+        .collect();
+
+    Some(ContentLine { name, params, value: line[value_start + 1..].to_owned() })
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => result.push('\n'),
+                Some(escaped) => result.push(escaped),
+                None => result.push(c),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Parses a DATE or DATE-TIME value. Floating times and dates are interpreted in `TZID` or in the default time zone.
+fn parse_date_time(line: &ContentLine, value: &str, default_timezone: Tz) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(|date_time| date_time.and_utc())
+            .map_err(|_| format!("Invalid date: {}", value));
+    }
+
+    let timezone = line.param("TZID")
+        .and_then(|tzid| tzid.parse::<Tz>().ok())
+        .unwrap_or(default_timezone);
+
+    let date_time = match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Ok(date_time) => date_time,
+        Err(_) => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map_err(|_| format!("Invalid date: {}", value))?
+            .and_hms_opt(0, 0, 0)
+            .unwrap(),
+    };
+
+    Ok(resolve_local(date_time, timezone))
+}
+
+/// Parses a DURATION value like `PT1H30M` or `P1D`
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", value);
+
+    let (negative, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.trim().trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+
+                let part = match (unit, in_time) {
+                    ('W', false) => Duration::try_weeks(n),
+                    ('D', false) => Duration::try_days(n),
+                    ('H', true) => Duration::try_hours(n),
+                    ('M', true) => Duration::try_minutes(n),
+                    ('S', true) => Duration::try_seconds(n),
+                    _ => return Err(invalid()),
+                };
+                total = part.and_then(|part| total.checked_add(&part)).ok_or_else(invalid)?;
+            },
+        }
+    }
+
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(if negative { -total } else { total })
+}
+
+fn event_to_activity(event: &[ContentLine], user_id: i32, default_timezone: Tz) -> Result<Activity, SkippedImport> {
+    let find = |name: &str| event.iter().find(|line| line.name == name);
+
+    let summary = find("SUMMARY").map(|line| unescape_text(&line.value));
+    let dtstart = find("DTSTART");
+    let uid = match find("UID") {
+        Some(line) => line.value.trim().to_owned(),
+        // derive a stable UID, so that a re-import is still detected
+        None => {
+            let seed = format!("{}|{}", dtstart.map(|line| line.value.as_str()).unwrap_or(""), summary.as_deref().unwrap_or(""));
+            format!("generated-{}", &secure_token::hash_token(&seed)[..32])
+        },
+    };
+    let skip = |reason: String| SkippedImport { uid: uid.clone(), reason };
+
+    if find("RECURRENCE-ID").is_some() {
+        return Err(skip("Modified occurrences of a series are not supported".to_owned()));
+    }
+
+    let dtstart = dtstart.ok_or_else(|| skip("DTSTART is missing".to_owned()))?;
+    let start = parse_date_time(dtstart, &dtstart.value, default_timezone).map_err(&skip)?;
+    let is_all_day = dtstart.param("VALUE") == Some("DATE") || dtstart.value.trim().len() == 8;
+
+    let end = match (find("DTEND"), find("DURATION")) {
+        (Some(dtend), _) => Some(parse_date_time(dtend, &dtend.value, default_timezone).map_err(&skip)?),
+        (None, Some(duration)) => {
+            let end = parse_duration(&duration.value).ok()
+                .and_then(|duration| start.checked_add_signed(duration))
+                .ok_or_else(|| skip("Invalid duration".to_owned()))?;
+            Some(end)
+        },
+        // an all-day event without end lasts one day
+        (None, None) if is_all_day => Some(start.checked_add_signed(Duration::days(1)).ok_or_else(|| skip("Invalid DTSTART".to_owned()))?),
+        (None, None) => None,
+    };
+
+    if end.is_some_and(|end| end < start) {
+        return Err(skip("DTEND is before DTSTART".to_owned()));
+    }
+
+    let recurrence = match find("RRULE") {
+        Some(rrule) => {
+            let mut rule: RecurrenceRule = rrule.value.parse()
+                .map_err(|e| skip(format!("Unsupported RRULE: {}", e)))?;
+
+            for exdate in event.iter().filter(|line| line.name == "EXDATE") {
+                for value in exdate.value.split(',') {
+                    rule.exceptions.push(parse_date_time(exdate, value, default_timezone).map_err(&skip)?);
+                }
+            }
+
+            Some(rule)
+        },
+        None => None,
+    };
+
+    let title = summary
+        .filter(|summary| !summary.trim().is_empty())
+        .unwrap_or_else(|| "(No title)".to_owned());
+
+    let mut activity = Activity::new(0, title, start, user_id);
+    activity.description = find("DESCRIPTION").map(|line| unescape_text(&line.value));
+    activity.end = end;
+    activity.recurrence = recurrence;
+    activity.ical_uid = Some(uid.clone());
+
+    Ok(activity)
+}
+
+/// Parses all VEVENTs of a VCALENDAR. Events that cannot be imported are returned as `Err` with the reason.
+pub fn parse_calendar(ics: &str, user_id: i32, default_timezone: Tz) -> Result<Vec<Result<Activity, SkippedImport>>, String> {
+    let lines = unfold(ics);
+    if !lines.first().is_some_and(|line| line.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR")) {
+        return Err("Not an iCalendar document".to_owned());
+    }
+
+    let mut events = Vec::new();
+    let mut components: Vec<String> = Vec::new();
+    let mut current: Option<Vec<ContentLine>> = None;
+
+    for line in lines.iter().filter_map(|line| parse_content_line(line)) {
+        match line.name.as_str() {
+            "BEGIN" => {
+                let component = line.value.trim().to_ascii_uppercase();
+                if component == "VEVENT" {
+                    current = Some(Vec::new());
+                }
+                components.push(component);
+            },
+            "END" => {
+                let component = line.value.trim().to_ascii_uppercase();
+                components.pop();
+                if component == "VEVENT" {
+                    if let Some(event) = current.take() {
+                        events.push(event_to_activity(&event, user_id, default_timezone));
+                    }
+                }
+            },
+            // properties of nested components (e.g. VALARM) are ignored
+            _ if components.last().map(String::as_str) == Some("VEVENT") => {
+                if let Some(event) = current.as_mut() {
+                    event.push(line);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Ok(events)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc, Weekday};
+
+    use crate::domain::{activity::Activity, recurrence::{Frequency, RecurrenceRule}};
+
+    use super::{parse_calendar, write_calendar};
+
+    #[test]
+    fn should_write_event_with_escaped_text_and_rrule() {
+        let mut activity = Activity::new(3, "Running, easy; slow".to_owned(), Utc.with_ymd_and_hms(2025, 4, 1, 18, 0, 0).unwrap(), 1);
+        activity.end = Some(Utc.with_ymd_and_hms(2025, 4, 1, 19, 0, 0).unwrap());
+        activity.recurrence = Some(RecurrenceRule::new(Frequency::Weekly));
+
+        let ics = write_calendar(&[activity], Utc.with_ymd_and_hms(2025, 4, 1, 0, 0, 0).unwrap());
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains("UID:activity-3@myactivities\r\n"));
+        assert!(ics.contains("DTSTART:20250401T180000Z\r\nDTEND:20250401T190000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Running\\, easy\\; slow\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;INTERVAL=1\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn should_fold_long_lines() {
+        let mut activity = Activity::new(1, "Reading".to_owned(), Utc::now(), 1);
+        activity.description = Some("x".repeat(200));
+
+        let ics = write_calendar(&[activity], Utc::now());
+
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+        assert!(ics.contains("\r\n x"));
+    }
+
+    #[test]
+    fn should_parse_events_with_time_zones_and_recurrence() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+            BEGIN:VEVENT\r\nUID:run-1\r\nSUMMARY:Running\\, easy\r\n\
+            DTSTART;TZID=Europe/Berlin:20250401T180000\r\nDURATION:PT1H\r\n\
+            RRULE:FREQ=WEEKLY;BYDAY=TU,TH\r\nEXDATE;TZID=Europe/Berlin:20250403T180000\r\n\
+            BEGIN:VALARM\r\nSUMMARY:Alarm\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:holiday\r\nSUMMARY:Holi\r\n day\r\nDTSTART;VALUE=DATE:20250418\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:yearly\r\nDTSTART:20250101T000000Z\r\nRRULE:FREQ=YEARLY\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        let events = parse_calendar(ics, 1, chrono_tz::UTC).unwrap();
+        assert_eq!(events.len(), 3);
+
+        let running = events[0].as_ref().unwrap();
+        assert_eq!(running.title, "Running, easy");
+        assert_eq!(running.ical_uid.as_deref(), Some("run-1"));
+        assert_eq!(running.start, Utc.with_ymd_and_hms(2025, 4, 1, 16, 0, 0).unwrap());
+        assert_eq!(running.end, Some(Utc.with_ymd_and_hms(2025, 4, 1, 17, 0, 0).unwrap()));
+        let rule = running.recurrence.as_ref().unwrap();
+        assert_eq!(rule.by_weekday, vec![Weekday::Tue, Weekday::Thu]);
+        assert_eq!(rule.exceptions, vec![Utc.with_ymd_and_hms(2025, 4, 3, 16, 0, 0).unwrap()]);
+
+        let holiday = events[1].as_ref().unwrap();
+        assert_eq!(holiday.title, "Holiday");
+        assert_eq!(holiday.end, Some(Utc.with_ymd_and_hms(2025, 4, 19, 0, 0, 0).unwrap()));
+
+        assert_eq!(events[2].as_ref().unwrap_err().uid, "yearly");
+    }
+
+    #[test]
+    fn should_skip_events_with_huge_duration() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+            BEGIN:VEVENT\r\nUID:days\r\nDTSTART:20250401T180000Z\r\nDURATION:P99999999999D\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:hours\r\nDTSTART:20250401T180000Z\r\nDURATION:PT9999999999999999H\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:sum\r\nDTSTART:20250401T180000Z\r\nDURATION:P13000000W10000000D\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        let events = parse_calendar(ics, 1, chrono_tz::UTC).unwrap();
+
+        assert_eq!(events.len(), 3);
+        for event in events {
+            assert_eq!(event.unwrap_err().reason, "Invalid duration");
+        }
+    }
+
+    #[test]
+    fn should_reject_other_documents() {
+        assert!(parse_calendar("hello", 1, chrono_tz::UTC).is_err());
+    }
+}
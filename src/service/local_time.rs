@@ -0,0 +1,10 @@
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Converts a local time to UTC. Times inside a DST gap are moved by one hour.
+pub fn resolve_local(date_time: NaiveDateTime, timezone: Tz) -> DateTime<Utc> {
+    timezone.from_local_datetime(&date_time).earliest()
+        .or_else(|| timezone.from_local_datetime(&(date_time + Duration::hours(1))).earliest())
+        .map(|local| local.with_timezone(&Utc))
+        .unwrap_or_else(|| date_time.and_utc())
+}
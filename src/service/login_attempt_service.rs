@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+
+use crate::{config::{config::LoginThrottleConfig, db::DbConfig}, domain::{login_attempt::LoginSubject, login_attempt_api::LoginAttemptApi}, error::errors::LoginAttemptError};
+
+pub struct LoginAttemptService {
+    db_config: Arc<DbConfig>,
+    throttle_config: LoginThrottleConfig,
+}
+
+impl LoginAttemptService {
+    pub fn new(db_config: Arc<DbConfig>, throttle_config: LoginThrottleConfig) -> Self {
+        Self {
+            db_config,
+            throttle_config,
+        }
+    }
+
+    fn max_failures(&self, subject: &LoginSubject) -> u32 {
+        match subject {
+            LoginSubject::Account(_) | LoginSubject::Mail(_) => self.throttle_config.max_failures,
+            LoginSubject::Ip(_) => self.throttle_config.ip_max_failures,
+        }
+    }
+}
+
+/// Lockout after the given number of failures, doubled for every failure above the threshold
+fn lockout_duration(failures: u32, max_failures: u32, config: &LoginThrottleConfig) -> Option<Duration> {
+    if failures < max_failures {
+        return None;
+    }
+
+    let seconds = 2i64.checked_pow(failures - max_failures)
+        .and_then(|factor| factor.checked_mul(config.lockout_seconds))
+        .map_or(config.max_lockout_seconds, |seconds| seconds.min(config.max_lockout_seconds));
+
+    Some(Duration::seconds(seconds))
+}
+
+#[async_trait]
+impl LoginAttemptApi for LoginAttemptService {
+    async fn locked_until(&self, subject: &LoginSubject) -> Result<Option<DateTime<Utc>>, LoginAttemptError> {
+        let db = self.db_config.get_database().to_owned();
+        let key = subject.key();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let locked_until: Option<DateTime<Utc>> = conn.query_row(
+                "SELECT locked_until FROM login_failures WHERE subject = ?1",
+                [key],
+                |row| row.get(0))
+                .optional()?
+                .flatten();
+
+            Ok(locked_until.filter(|locked_until| *locked_until > Utc::now()))
+        }).await?
+    }
+
+    async fn record_failure(&self, subject: &LoginSubject) -> Result<(), LoginAttemptError> {
+        let db = self.db_config.get_database().to_owned();
+        let key = subject.key();
+        let max_failures = self.max_failures(subject);
+        let config = self.throttle_config.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            // the write lock is taken before reading, so parallel failures cannot overwrite each other's count
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let now = Utc::now();
+
+            let previous: Option<(u32, DateTime<Utc>)> = tx.query_row(
+                "SELECT failures, last_failure FROM login_failures WHERE subject = ?1",
+                [&key],
+                |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?;
+
+            // failures are forgotten after a quiet period as long as the longest lockout
+            let failures = match previous {
+                Some((failures, last_failure)) if now - last_failure < Duration::seconds(config.max_lockout_seconds) => failures + 1,
+                _ => 1,
+            };
+            let locked_until = lockout_duration(failures, max_failures, &config).map(|lockout| now + lockout);
+
+            tx.execute(
+                "INSERT OR REPLACE INTO login_failures (subject, failures, last_failure, locked_until) values (?1, ?2, ?3, ?4)",
+                (&key, failures, now, locked_until))?;
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn reset(&self, subject: &LoginSubject) -> Result<(), LoginAttemptError> {
+        let db = self.db_config.get_database().to_owned();
+        let key = subject.key();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("DELETE FROM login_failures WHERE subject = ?1", [key])?;
+
+            Ok(())
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod login_attempt_service_tests {
+    use std::sync::Arc;
+
+    use chrono::Duration;
+
+    use crate::{config::{config::LoginThrottleConfig, db::DbConfig}, create_db, domain::{login_attempt::LoginSubject, login_attempt_api::LoginAttemptApi}};
+
+    use super::{lockout_duration, LoginAttemptService};
+
+    #[test]
+    fn should_double_lockout_up_to_maximum() {
+        let config = LoginThrottleConfig { max_failures: 3, ip_max_failures: 10, lockout_seconds: 30, max_lockout_seconds: 100 };
+
+        assert_eq!(lockout_duration(2, 3, &config), None);
+        assert_eq!(lockout_duration(3, 3, &config), Some(Duration::seconds(30)));
+        assert_eq!(lockout_duration(4, 3, &config), Some(Duration::seconds(60)));
+        assert_eq!(lockout_duration(5, 3, &config), Some(Duration::seconds(100)));
+        assert_eq!(lockout_duration(200, 3, &config), Some(Duration::seconds(100)));
+    }
+
+    #[tokio::test]
+    async fn should_lock_account_after_max_failures() {
+        let db_config = DbConfig::new("file:login_attempt_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let config = LoginThrottleConfig { max_failures: 2, ..LoginThrottleConfig::default() };
+        let login_attempt_service = LoginAttemptService::new(Arc::new(db_config), config);
+        let account = LoginSubject::Account("test@example.org".to_owned());
+        let ip = LoginSubject::Ip("127.0.0.1".to_owned());
+
+        login_attempt_service.record_failure(&account).await.unwrap();
+        login_attempt_service.record_failure(&ip).await.unwrap();
+        assert!(login_attempt_service.locked_until(&account).await.unwrap().is_none());
+
+        login_attempt_service.record_failure(&account).await.unwrap();
+        login_attempt_service.record_failure(&ip).await.unwrap();
+        assert!(login_attempt_service.locked_until(&account).await.unwrap().is_some());
+        assert!(login_attempt_service.locked_until(&ip).await.unwrap().is_none(), "The ip has a higher threshold");
+
+        login_attempt_service.reset(&account).await.unwrap();
+        assert!(login_attempt_service.locked_until(&account).await.unwrap().is_none());
+    }
+}
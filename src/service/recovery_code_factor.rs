@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use actix_web::HttpRequest;
+use async_trait::async_trait;
+use authfix::multifactor::{CheckCodeError, Factor, GenerateCodeError};
+
+use crate::{domain::recovery_code_api::RecoveryCodeApi, service::{email_otp_factor::EmailOtpFactor, recovery_code_service}};
+
+/// Accepts a recovery code in place of the code of the wrapped factor, so a login with a lost authenticator
+/// uses the same second step as any other login. Codes in the format of recovery codes are only checked
+/// against the recovery codes, all other codes only by the wrapped factor.
+pub struct RecoveryCodeFactor {
+    inner: Box<dyn Factor>,
+    recovery_code_api: Arc<dyn RecoveryCodeApi>,
+}
+
+impl RecoveryCodeFactor {
+    pub fn new(inner: Box<dyn Factor>, recovery_code_api: Arc<dyn RecoveryCodeApi>) -> Self {
+        Self {
+            inner,
+            recovery_code_api,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Factor for RecoveryCodeFactor {
+    async fn generate_code(&self, req: &HttpRequest) -> Result<(), GenerateCodeError> {
+        self.inner.generate_code(req).await
+    }
+
+    fn get_unique_id(&self) -> String {
+        self.inner.get_unique_id()
+    }
+
+    async fn check_code(&self, code: &str, req: &HttpRequest) -> Result<(), CheckCodeError> {
+        if !recovery_code_service::is_recovery_code(code) {
+            return self.inner.check_code(code, req).await;
+        }
+
+        let user_id = EmailOtpFactor::pending_user_id(req)
+            .ok_or_else(|| CheckCodeError::new("No login in progress"))?;
+
+        match self.recovery_code_api.use_code(user_id, code).await {
+            Ok(true) => {
+                log::info!("User {} logged in with a recovery code", user_id);
+                Ok(())
+            },
+            Ok(false) => Err(CheckCodeError::new("Invalid recovery code")),
+            Err(err) => {
+                log::error!("Cannot check recovery code: {}", err);
+                Err(CheckCodeError::new("Cannot verify code"))
+            },
+        }
+    }
+}
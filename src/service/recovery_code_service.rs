@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use argon2::{password_hash::{rand_core::{OsRng, RngCore}, SaltString}, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use async_trait::async_trait;
+use rusqlite::Connection;
+
+use crate::{config::db::DbConfig, domain::recovery_code_api::RecoveryCodeApi, error::errors::RecoveryCodeError};
+
+const CODE_COUNT: usize = 10;
+const CODE_GROUP_LENGTH: usize = 5;
+/// Without 0, 1, I and O, which are easily confused when typed from paper
+const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// Whether the code has the format of a recovery code. TOTP and email codes are shorter, so they are never
+/// checked against the recovery codes, which needs an argon2 verification per remaining code.
+pub fn is_recovery_code(code: &str) -> bool {
+    let code = RecoveryCodeService::normalize_code(code);
+    code.len() == 2 * CODE_GROUP_LENGTH && code.bytes().all(|c| CODE_ALPHABET.contains(&c))
+}
+
+pub struct RecoveryCodeService {
+    db_config: Arc<DbConfig>
+}
+
+impl RecoveryCodeService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    /// Random code like `7KQ2M-XH4TZ` (50 bits)
+    fn generate_code() -> String {
+        let group = || -> String {
+            (0..CODE_GROUP_LENGTH)
+                .map(|_| CODE_ALPHABET[OsRng.next_u32() as usize % CODE_ALPHABET.len()] as char)
+                .collect()
+        };
+
+        format!("{}-{}", group(), group())
+    }
+
+    /// Codes are accepted case insensitive and with or without separator
+    fn normalize_code(code: &str) -> String {
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+
+    fn hash_code(code: &str) -> Result<String, RecoveryCodeError> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Ok(Argon2::default()
+            .hash_password(RecoveryCodeService::normalize_code(code).as_bytes(), &salt)
+            .map_err(|_| RecoveryCodeError::new("Cannot hash recovery code"))?
+            .to_string())
+    }
+}
+
+#[async_trait]
+impl RecoveryCodeApi for RecoveryCodeService {
+    async fn replace_codes(&self, user_id: i32) -> Result<Vec<String>, RecoveryCodeError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let codes: Vec<String> = (0..CODE_COUNT).map(|_| RecoveryCodeService::generate_code()).collect();
+            let hashes = codes.iter()
+                .map(|code| RecoveryCodeService::hash_code(code))
+                .collect::<Result<Vec<String>, RecoveryCodeError>>()?;
+
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM recovery_codes WHERE user_id = ?1", [user_id])?;
+            for hash in hashes {
+                tx.execute("INSERT INTO recovery_codes (user_id, code_hash) values (?1, ?2)", (user_id, hash))?;
+            }
+            tx.commit()?;
+
+            Ok(codes)
+        }).await?
+    }
+
+    async fn count_remaining(&self, user_id: i32) -> Result<usize, RecoveryCodeError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let count: i64 = conn.query_row("SELECT COUNT(*) FROM recovery_codes WHERE user_id = ?1", [user_id], |row| row.get(0))?;
+
+            Ok(count as usize)
+        }).await?
+    }
+
+    async fn use_code(&self, user_id: i32, code: &str) -> Result<bool, RecoveryCodeError> {
+        let db = self.db_config.get_database().to_owned();
+        let code = RecoveryCodeService::normalize_code(code);
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let mut stmt = conn.prepare("SELECT id, code_hash FROM recovery_codes WHERE user_id = ?1")?;
+            let hashes = stmt.query_map([user_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<Result<Vec<(i32, String)>, rusqlite::Error>>()?;
+
+            let argon2 = Argon2::default();
+            let matching = hashes.iter().find(|(_, hash)| {
+                PasswordHash::new(hash)
+                    .is_ok_and(|hash| argon2.verify_password(code.as_bytes(), &hash).is_ok())
+            });
+
+            match matching {
+                Some((id, _)) => {
+                    // a concurrent login could have used the same code in the meantime
+                    let deleted = conn.execute("DELETE FROM recovery_codes WHERE id = ?1", [id])?;
+                    Ok(deleted == 1)
+                },
+                None => Ok(false),
+            }
+        }).await?
+    }
+
+    async fn delete_codes(&self, user_id: i32) -> Result<(), RecoveryCodeError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("DELETE FROM recovery_codes WHERE user_id = ?1", [user_id])?;
+
+            Ok(())
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod recovery_code_service_tests {
+    use std::sync::Arc;
+
+    use crate::{config::db::DbConfig, create_db, domain::recovery_code_api::RecoveryCodeApi};
+
+    use super::{is_recovery_code, RecoveryCodeService};
+
+    #[tokio::test]
+    async fn should_accept_each_code_once() {
+        let db_config = DbConfig::new("file:recovery_code_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let recovery_code_service = RecoveryCodeService::new(Arc::new(db_config));
+
+        let codes = recovery_code_service.replace_codes(1).await.unwrap();
+        assert_eq!(codes.len(), 10);
+
+        assert!(recovery_code_service.use_code(1, &codes[3].to_lowercase()).await.unwrap());
+        assert!(!recovery_code_service.use_code(1, &codes[3]).await.unwrap());
+        assert!(!recovery_code_service.use_code(2, &codes[4]).await.unwrap());
+        assert_eq!(recovery_code_service.count_remaining(1).await.unwrap(), 9);
+
+        recovery_code_service.replace_codes(1).await.unwrap();
+        assert!(!recovery_code_service.use_code(1, &codes[4]).await.unwrap());
+    }
+
+    #[test]
+    fn should_distinguish_recovery_codes_from_factor_codes() {
+        assert!(is_recovery_code("7KQ2M-XH4TZ"));
+        assert!(is_recovery_code("7kq2mxh4tz"));
+        assert!(!is_recovery_code("123456"));
+        assert!(!is_recovery_code("7KQ2M-XH4T0"), "0 is not part of the alphabet");
+    }
+}
@@ -0,0 +1,87 @@
+use std::io;
+
+use aes_gcm::{aead::{Aead, AeadCore, KeyInit, OsRng, Payload}, Aes256Gcm, Key, Nonce};
+
+use crate::{config::{config::Config, key_file}, error::errors::SecretCipherError, service::secure_token};
+
+pub const SECRET_KEY_BYTES: usize = 32;
+/// Marks encrypted values, so the format can change later and plaintext values are recognized
+const ENCRYPTED_PREFIX: &str = "v1:";
+const NONCE_BYTES: usize = 12;
+
+/// Encrypts secrets stored in the database (AES-256-GCM). The user id is bound as associated data,
+/// so an encrypted secret cannot be copied to another user.
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    pub fn new(key: &[u8]) -> Result<Self, SecretCipherError> {
+        if key.len() != SECRET_KEY_BYTES {
+            return Err(SecretCipherError::new(&format!("The key must be {} bytes long", SECRET_KEY_BYTES)));
+        }
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        })
+    }
+
+    pub fn from_config(config: &Config) -> io::Result<Self> {
+        let key = match &config.mfa_secret_key {
+            Some(hex) => secure_token::from_hex(hex.trim())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "MA_MFA_SECRET_KEY is not hex encoded"))?,
+            None => key_file::load_or_create(&config.mfa_secret_key_file, SecretCipher::generate_key)?,
+        };
+
+        SecretCipher::new(&key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn generate_key() -> Vec<u8> {
+        Aes256Gcm::generate_key(&mut OsRng).to_vec()
+    }
+
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(ENCRYPTED_PREFIX)
+    }
+
+    pub fn encrypt(&self, user_id: i32, secret: &str) -> Result<String, SecretCipherError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = user_id.to_be_bytes();
+        let ciphertext = self.cipher.encrypt(&nonce, Payload { msg: secret.as_bytes(), aad: &aad })
+            .map_err(|_| SecretCipherError::new("Cannot encrypt secret"))?;
+
+        Ok(format!("{}{}{}", ENCRYPTED_PREFIX, secure_token::to_hex(&nonce), secure_token::to_hex(&ciphertext)))
+    }
+
+    pub fn decrypt(&self, user_id: i32, value: &str) -> Result<String, SecretCipherError> {
+        let bytes = value.strip_prefix(ENCRYPTED_PREFIX)
+            .and_then(secure_token::from_hex)
+            .filter(|bytes| bytes.len() > NONCE_BYTES)
+            .ok_or_else(|| SecretCipherError::new("The secret is not encrypted"))?;
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_BYTES);
+        let aad = user_id.to_be_bytes();
+        let secret = self.cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| SecretCipherError::new("Cannot decrypt secret, wrong key?"))?;
+
+        String::from_utf8(secret).map_err(|_| SecretCipherError::new("The secret is not valid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretCipher;
+
+    #[test]
+    fn should_decrypt_only_for_same_user() {
+        let cipher = SecretCipher::new(&SecretCipher::generate_key()).unwrap();
+
+        let encrypted = cipher.encrypt(1, "JBSWY3DPEHPK3PXP").unwrap();
+
+        assert!(SecretCipher::is_encrypted(&encrypted));
+        assert!(!encrypted.contains("JBSWY3DPEHPK3PXP"));
+        assert_eq!(cipher.decrypt(1, &encrypted).unwrap(), "JBSWY3DPEHPK3PXP");
+        assert!(cipher.decrypt(2, &encrypted).is_err());
+        assert!(cipher.decrypt(1, "JBSWY3DPEHPK3PXP").is_err());
+    }
+}
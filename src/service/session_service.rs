@@ -0,0 +1,269 @@
+use std::{collections::HashMap, sync::Arc};
+
+use actix_web::cookie::time::Duration;
+use async_trait::async_trait;
+use authfix::actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+
+use crate::{config::db::DbConfig, domain::{session::{SessionInfo, SESSION_KEY_IP, SESSION_KEY_PUBLIC_ID, SESSION_KEY_USER_AGENT, SESSION_KEY_USER_ID}, session_api::SessionApi}, error::errors::SessionError, service::secure_token};
+
+type SessionState = HashMap<String, String>;
+
+/// Keeps the sessions in the database, so they can be listed and revoked.
+/// Only a hash of the session key is stored, the cookie holds the key itself.
+pub struct SessionService {
+    db_config: Arc<DbConfig>
+}
+
+impl SessionService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    async fn insert(&self, session_state: SessionState, ttl: &Duration) -> anyhow::Result<SessionKey> {
+        let db = self.db_config.get_database().to_owned();
+        let key = secure_token::generate_token();
+        let key_hash = secure_token::hash_token(&key);
+        let expires_at = expires_at(ttl);
+        let columns = TrackedColumns::from_state(&session_state);
+        let state = serde_json::to_string(&session_state)?;
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            // expired sessions are never loaded again
+            conn.execute("DELETE FROM sessions WHERE expires_at <= ?1", [Utc::now()])?;
+            conn.execute(
+                "INSERT INTO sessions (key_hash, public_id, user_id, user_agent, ip, state, created_at, last_seen, expires_at) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7, ?8)",
+                (key_hash, columns.public_id, columns.user_id, columns.user_agent, columns.ip, state, Utc::now(), expires_at))?;
+
+            Ok::<(), anyhow::Error>(())
+        }).await??;
+
+        Ok(SessionKey::try_from(key)?)
+    }
+
+    /// Returns `false` if the session does not exist (anymore)
+    async fn replace_state(&self, session_key: &SessionKey, session_state: &SessionState, ttl: &Duration) -> anyhow::Result<bool> {
+        let db = self.db_config.get_database().to_owned();
+        let key_hash = secure_token::hash_token(session_key.as_ref());
+        let expires_at = expires_at(ttl);
+        let columns = TrackedColumns::from_state(session_state);
+        let state = serde_json::to_string(session_state)?;
+
+        let updated = tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let updated = conn.execute(
+                "UPDATE sessions SET public_id = ?1, user_id = ?2, user_agent = ?3, ip = ?4, state = ?5, last_seen = ?6, expires_at = CASE WHEN api_token_id IS NULL THEN ?7 ELSE expires_at END WHERE key_hash = ?8 AND expires_at > ?6",
+                (columns.public_id, columns.user_id, columns.user_agent, columns.ip, state, Utc::now(), expires_at, key_hash))?;
+
+            Ok::<usize, anyhow::Error>(updated)
+        }).await??;
+
+        Ok(updated > 0)
+    }
+}
+
+fn expires_at(ttl: &Duration) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+}
+
+/// Values of the session state that are needed to list the sessions of a user
+struct TrackedColumns {
+    public_id: Option<String>,
+    user_id: Option<i32>,
+    user_agent: Option<String>,
+    ip: Option<String>,
+}
+
+impl TrackedColumns {
+    fn from_state(state: &SessionState) -> Self {
+        Self {
+            public_id: state_value(state, SESSION_KEY_PUBLIC_ID),
+            user_id: state_value(state, SESSION_KEY_USER_ID),
+            user_agent: state_value(state, SESSION_KEY_USER_AGENT),
+            ip: state_value(state, SESSION_KEY_IP),
+        }
+    }
+}
+
+/// The session stores its values serialized as JSON
+fn state_value<T: DeserializeOwned>(state: &SessionState, key: &str) -> Option<T> {
+    state.get(key).and_then(|value| serde_json::from_str(value).ok())
+}
+
+impl SessionStore for SessionService {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let db = self.db_config.get_database().to_owned();
+        let key_hash = secure_token::hash_token(session_key.as_ref());
+
+        let state: Option<String> = tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let state = conn.query_row(
+                "SELECT state FROM sessions WHERE key_hash = ?1 AND expires_at > ?2",
+                (key_hash, Utc::now()),
+                |row| row.get(0))
+                .optional()?;
+
+            Ok::<Option<String>, anyhow::Error>(state)
+        }).await
+            .map_err(|e| LoadError::Other(e.into()))?
+            .map_err(LoadError::Other)?;
+
+        state.map(|state| serde_json::from_str(&state))
+            .transpose()
+            .map_err(|e| LoadError::Deserialization(e.into()))
+    }
+
+    async fn save(&self, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, SaveError> {
+        self.insert(session_state, ttl).await
+            .map_err(SaveError::Other)
+    }
+
+    async fn update(&self, session_key: SessionKey, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, UpdateError> {
+        let updated = self.replace_state(&session_key, &session_state, ttl).await
+            .map_err(UpdateError::Other)?;
+
+        if !updated {
+            // the session was revoked or expired in the meantime, it must not be created again
+            log::info!("Dropping state of a session that does not exist anymore");
+        }
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> Result<(), anyhow::Error> {
+        let db = self.db_config.get_database().to_owned();
+        let key_hash = secure_token::hash_token(session_key.as_ref());
+        let expires_at = expires_at(ttl);
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("UPDATE sessions SET expires_at = ?1 WHERE key_hash = ?2 AND api_token_id IS NULL", (expires_at, key_hash))?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        let db = self.db_config.get_database().to_owned();
+        let key_hash = secure_token::hash_token(session_key.as_ref());
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("DELETE FROM sessions WHERE key_hash = ?1", [key_hash])?;
+
+            Ok(())
+        }).await?
+    }
+}
+
+#[async_trait]
+impl SessionApi for SessionService {
+    async fn find_by_user(&self, user_id: i32, current_id: Option<&str>) -> Result<Vec<SessionInfo>, SessionError> {
+        let db = self.db_config.get_database().to_owned();
+        let current_id = current_id.map(str::to_owned);
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let mut stmt = conn.prepare(
+                "SELECT public_id, user_agent, ip, created_at, last_seen FROM sessions WHERE user_id = ?1 AND public_id IS NOT NULL AND api_token_id IS NULL AND expires_at > ?2 ORDER BY last_seen DESC")?;
+
+            let sessions = stmt.query_map((user_id, Utc::now()), |row| {
+                let id: String = row.get(0)?;
+                Ok(SessionInfo {
+                    current: current_id.as_deref() == Some(id.as_str()),
+                    id,
+                    user_agent: row.get(1)?,
+                    ip: row.get(2)?,
+                    created_at: row.get(3)?,
+                    last_seen: row.get(4)?,
+                })
+            })?.collect::<Result<Vec<SessionInfo>, rusqlite::Error>>()?;
+
+            Ok(sessions)
+        }).await?
+    }
+
+    async fn delete(&self, user_id: i32, id: &str) -> Result<(), SessionError> {
+        let db = self.db_config.get_database().to_owned();
+        let id = id.to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let deleted = conn.execute("DELETE FROM sessions WHERE user_id = ?1 AND public_id = ?2 AND api_token_id IS NULL", (user_id, id))?;
+
+            if deleted == 0 {
+                Err(SessionError::NotFound)
+            } else {
+                Ok(())
+            }
+        }).await?
+    }
+
+    async fn delete_all(&self, user_id: i32) -> Result<(), SessionError> {
+        let db = self.db_config.get_database().to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            // sessions of api tokens end when the token is revoked
+            conn.execute("DELETE FROM sessions WHERE user_id = ?1 AND api_token_id IS NULL", [user_id])?;
+
+            Ok(())
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod session_service_tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use actix_web::cookie::time::Duration;
+    use authfix::actix_session::storage::SessionStore;
+
+    use crate::{config::db::DbConfig, create_db, domain::{session::{SESSION_KEY_PUBLIC_ID, SESSION_KEY_USER_ID}, session_api::SessionApi}};
+
+    use super::SessionService;
+
+    #[tokio::test]
+    async fn should_not_load_revoked_session() {
+        let db_config = DbConfig::new("file:session_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let session_service = SessionService::new(Arc::new(db_config));
+        let state = HashMap::from([
+            (SESSION_KEY_PUBLIC_ID.to_owned(), "\"abc\"".to_owned()),
+            (SESSION_KEY_USER_ID.to_owned(), "1".to_owned()),
+        ]);
+
+        let key = session_service.save(state.clone(), &Duration::hours(1)).await.unwrap();
+        assert_eq!(session_service.load(&key).await.unwrap(), Some(state));
+
+        let sessions = session_service.find_by_user(1, Some("abc")).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].current);
+
+        SessionApi::delete(&session_service, 1, "abc").await.unwrap();
+        assert_eq!(session_service.load(&key).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn should_not_restore_revoked_session_on_update() {
+        let db_config = DbConfig::new("file:session_service_update_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let session_service = SessionService::new(Arc::new(db_config));
+        let state = HashMap::from([
+            (SESSION_KEY_PUBLIC_ID.to_owned(), "\"abc\"".to_owned()),
+            (SESSION_KEY_USER_ID.to_owned(), "1".to_owned()),
+        ]);
+
+        let key = session_service.save(state.clone(), &Duration::hours(1)).await.unwrap();
+        SessionApi::delete(&session_service, 1, "abc").await.unwrap();
+
+        let key = session_service.update(key, state, &Duration::hours(1)).await.unwrap();
+        assert_eq!(session_service.load(&key).await.unwrap(), None);
+        assert!(session_service.find_by_user(1, None).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+use lettre::{message::{header::ContentType, Mailbox}, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::{config::config::SmtpConfig, domain::{mail::Mail, mailer_api::Mailer}, error::errors::MailError};
+
+/// Sends mails via SMTP with STARTTLS
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig, from: &str) -> Result<Self, MailError> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+            .map_err(|e| MailError::new(&format!("Invalid SMTP host: {}", e)))?
+            .port(config.port)
+            .credentials(Credentials::new(config.username.clone(), config.password.clone()))
+            .build();
+
+        let from = from.parse()
+            .map_err(|e| MailError::new(&format!("Invalid sender address: {}", e)))?;
+
+        Ok(Self {
+            transport,
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let to: Mailbox = mail.to.parse()
+            .map_err(|e| MailError::new(&format!("Invalid recipient address: {}", e)))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body)
+            .map_err(|e| MailError::new(&e.to_string()))?;
+
+        self.transport.send(message).await
+            .map_err(|e| MailError::new(&e.to_string()))?;
+
+        Ok(())
+    }
+}
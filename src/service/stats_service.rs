@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::{functions::FunctionFlags, Connection};
+
+use crate::{config::db::DbConfig, domain::{stats::{StatsDimension, StatsEntry, StatsGrouping, StatsQuery}, stats_api::StatsApi}, error::errors::QueryStatsError};
+
+pub struct StatsService {
+    db_config: Arc<DbConfig>
+}
+
+impl StatsService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+
+    /// Start of the local day as UTC
+    fn local_midnight(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        timezone.from_local_datetime(&midnight)
+            .earliest()
+            .map(|date_time| date_time.with_timezone(&Utc))
+            .unwrap_or_else(|| midnight.and_utc())
+    }
+
+    /// Registers `local_period(start_time)`, which maps a UTC timestamp to the local period it belongs to.
+    /// SQLite has no support for named time zones, so the conversion (including DST) is done by chrono-tz.
+    fn register_local_period(conn: &Connection, timezone: Tz, grouping: StatsGrouping) -> Result<(), rusqlite::Error> {
+        conn.create_scalar_function("local_period", 1, FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC, move |ctx| {
+            let start: DateTime<Utc> = ctx.get(0)?;
+            let local_date = start.with_timezone(&timezone).date_naive();
+
+            let period = match grouping {
+                StatsGrouping::Day => local_date.format("%Y-%m-%d").to_string(),
+                StatsGrouping::Week => {
+                    let week_start = local_date - Duration::days(local_date.weekday().num_days_from_monday() as i64);
+                    week_start.format("%Y-%m-%d").to_string()
+                },
+                StatsGrouping::Month => local_date.format("%Y-%m").to_string(),
+            };
+
+            Ok(period)
+        })
+    }
+}
+
+#[async_trait]
+impl StatsApi for StatsService {
+    async fn aggregate(&self, user_id: i32, query: &StatsQuery, timezone: Tz) -> Result<Vec<StatsEntry>, QueryStatsError> {
+        let db = self.db_config.get_database().to_owned();
+        let from = StatsService::local_midnight(query.from, timezone);
+        // `StatsQuery::validate` rejects the last representable date
+        let to = StatsService::local_midnight(query.to.succ_opt().unwrap_or(NaiveDate::MAX), timezone);
+        let grouping = query.group_by;
+        let by_category = query.by == Some(StatsDimension::Category);
+
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            StatsService::register_local_period(&conn, timezone, grouping)?;
+
+            let (category_column, category_group) = match by_category {
+                true => ("activities.category_id", ", activities.category_id"),
+                false => ("NULL", ""),
+            };
+
+            // running sessions count until now
+            let query = format!(r#"
+                SELECT local_period(activity_sessions.start_time) AS period,
+                    {category_column},
+                    SUM(CAST(ROUND((julianday(COALESCE(activity_sessions.end_time, ?4)) - julianday(activity_sessions.start_time)) * 86400) AS INTEGER)),
+                    COUNT(*)
+                FROM activity_sessions
+                JOIN activities ON activities.id = activity_sessions.activity_id
+                WHERE activity_sessions.user_id = ?1
+                AND activity_sessions.start_time >= ?2
+                AND activity_sessions.start_time < ?3
+                GROUP BY period{category_group}
+                ORDER BY period{category_group}
+            "#);
+
+            let mut stmt = conn.prepare(&query)?;
+            let entries = stmt.query_map((user_id, from, to, Utc::now()), |row| {
+                Ok(StatsEntry {
+                    period: row.get(0)?,
+                    category_id: row.get(1)?,
+                    total_seconds: row.get(2)?,
+                    count: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<StatsEntry>, rusqlite::Error>>()?;
+
+            Ok(entries)
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod stats_service_tests {
+    use std::sync::Arc;
+
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rusqlite::Connection;
+
+    use crate::{config::db::DbConfig, create_db, domain::{stats::{StatsDimension, StatsGrouping, StatsQuery}, stats_api::StatsApi}};
+
+    use super::StatsService;
+
+    fn insert_session(conn: &Connection, activity_id: i32, start: (u32, u32), minutes: i64) {
+        let start = Utc.with_ymd_and_hms(2025, 4, start.0, start.1, 30, 0).unwrap();
+        let end = start + chrono::Duration::minutes(minutes);
+        conn.execute("INSERT INTO activity_sessions (activity_id, user_id, start_time, end_time) values (?1, 1, ?2, ?3)", (activity_id, start, end)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_group_sessions_by_local_day_and_category() {
+        let db_config = DbConfig::new("file:stats_service_day_test?mode=memory&cache=shared");
+        let db = create_db(&db_config);
+        let stats_service = StatsService::new(Arc::new(db_config));
+
+        db.execute("INSERT INTO activities (id, title, start_time, category_id, user_id) values (1, 'Running', ?1, 7, 1)", [Utc::now()]).unwrap();
+        db.execute("INSERT INTO activities (id, title, start_time, user_id) values (2, 'Reading', ?1, 1)", [Utc::now()]).unwrap();
+        // 2025-04-01 23:30 UTC is already 2025-04-02 in Berlin
+        insert_session(&db, 1, (1, 23), 30);
+        insert_session(&db, 1, (2, 10), 60);
+        insert_session(&db, 2, (2, 12), 15);
+
+        let query = StatsQuery {
+            from: NaiveDate::from_ymd_opt(2025, 4, 1).unwrap(),
+            to: NaiveDate::from_ymd_opt(2025, 4, 2).unwrap(),
+            group_by: StatsGrouping::Day,
+            by: Some(StatsDimension::Category),
+        };
+        let entries = stats_service.aggregate(1, &query, chrono_tz::Europe::Berlin).await.unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].period, "2025-04-02");
+        assert_eq!(entries[0].category_id, None);
+        assert_eq!(entries[0].total_seconds, 15 * 60);
+        assert_eq!(entries[1].category_id, Some(7));
+        assert_eq!(entries[1].total_seconds, 90 * 60);
+        assert_eq!(entries[1].count, 2);
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rusqlite::Connection;
+
+use crate::{config::db::DbConfig, domain::{tag::Tag, tag_api::TagApi}, error::errors::{QueryTagError, TagUpdateError}};
+
+pub struct TagService {
+    db_config: Arc<DbConfig>
+}
+
+impl TagService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+}
+
+#[async_trait]
+impl TagApi for TagService {
+    async fn find_all_by_user_id(&self, user_id: i32) -> Result<Vec<Tag>, QueryTagError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let mut stmt = conn.prepare("SELECT id, name, user_id FROM tags WHERE user_id = ?1 ORDER BY name")?;
+            let tags = stmt.query_map([user_id], |row| Ok(Tag::new(row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<Result<Vec<Tag>, rusqlite::Error>>()?;
+
+            Ok(tags)
+        }).await?
+    }
+
+    async fn save_tag(&self, tag: Tag) -> Result<Tag, TagUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let tag_id = if tag.id > 0 {
+                let changed = conn.execute("UPDATE tags SET name = ?1 WHERE id = ?2 AND user_id = ?3", (&tag.name, tag.id, tag.user_id))?;
+                if changed == 0 {
+                    return Err(TagUpdateError::NotFound);
+                }
+
+                tag.id
+            } else {
+                conn.execute("INSERT INTO tags (name, user_id) values (?1, ?2)", (&tag.name, tag.user_id))?;
+                conn.last_insert_rowid() as i32
+            };
+
+            Ok(Tag::new(tag_id, tag.name, tag.user_id))
+        }).await?
+    }
+
+    async fn delete_tag(&self, tag_id: i32, user_id: i32) -> Result<(), TagUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let deleted = tx.execute("DELETE FROM tags WHERE id = ?1 AND user_id = ?2", [tag_id, user_id])?;
+            if deleted == 0 {
+                return Err(TagUpdateError::NotFound);
+            }
+
+            tx.execute("DELETE FROM activity_tags WHERE tag_id = ?1", [tag_id])?;
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+}
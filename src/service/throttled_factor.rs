@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use actix_web::HttpRequest;
+use async_trait::async_trait;
+use authfix::multifactor::{CheckCodeError, Factor, GenerateCodeError};
+
+use crate::{domain::{login_attempt::LoginSubject, login_attempt_api::LoginAttemptApi, user_api::UserApi}, service::email_otp_factor::EmailOtpFactor};
+
+/// Counts wrong codes of the wrapped factor as failed logins of the account, so the second factor
+/// cannot be guessed while the password is known
+pub struct ThrottledFactor {
+    inner: Box<dyn Factor>,
+    user_api: Arc<dyn UserApi>,
+    login_attempt_api: Arc<dyn LoginAttemptApi>,
+}
+
+impl ThrottledFactor {
+    pub fn new(inner: Box<dyn Factor>, user_api: Arc<dyn UserApi>, login_attempt_api: Arc<dyn LoginAttemptApi>) -> Self {
+        Self {
+            inner,
+            user_api,
+            login_attempt_api,
+        }
+    }
+
+    async fn pending_account(&self, req: &HttpRequest) -> Option<LoginSubject> {
+        let user_id = EmailOtpFactor::pending_user_id(req)?;
+        match self.user_api.find_by_id(user_id).await {
+            Ok(user) => Some(LoginSubject::Account(user.email)),
+            Err(err) => {
+                log::error!("Cannot load user of pending login: {}", err);
+                None
+            },
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Factor for ThrottledFactor {
+    async fn generate_code(&self, req: &HttpRequest) -> Result<(), GenerateCodeError> {
+        self.inner.generate_code(req).await
+    }
+
+    fn get_unique_id(&self) -> String {
+        self.inner.get_unique_id()
+    }
+
+    async fn check_code(&self, code: &str, req: &HttpRequest) -> Result<(), CheckCodeError> {
+        let Some(account) = self.pending_account(req).await else {
+            return self.inner.check_code(code, req).await;
+        };
+
+        match self.login_attempt_api.locked_until(&account).await {
+            Ok(Some(_)) => return Err(CheckCodeError::new("Too many failed login attempts, try again later")),
+            Ok(None) => {},
+            Err(err) => log::error!("Cannot check login lockout: {}", err),
+        }
+
+        let result = self.inner.check_code(code, req).await;
+        let recorded = match result {
+            Ok(_) => self.login_attempt_api.reset(&account).await,
+            Err(_) => self.login_attempt_api.record_failure(&account).await,
+        };
+        if let Err(err) = recorded {
+            log::error!("Cannot record login attempt: {}", err);
+        }
+
+        result
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{DateTime, Duration, Utc};
+use roxmltree::{Document, Node};
+
+use crate::domain::activity::{SportMetrics, TrackPoint};
+
+/// GPS elevation is noisy, climbs below this threshold are ignored
+const ELEVATION_THRESHOLD_METERS: f64 = 3.0;
+/// Maximum deviation of the simplified track from the recorded one
+const SIMPLIFY_TOLERANCE_METERS: f64 = 5.0;
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+/// Longer recordings are rejected as invalid
+const MAX_DURATION_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+/// An activity recorded by a GPS device
+pub struct Recording {
+    pub name: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub metrics: SportMetrics,
+    /// Simplified track
+    pub track: Vec<TrackPoint>,
+}
+
+struct RecordedPoint {
+    position: Option<TrackPoint>,
+    time: Option<DateTime<Utc>>,
+}
+
+/// Totals given by the laps of a TCX file. They are preferred over the values computed from the track.
+struct LapTotals {
+    start: Option<DateTime<Utc>>,
+    duration_seconds: Option<f64>,
+    distance_meters: Option<f64>,
+}
+
+/// Parses a GPX or TCX file, depending on the root element
+pub fn parse_recording(data: &str) -> Result<Recording, String> {
+    let document = Document::parse(data).map_err(|e| format!("Invalid XML: {}", e))?;
+    let root = document.root_element();
+
+    match root.tag_name().name() {
+        "gpx" => parse_gpx(root),
+        "TrainingCenterDatabase" => parse_tcx(root),
+        other => Err(format!("Unsupported file format: {}", other)),
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.tag_name().name() == name)
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|time| time.with_timezone(&Utc))
+}
+
+/// Accepts finite numbers only, `inf` and `NaN` are rejected
+fn parse_number(value: Option<&str>, name: &str) -> Result<Option<f64>, String> {
+    value.map(|value| value.parse::<f64>().ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("Invalid {}: {}", name, value)))
+        .transpose()
+}
+
+fn parse_gpx(root: Node) -> Result<Recording, String> {
+    let track = child(root, "trk");
+    let name = track.and_then(|track| child_text(track, "name"))
+        .or_else(|| child(root, "metadata").and_then(|metadata| child_text(metadata, "name")))
+        .map(str::to_owned);
+    let sport = track.and_then(|track| child_text(track, "type")).map(str::to_owned);
+
+    let points = root.descendants()
+        .filter(|node| node.tag_name().name() == "trkpt")
+        .map(|node| -> Result<RecordedPoint, String> {
+            let latitude = parse_number(node.attribute("lat"), "latitude")?.ok_or("Track point without latitude")?;
+            let longitude = parse_number(node.attribute("lon"), "longitude")?.ok_or("Track point without longitude")?;
+
+            Ok(RecordedPoint {
+                position: Some(TrackPoint { latitude, longitude, elevation: parse_number(child_text(node, "ele"), "elevation")? }),
+                time: child_text(node, "time").and_then(parse_time),
+            })
+        })
+        .collect::<Result<Vec<RecordedPoint>, String>>()?;
+
+    build_recording(name, sport, points, None)
+}
+
+fn parse_tcx(root: Node) -> Result<Recording, String> {
+    let activity = root.descendants()
+        .find(|node| node.tag_name().name() == "Activity")
+        .ok_or("The file contains no activity")?;
+    let sport = activity.attribute("Sport").map(str::to_owned);
+    let name = child_text(activity, "Notes").map(str::to_owned);
+
+    let laps: Vec<Node> = activity.children().filter(|node| node.tag_name().name() == "Lap").collect();
+    let sum = |name: &str| -> Result<Option<f64>, String> {
+        if laps.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(laps.iter()
+            .map(|lap| parse_number(child_text(*lap, name), name))
+            .collect::<Result<Option<Vec<f64>>, String>>()?
+            .map(|values| values.iter().sum()))
+    };
+    let totals = LapTotals {
+        start: laps.first().and_then(|lap| lap.attribute("StartTime")).and_then(parse_time),
+        duration_seconds: sum("TotalTimeSeconds")?,
+        distance_meters: sum("DistanceMeters")?,
+    };
+
+    let points = activity.descendants()
+        .filter(|node| node.tag_name().name() == "Trackpoint")
+        .map(|node| -> Result<RecordedPoint, String> {
+            let position = match child(node, "Position") {
+                Some(position) => Some(TrackPoint {
+                    latitude: parse_number(child_text(position, "LatitudeDegrees"), "latitude")?.ok_or("Position without latitude")?,
+                    longitude: parse_number(child_text(position, "LongitudeDegrees"), "longitude")?.ok_or("Position without longitude")?,
+                    elevation: parse_number(child_text(node, "AltitudeMeters"), "elevation")?,
+                }),
+                // indoor activities have no position
+                None => None,
+            };
+
+            Ok(RecordedPoint { position, time: child_text(node, "Time").and_then(parse_time) })
+        })
+        .collect::<Result<Vec<RecordedPoint>, String>>()?;
+
+    build_recording(name, sport, points, Some(totals))
+}
+
+fn build_recording(name: Option<String>, sport: Option<String>, points: Vec<RecordedPoint>, totals: Option<LapTotals>) -> Result<Recording, String> {
+    let first_time = points.iter().find_map(|point| point.time);
+    let last_time = points.iter().rev().find_map(|point| point.time);
+
+    let start = totals.as_ref().and_then(|totals| totals.start).or(first_time)
+        .ok_or("The recording contains no timestamps")?;
+    let duration_seconds = match totals.as_ref().and_then(|totals| totals.duration_seconds) {
+        // the sum of the laps may still overflow
+        Some(duration) if duration.is_finite() && (0.0..=MAX_DURATION_SECONDS as f64).contains(&duration) => duration.round() as i64,
+        Some(duration) => return Err(format!("Invalid total time: {}", duration)),
+        None => last_time.map(|end| (end - start).num_seconds()).unwrap_or(0).max(0),
+    };
+    if duration_seconds > MAX_DURATION_SECONDS {
+        return Err(format!("The recording is longer than {} days", MAX_DURATION_SECONDS / (24 * 60 * 60)));
+    }
+    let end = Duration::try_seconds(duration_seconds)
+        .and_then(|duration| start.checked_add_signed(duration))
+        .ok_or("The recording ends out of range")?;
+
+    let positions: Vec<TrackPoint> = points.into_iter().filter_map(|point| point.position).collect();
+    let distance_meters = match totals.as_ref().and_then(|totals| totals.distance_meters) {
+        Some(meters) if meters.is_finite() && meters >= 0.0 => meters,
+        Some(meters) => return Err(format!("Invalid distance: {}", meters)),
+        None => positions.windows(2).map(|pair| distance(&pair[0], &pair[1])).sum(),
+    };
+
+    Ok(Recording {
+        name,
+        start,
+        end,
+        metrics: SportMetrics {
+            sport,
+            duration_seconds,
+            distance_meters,
+            elevation_gain_meters: elevation_gain(&positions),
+        },
+        track: simplify(&positions, SIMPLIFY_TOLERANCE_METERS),
+    })
+}
+
+/// Great circle distance in meters
+fn distance(a: &TrackPoint, b: &TrackPoint) -> f64 {
+    let (latitude_a, latitude_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let delta_latitude = latitude_b - latitude_a;
+    let delta_longitude = (b.longitude - a.longitude).to_radians();
+
+    let h = (delta_latitude / 2.0).sin().powi(2) + latitude_a.cos() * latitude_b.cos() * (delta_longitude / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_METERS * h.sqrt().asin()
+}
+
+/// Sum of all climbs of at least [`ELEVATION_THRESHOLD_METERS`]
+fn elevation_gain(points: &[TrackPoint]) -> f64 {
+    let mut gain = 0.0;
+    let mut reference: Option<f64> = None;
+
+    for elevation in points.iter().filter_map(|point| point.elevation) {
+        match reference {
+            Some(low) if elevation - low >= ELEVATION_THRESHOLD_METERS => {
+                gain += elevation - low;
+                reference = Some(elevation);
+            },
+            Some(low) if elevation >= low => {},
+            _ => reference = Some(elevation),
+        }
+    }
+
+    gain
+}
+
+/// Distance of `point` to the segment from `start` to `end` in meters, using a local planar projection
+fn segment_distance(point: &TrackPoint, start: &TrackPoint, end: &TrackPoint) -> f64 {
+    let project = |p: &TrackPoint| (
+        (p.longitude - start.longitude).to_radians() * start.latitude.to_radians().cos() * EARTH_RADIUS_METERS,
+        (p.latitude - start.latitude).to_radians() * EARTH_RADIUS_METERS,
+    );
+    let (px, py) = project(point);
+    let (ex, ey) = project(end);
+
+    let length = ex * ex + ey * ey;
+    let t = if length == 0.0 { 0.0 } else { ((px * ex + py * ey) / length).clamp(0.0, 1.0) };
+
+    ((px - t * ex).powi(2) + (py - t * ey).powi(2)).sqrt()
+}
+
+/// Ramer-Douglas-Peucker simplification
+fn simplify(points: &[TrackPoint], tolerance: f64) -> Vec<TrackPoint> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|index| (index, segment_distance(&points[index], &points[first], &points[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((index, deviation)) = farthest {
+            if deviation > tolerance {
+                keep[index] = true;
+                ranges.push((first, index));
+                ranges.push((index, last));
+            }
+        }
+    }
+
+    points.iter().zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| point.clone())
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::parse_recording;
+
+    #[test]
+    fn should_compute_metrics_of_gpx_track() {
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+              <trk>
+                <name>Morning Run</name>
+                <type>running</type>
+                <trkseg>
+                  <trkpt lat="0.0" lon="0.000"><ele>100</ele><time>2025-04-01T06:00:00Z</time></trkpt>
+                  <trkpt lat="0.0" lon="0.001"><ele>101</ele><time>2025-04-01T06:01:00Z</time></trkpt>
+                  <trkpt lat="0.0" lon="0.002"><ele>110</ele><time>2025-04-01T06:02:00Z</time></trkpt>
+                  <trkpt lat="0.0" lon="0.003"><ele>104</ele><time>2025-04-01T06:03:00Z</time></trkpt>
+                </trkseg>
+              </trk>
+            </gpx>"#;
+
+        let recording = parse_recording(gpx).unwrap();
+
+        assert_eq!(recording.name.as_deref(), Some("Morning Run"));
+        assert_eq!(recording.start, Utc.with_ymd_and_hms(2025, 4, 1, 6, 0, 0).unwrap());
+        assert_eq!(recording.metrics.duration_seconds, 180);
+        assert!((recording.metrics.distance_meters - 333.6).abs() < 1.0);
+        assert_eq!(recording.metrics.elevation_gain_meters, 10.0);
+        // all points are on a straight line
+        assert_eq!(recording.track.len(), 2);
+    }
+
+    #[test]
+    fn should_prefer_lap_totals_of_tcx() {
+        let tcx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+              <Activities>
+                <Activity Sport="Biking">
+                  <Id>2025-04-01T16:00:00Z</Id>
+                  <Lap StartTime="2025-04-01T16:00:00Z">
+                    <TotalTimeSeconds>1800</TotalTimeSeconds>
+                    <DistanceMeters>12000</DistanceMeters>
+                    <Track>
+                      <Trackpoint><Time>2025-04-01T16:00:00Z</Time><Position><LatitudeDegrees>48.0</LatitudeDegrees><LongitudeDegrees>11.0</LongitudeDegrees></Position></Trackpoint>
+                      <Trackpoint><Time>2025-04-01T16:29:00Z</Time><Position><LatitudeDegrees>48.1</LatitudeDegrees><LongitudeDegrees>11.0</LongitudeDegrees></Position></Trackpoint>
+                    </Track>
+                  </Lap>
+                </Activity>
+              </Activities>
+            </TrainingCenterDatabase>"#;
+
+        let recording = parse_recording(tcx).unwrap();
+
+        assert_eq!(recording.metrics.sport.as_deref(), Some("Biking"));
+        assert_eq!(recording.metrics.duration_seconds, 1800);
+        assert_eq!(recording.metrics.distance_meters, 12000.0);
+        assert_eq!(recording.end, Utc.with_ymd_and_hms(2025, 4, 1, 16, 30, 0).unwrap());
+        assert_eq!(recording.track.len(), 2);
+    }
+
+    #[test]
+    fn should_reject_invalid_lap_totals() {
+        let tcx = |total_time: &str| format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+            <TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+              <Activities>
+                <Activity Sport="Running">
+                  <Lap StartTime="2025-04-01T16:00:00Z">
+                    <TotalTimeSeconds>{}</TotalTimeSeconds>
+                  </Lap>
+                </Activity>
+              </Activities>
+            </TrainingCenterDatabase>"#, total_time);
+
+        assert!(parse_recording(&tcx("600")).is_ok());
+        for total_time in ["inf", "NaN", "1e30", "-60"] {
+            assert!(parse_recording(&tcx(total_time)).is_err(), "{} should be rejected", total_time);
+        }
+    }
+
+    #[test]
+    fn should_reject_unknown_format() {
+        assert!(parse_recording("<kml></kml>").is_err());
+        assert!(parse_recording("not xml").is_err());
+    }
+}
@@ -3,21 +3,66 @@ use std::sync::Arc;
 use argon2::{password_hash::{rand_core::OsRng, SaltString}, Argon2, PasswordHasher};
 use async_trait::async_trait;
 use authfix::multifactor::{GetTotpSecretError, TotpSecretRepository};
-use rusqlite::Connection;
+use rusqlite::{types::Type, Connection, Row};
 
-use crate::{config::db::DbConfig, domain::{user::{Credentials, MfaConfig, User}, user_api::UserApi}, error::errors::{QueryUserError, UserUpdateError}};
+use crate::{config::db::DbConfig, domain::{user::{Credentials, MfaConfig, Role, User}, user_api::UserApi}, error::errors::{QueryUserError, UserUpdateError}, service::secret_cipher::SecretCipher};
+
+const SELECT_USER: &str = "SELECT id, name, email, timezone, email_verified, session_version, (SELECT group_concat(role) FROM user_roles WHERE user_id = users.id), disabled FROM users";
 
 pub struct UserService {
-    db_config: Arc<DbConfig>
+    db_config: Arc<DbConfig>,
+    /// The TOTP secret is only stored encrypted
+    secret_cipher: Arc<SecretCipher>,
 }
 
 impl UserService {
-    pub fn new(db_config: Arc<DbConfig>) -> Self {
+    pub fn new(db_config: Arc<DbConfig>, secret_cipher: Arc<SecretCipher>) -> Self {
         Self {
-            db_config
+            db_config,
+            secret_cipher,
         }
     }
 
+    /// Encrypts TOTP secrets that were stored before encryption was introduced. Returns the number of
+    /// encrypted secrets, already encrypted ones are skipped.
+    pub async fn encrypt_plaintext_mfa_secrets(&self) -> Result<usize, UserUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        let secret_cipher = Arc::clone(&self.secret_cipher);
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            let plaintext_secrets = {
+                let mut stmt = tx.prepare("SELECT id, user_id, mfa_secret FROM credentials WHERE mfa_secret IS NOT NULL")?;
+                let rows = stmt.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?, row.get::<_, String>(2)?)))?
+                    .collect::<Result<Vec<(i32, i32, String)>, rusqlite::Error>>()?;
+                rows.into_iter()
+                    .filter(|(_, _, secret)| !SecretCipher::is_encrypted(secret))
+                    .collect::<Vec<(i32, i32, String)>>()
+            };
+
+            for (id, user_id, secret) in &plaintext_secrets {
+                let encrypted = secret_cipher.encrypt(*user_id, secret)
+                    .map_err(|e| UserUpdateError::new(&e.to_string()))?;
+                tx.execute("UPDATE credentials SET mfa_secret = ?1 WHERE id = ?2", (encrypted, id))?;
+            }
+            tx.commit()?;
+
+            Ok(plaintext_secrets.len())
+        }).await?
+    }
+
+    fn map_user(row: &Row) -> Result<User, rusqlite::Error> {
+        let mut user = User::new(row.get(0)?, row.get(2)?, row.get(1)?);
+        user.timezone = row.get(3)?;
+        user.email_verified = row.get(4)?;
+        user.session_version = row.get(5)?;
+        let roles: Option<String> = row.get(6)?;
+        user.roles = roles.unwrap_or_default().split(',').filter_map(Role::parse).collect();
+        user.disabled = row.get(7)?;
+        Ok(user)
+    }
+
     /// Utility method for password hashing
     pub fn hash_password(password: &str) -> Result<String, UserUpdateError> {
         let salt = SaltString::generate(&mut OsRng);
@@ -62,9 +107,7 @@ impl UserApi for UserService {
         tokio::task::spawn_blocking(move || {
             let conn = Connection::open(db)?;
 
-            Ok(conn.query_row("SELECT id, name, email FROM users WHERE email = ?1", [owned_email], |row| {
-                Ok(User::new(row.get(0)?, row.get(1)?, row.get(2)?))
-            })?)
+            Ok(conn.query_row(&format!("{} WHERE email = ?1", SELECT_USER), [owned_email], UserService::map_user)?)
         }).await?
     }
 
@@ -73,13 +116,47 @@ impl UserApi for UserService {
         tokio::task::spawn_blocking(move || {
             let conn = Connection::open(db)?;
 
-            Ok(conn.query_row("SELECT id, name, email FROM users WHERE id = ?1", [user_id], |row| {
-                Ok(User::new(row.get(0)?, row.get(1)?, row.get(2)?))
-            })?)
+            Ok(conn.query_row(&format!("{} WHERE id = ?1", SELECT_USER), [user_id], UserService::map_user)?)
         }).await?
     
     }
 
+    async fn find_users(&self, search: Option<&str>, offset: i64, limit: i64) -> Result<(Vec<User>, i64), QueryUserError> {
+        let db = self.db_config.get_database().to_owned();
+        // `%` and `_` in the search are no wildcards
+        let pattern = search.map(|search| format!("%{}%", search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")));
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let filter = "WHERE ?1 IS NULL OR email LIKE ?1 ESCAPE '\\' OR name LIKE ?1 ESCAPE '\\'";
+
+            let total: i64 = conn.query_row(&format!("SELECT count(*) FROM users {}", filter), [&pattern], |row| row.get(0))?;
+            let mut stmt = conn.prepare(&format!("{} {} ORDER BY email LIMIT ?2 OFFSET ?3", SELECT_USER, filter))?;
+            let users = stmt.query_map((&pattern, limit, offset), UserService::map_user)?
+                .collect::<Result<Vec<User>, rusqlite::Error>>()?;
+
+            Ok((users, total))
+        }).await?
+    }
+
+    async fn save_user(&self, user: User) -> Result<User, UserUpdateError> {
+        if user.id <= 0 {
+            return Err(UserUpdateError::new("Cannot save user without id"));
+        }
+
+        let db = self.db_config.get_database().to_owned();
+        let user_id = user.id;
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("UPDATE users SET name = ?1, email = ?2, timezone = ?3 WHERE id = ?4", (user.name, user.email, user.timezone, user.id))?;
+
+            Ok::<(), UserUpdateError>(())
+        }).await??;
+
+        self.find_by_id(user_id)
+            .await
+            .map_err(|_| UserUpdateError::new("Unable to retrieve user after update"))
+    }
+
     /// Takes in plain text password
     async fn save_user_with_credentials(&self, user: User, password: &str) -> Result<User, UserUpdateError> {
         let db = self.db_config.get_database().to_owned();
@@ -93,17 +170,18 @@ impl UserApi for UserService {
 
             let mut user_id = user.id;
             if user_id > 0 {
-                let update_user = "UPDATE users SET name = ?1, email =?2 WHERE id = ?3";
+                let update_user = "UPDATE users SET name = ?1, email =?2, timezone = ?3 WHERE id = ?4";
                 let update_creds = "UPDATE credentials SET password = ?1 WHERE user_id = ?2";
-                tx.execute(update_user, (user.name, user.email, user.id))?;
+                tx.execute(update_user, (user.name, user.email, user.timezone, user.id))?;
                 tx.execute(update_creds, (hashed_password.to_string(), user.id))?;
             } else {
-                let insert_user = "INSERT INTO users (name, email) values(?1, ?2)";
+                let insert_user = "INSERT INTO users (name, email, timezone) values(?1, ?2, ?3)";
                 let insert_creds = "INSERT INTO credentials (password, user_id) values(?1, ?2)";
-                tx.execute(insert_user, (user.name, user.email))?;
+                tx.execute(insert_user, (user.name, user.email, user.timezone))?;
 
                 user_id = tx.last_insert_rowid() as i32;
                 tx.execute(insert_creds, (hashed_password.to_string(), user_id))?;
+                tx.execute("INSERT INTO user_roles (user_id, role) values (?1, ?2)", (user_id, Role::User.as_str()))?;
             }
 
             tx.commit()?;
@@ -120,6 +198,31 @@ impl UserApi for UserService {
         
     }
 
+    async fn create_verified_user(&self, user: User, password: &str) -> Result<User, UserUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        let owned_pass = password.to_owned();
+
+        let user_id = tokio::task::spawn_blocking(move || {
+            let hashed_password = UserService::hash_password(&owned_pass)?;
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+
+            tx.execute("INSERT INTO users (name, email, timezone, email_verified) values(?1, ?2, ?3, 1)", (user.name, user.email, user.timezone))?;
+            let user_id = tx.last_insert_rowid() as i32;
+            tx.execute("INSERT INTO credentials (password, user_id) values(?1, ?2)", (hashed_password.to_string(), user_id))?;
+            for role in user.roles.iter().chain([&Role::User]) {
+                tx.execute("INSERT OR IGNORE INTO user_roles (user_id, role) values (?1, ?2)", (user_id, role.as_str()))?;
+            }
+            tx.commit()?;
+
+            Ok::<i32, UserUpdateError>(user_id)
+        }).await??;
+
+        self.find_by_id(user_id)
+            .await
+            .map_err(|_| UserUpdateError::new("Unable to retrieve user after update"))
+    }
+
     /// Expects that password is already hashed
     async fn save_credentials(&self, credentials: Credentials) -> Result<Credentials, UserUpdateError> {
         if credentials.user_id == 0 {
@@ -127,23 +230,23 @@ impl UserApi for UserService {
         } else {
             let db = self.db_config.get_database().to_owned();
 
-            let mfa_config = match credentials.mfa_config {
+            // without mfa both columns are NULL
+            let mfa_config: (Option<String>, Option<String>) = match credentials.mfa_config {
                 Some(mfa_config) => {
-                    let secret = match mfa_config.secret {
-                        Some(secret) => secret,
-                        None => "null".to_owned(),
-                    };
-
-                    (mfa_config.mfa_id, secret)
+                    let secret = mfa_config.secret
+                        .map(|secret| self.secret_cipher.encrypt(credentials.user_id, &secret))
+                        .transpose()
+                        .map_err(|e| UserUpdateError::new(&e.to_string()))?;
+                    (Some(mfa_config.mfa_id), secret)
                 },
-                None => todo!(),
+                None => (None, None),
             };
 
             let command = match credentials.id > 0 {
-                true => ("UPDATE credentials SET password = ?1, mfa_id = ?2, mfa_secret = ?3 WHERE id = ?4", 
-                    (credentials.password, mfa_config.0, mfa_config.1, credentials.id)),
-                false => ("INSERT INTO credentials (password, mfa_id, mfa_secret, user_id) values (?1, ?2, ?3, ?4)", 
-                    (credentials.password, mfa_config.0, mfa_config.1, credentials.user_id)),
+                true => ("UPDATE credentials SET password = ?1, mfa_id = ?2, mfa_secret = ?3, email_otp_enabled = ?4 WHERE id = ?5", 
+                    (credentials.password, mfa_config.0, mfa_config.1, credentials.email_otp_enabled, credentials.id)),
+                false => ("INSERT INTO credentials (password, mfa_id, mfa_secret, email_otp_enabled, user_id) values (?1, ?2, ?3, ?4, ?5)", 
+                    (credentials.password, mfa_config.0, mfa_config.1, credentials.email_otp_enabled, credentials.user_id)),
             };
 
             let exec: Result<(), rusqlite::Error> = tokio::task::spawn_blocking(move || {
@@ -161,14 +264,76 @@ impl UserApi for UserService {
         }   
     }
 
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), UserUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        let owned_pass = password.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let hashed_password = UserService::hash_password(&owned_pass)?;
+            let mut conn = Connection::open(db)?;
+
+            let tx = conn.transaction()?;
+            let changed = tx.execute("UPDATE credentials SET password = ?1 WHERE user_id = ?2", (hashed_password, user_id))?;
+            if changed == 0 {
+                return Err(UserUpdateError::new("No credentials found"));
+            }
+            tx.execute("UPDATE users SET session_version = session_version + 1 WHERE id = ?1", [user_id])?;
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn mark_email_verified(&self, user_id: i32) -> Result<(), UserUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            conn.execute("UPDATE users SET email_verified = 1 WHERE id = ?1", [user_id])?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn set_roles(&self, user_id: i32, roles: &[Role]) -> Result<(), UserUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        let roles = roles.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM user_roles WHERE user_id = ?1", [user_id])?;
+            for role in roles {
+                tx.execute("INSERT OR IGNORE INTO user_roles (user_id, role) values (?1, ?2)", (user_id, role.as_str()))?;
+            }
+            tx.commit()?;
+
+            Ok(())
+        }).await?
+    }
+
+    async fn set_disabled(&self, user_id: i32, disabled: bool) -> Result<(), UserUpdateError> {
+        let db = self.db_config.get_database().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+            let changed = conn.execute("UPDATE users SET disabled = ?1, session_version = session_version + 1 WHERE id = ?2", (disabled, user_id))?;
+            if changed == 0 {
+                return Err(UserUpdateError::new("User not found"));
+            }
+
+            Ok(())
+        }).await?
+    }
+
     async fn find_credentials_by_user_id(&self, user_id: i32) -> Result<Credentials, QueryUserError> {
         let db = self.db_config.get_database().to_owned();
+        let secret_cipher = Arc::clone(&self.secret_cipher);
         tokio::task::spawn_blocking(move || {
             let conn = Connection::open(db)?;
 
-            Ok(conn.query_row("SELECT id, password, mfa_id, mfa_secret, user_id FROM credentials WHERE user_id = ?1", [user_id], |row| {
+            Ok(conn.query_row("SELECT id, password, mfa_id, mfa_secret, user_id, email_otp_enabled FROM credentials WHERE user_id = ?1", [user_id], |row| {
                 let mfa_id: Option<String> = row.get(2)?;
-                let mfa_secret: Option<String> = row.get(3)?;
+                let mfa_secret: Option<String> = row.get::<_, Option<String>>(3)?
+                    .map(|secret: String| secret_cipher.decrypt(user_id, &secret))
+                    .transpose()
+                    .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, Type::Text, Box::new(e)))?;
 
                 let mut mfa_config = None;
                 if let Some(mfa_id) = mfa_id {
@@ -180,6 +345,7 @@ impl UserApi for UserService {
                 }
 
                 let mut creds = Credentials::new(row.get(0)?, row.get(1)?, row.get(4)?);
+                creds.email_otp_enabled = row.get(5)?;
                 if let Some(mfa_config) = mfa_config {
                     creds.set_mfa(mfa_config);
                 }
@@ -194,8 +360,11 @@ impl UserApi for UserService {
 mod user_service_tests {
     use std::sync::Arc;
 
-    use crate::{config::db::DbConfig, create_db, domain::{user::{MfaConfig, User}, user_api::UserApi}, service::user_service::UserService};
+    use crate::{config::db::DbConfig, create_db, domain::{user::{MfaConfig, Role, User}, user_api::UserApi}, error::errors::UserUpdateError, service::{secret_cipher::SecretCipher, user_service::UserService}};
 
+    fn create_user_service(db_config: DbConfig) -> UserService {
+        UserService::new(Arc::new(db_config), Arc::new(SecretCipher::new(&SecretCipher::generate_key()).unwrap()))
+    }
 
     #[tokio::test]
     async fn should_be_able_to_save_credentials() {
@@ -204,7 +373,7 @@ mod user_service_tests {
         let _db = create_db(&db_config);
 
         // Arrange
-        let user_service = UserService::new(Arc::new(db_config));
+        let user_service = create_user_service(db_config);
         let user = User::new(0, "test@example.org".to_owned(), "Test User".to_owned());
         let saved_user = user_service.save_user_with_credentials(user, "secretpassword").await.unwrap();
 
@@ -224,4 +393,128 @@ mod user_service_tests {
         assert_eq!(mfa_config.secret.unwrap(), "asecret");
     }
 
+    #[tokio::test]
+    async fn should_store_mfa_secret_encrypted() {
+        let db_config = DbConfig::new("file:user_service_encrypt_test?mode=memory&cache=shared");
+        let db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+        let user = User::new(0, "test@example.org".to_owned(), "Test User".to_owned());
+        let saved_user = user_service.save_user_with_credentials(user, "secretpassword").await.unwrap();
+
+        // a secret from before the encryption
+        db.execute("UPDATE credentials SET mfa_id = 'MFA_ID', mfa_secret = 'asecret' WHERE user_id = ?1", [saved_user.id]).unwrap();
+        assert_eq!(user_service.encrypt_plaintext_mfa_secrets().await.unwrap(), 1);
+        assert_eq!(user_service.encrypt_plaintext_mfa_secrets().await.unwrap(), 0);
+
+        let stored: String = db.query_row("SELECT mfa_secret FROM credentials WHERE user_id = ?1", [saved_user.id], |row| row.get(0)).unwrap();
+        assert_ne!(stored, "asecret");
+        let creds = user_service.find_credentials_by_user_id(saved_user.id).await.unwrap();
+        assert_eq!(creds.mfa_config.unwrap().secret.unwrap(), "asecret");
+    }
+
+    #[tokio::test]
+    async fn should_be_able_to_remove_mfa() {
+        let db_config = DbConfig::new("file:user_service_remove_mfa_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+        let user = User::new(0, "test@example.org".to_owned(), "Test User".to_owned());
+        let saved_user = user_service.save_user_with_credentials(user, "secretpassword").await.unwrap();
+
+        let mut creds = user_service.find_credentials_by_user_id(saved_user.id).await.unwrap();
+        creds.set_mfa(MfaConfig::with_secret("MFA_ID", "asecret"));
+        let mut creds = user_service.save_credentials(creds).await.unwrap();
+
+        creds.mfa_config = None;
+        user_service.save_credentials(creds).await.unwrap();
+
+        assert!(user_service.find_credentials_by_user_id(saved_user.id).await.unwrap().mfa_config.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_reject_duplicate_email() {
+        let db_config = DbConfig::new("file:user_service_duplicate_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+
+        let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        user_service.save_user_with_credentials(user.clone(), "secretpassword").await.unwrap();
+
+        let result = user_service.save_user_with_credentials(user, "otherpassword").await;
+
+        assert!(matches!(result, Err(UserUpdateError::DuplicateEmail)));
+    }
+
+    #[tokio::test]
+    async fn should_end_sessions_when_password_is_updated() {
+        let db_config = DbConfig::new("file:user_service_password_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+
+        let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        let user = user_service.save_user_with_credentials(user, "secretpassword").await.unwrap();
+        let old_hash = user_service.find_credentials_by_user_id(user.id).await.unwrap().password;
+
+        user_service.update_password(user.id, "newsecretpassword").await.unwrap();
+
+        assert_ne!(user_service.find_credentials_by_user_id(user.id).await.unwrap().password, old_hash);
+        assert_eq!(user_service.find_by_id(user.id).await.unwrap().session_version, user.session_version + 1);
+    }
+
+    #[tokio::test]
+    async fn should_save_roles() {
+        let db_config = DbConfig::new("file:user_service_roles_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+
+        let user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        let user = user_service.save_user_with_credentials(user, "secretpassword").await.unwrap();
+        assert_eq!(user.roles, vec![Role::User]);
+
+        user_service.set_roles(user.id, &[Role::User, Role::Admin]).await.unwrap();
+
+        assert!(user_service.find_by_id(user.id).await.unwrap().has_role(Role::Admin));
+    }
+
+    #[tokio::test]
+    async fn should_create_verified_user_with_roles() {
+        let db_config = DbConfig::new("file:user_service_create_verified_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+
+        let mut user = User::new(0, "linda@example.org".to_owned(), "Linda".to_owned());
+        user.roles = vec![Role::Admin];
+        let user = user_service.create_verified_user(user, "secretpassword").await.unwrap();
+
+        assert!(user.email_verified);
+        assert!(user.has_role(Role::Admin));
+        assert!(user.has_role(Role::User));
+
+        let duplicate = User::new(0, "linda@example.org".to_owned(), "Other".to_owned());
+        let result = user_service.create_verified_user(duplicate, "secretpassword").await;
+        assert!(matches!(result, Err(UserUpdateError::DuplicateEmail)));
+        assert_eq!(user_service.find_users(None, 0, 10).await.unwrap().1, 1);
+    }
+
+    #[tokio::test]
+    async fn should_find_users_by_search() {
+        let db_config = DbConfig::new("file:user_service_find_users_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let user_service = create_user_service(db_config);
+
+        for (email, name) in [("linda@example.org", "Linda"), ("hans@example.org", "Hans"), ("lin_2@example.org", "Lin")] {
+            user_service.save_user_with_credentials(User::new(0, email.to_owned(), name.to_owned()), "secretpassword").await.unwrap();
+        }
+
+        let (users, total) = user_service.find_users(Some("lin"), 0, 1).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].email, "lin_2@example.org");
+
+        let (users, total) = user_service.find_users(Some("_"), 0, 10).await.unwrap();
+        assert_eq!(total, 1, "`_` must not be a wildcard");
+        assert_eq!(users[0].name, "Lin");
+
+        let (_, total) = user_service.find_users(None, 0, 10).await.unwrap();
+        assert_eq!(total, 3);
+    }
 }
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{config::db::DbConfig, domain::{user_token::TokenPurpose, user_token_api::UserTokenApi}, error::errors::UserTokenError, service::secure_token};
+
+pub struct UserTokenService {
+    db_config: Arc<DbConfig>
+}
+
+impl UserTokenService {
+    pub fn new(db_config: Arc<DbConfig>) -> Self {
+        Self {
+            db_config
+        }
+    }
+}
+
+#[async_trait]
+impl UserTokenApi for UserTokenService {
+    async fn create_token(&self, user_id: i32, purpose: TokenPurpose) -> Result<String, UserTokenError> {
+        let db = self.db_config.get_database().to_owned();
+        let token = secure_token::generate_token();
+        let token_hash = secure_token::hash_token(&token);
+        let expires_at = Utc::now() + purpose.validity();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(db)?;
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM user_tokens WHERE user_id = ?1 AND purpose = ?2", (user_id, purpose.as_str()))?;
+            tx.execute("INSERT INTO user_tokens (token_hash, user_id, purpose, expires_at) values (?1, ?2, ?3, ?4)", (token_hash, user_id, purpose.as_str(), expires_at))?;
+            tx.commit()?;
+
+            Ok::<(), UserTokenError>(())
+        }).await??;
+
+        Ok(token)
+    }
+
+    async fn consume_token(&self, token: &str, purpose: TokenPurpose) -> Result<Option<i32>, UserTokenError> {
+        let db = self.db_config.get_database().to_owned();
+        let token_hash = secure_token::hash_token(token);
+        tokio::task::spawn_blocking(move || {
+            let conn = Connection::open(db)?;
+
+            let found: Option<(i32, DateTime<Utc>)> = conn.query_row(
+                "DELETE FROM user_tokens WHERE token_hash = ?1 AND purpose = ?2 RETURNING user_id, expires_at",
+                (token_hash, purpose.as_str()),
+                |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?;
+
+            Ok(found
+                .filter(|(_, expires_at)| *expires_at > Utc::now())
+                .map(|(user_id, _)| user_id))
+        }).await?
+    }
+}
+
+
+#[cfg(test)]
+mod user_token_service_tests {
+    use std::sync::Arc;
+
+    use crate::{config::db::DbConfig, create_db, domain::{user_token::TokenPurpose, user_token_api::UserTokenApi}};
+
+    use super::UserTokenService;
+
+    #[tokio::test]
+    async fn should_accept_token_only_once() {
+        let db_config = DbConfig::new("file:user_token_service_test?mode=memory&cache=shared");
+        let _db = create_db(&db_config);
+        let token_service = UserTokenService::new(Arc::new(db_config));
+
+        let revoked = token_service.create_token(1, TokenPurpose::EmailVerification).await.unwrap();
+        let token = token_service.create_token(1, TokenPurpose::EmailVerification).await.unwrap();
+
+        assert_eq!(token_service.consume_token(&revoked, TokenPurpose::EmailVerification).await.unwrap(), None);
+        assert_eq!(token_service.consume_token(&token, TokenPurpose::EmailVerification).await.unwrap(), Some(1));
+        assert_eq!(token_service.consume_token(&token, TokenPurpose::EmailVerification).await.unwrap(), None);
+    }
+}